@@ -1,4 +1,4 @@
-use crate::types::{Consensus, Info, Mempool, Snapshot, State};
+use crate::types::{Consensus, Info, Mempool, Receipts, Snapshot, State};
 use revm::db::{CacheDB, EmptyDB};
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -18,24 +18,23 @@ impl Default for App<CacheDB<EmptyDB>> {
 
 impl App<CacheDB<EmptyDB>> {
     pub fn new() -> Self {
-        let state = State {
-            db: CacheDB::new(EmptyDB()),
-            block_height: Default::default(),
-            app_hash: Default::default(),
-            env: Default::default(),
-        };
+        let state = State::default();
 
         let committed_state = Arc::new(Mutex::new(state.clone()));
         let current_state = Arc::new(Mutex::new(state));
 
+        let receipts = Arc::new(Mutex::new(Receipts::default()));
+
         let consensus = Consensus {
             committed_state: committed_state.clone(),
             current_state,
+            receipts: receipts.clone(),
         };
 
         let mempool = Mempool::default();
         let info = Info {
             state: committed_state,
+            receipts,
         };
         let snapshot = Snapshot::default();
 
@@ -8,10 +8,14 @@ use abci::{
     async_trait,
     types::*,
 };
-use anyhow::{bail, Result};
+use anyhow::{anyhow, bail, Result};
 use ethers::abi::AbiDecode;
 use ethers::prelude::NameOrAddress;
-use ethers::types::{Address, TransactionRequest};
+use ethers::types::{
+    transaction::eip2718::TypedTransaction, Address, BlockNumber, Bytes, Filter, FilterBlockOption,
+    Log, TransactionReceipt, TransactionRequest, ValueOrArray, H256, U64,
+};
+use ethers::utils::{keccak256, rlp::Rlp};
 use futures::executor;
 use revm::primitives::{AccountInfo, Bytecode, CreateScheme, TransactTo, B160, U256};
 use revm::{
@@ -21,6 +25,7 @@ use revm::{
     Database, DatabaseCommit,
 };
 use revm::{db::DatabaseRef, primitives::Output};
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use ursa_utils::evm::epoch_manager::{SignalEpochChangeReturn, EPOCH_ADDRESS};
@@ -31,6 +36,68 @@ pub struct State<Db> {
     pub app_hash: Vec<u8>,
     pub db: Db,
     pub env: Env,
+    /// Receipts of the transactions executed in the current block, moved to the
+    /// [`Receipts`] on commit.
+    pub block_receipts: Vec<TransactionReceipt>,
+    /// Gas used by the transactions of the current block.
+    pub block_gas_used: u64,
+}
+
+/// Number of receipts kept for `eth_getTransactionReceipt` and `eth_getLogs`.
+pub const MAX_RECEIPTS: usize = 100_000;
+
+/// Receipts of the committed transactions, oldest pruned past [`MAX_RECEIPTS`].
+///
+/// Kept outside of [`State`], which is cloned on every commit.
+#[derive(Debug)]
+pub struct Receipts {
+    receipts: HashMap<H256, TransactionReceipt>,
+    /// Hashes in execution order.
+    order: VecDeque<H256>,
+    capacity: usize,
+}
+
+impl Default for Receipts {
+    fn default() -> Self {
+        Self::new(MAX_RECEIPTS)
+    }
+}
+
+impl Receipts {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            receipts: HashMap::new(),
+            order: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn get(&self, hash: &H256) -> Option<&TransactionReceipt> {
+        self.receipts.get(hash)
+    }
+
+    /// Add the receipts of a committed block, pruning the oldest ones.
+    pub fn extend(&mut self, receipts: impl IntoIterator<Item = TransactionReceipt>) {
+        for receipt in receipts {
+            let hash = receipt.transaction_hash;
+            if self.receipts.insert(hash, receipt).is_none() {
+                self.order.push_back(hash);
+            }
+        }
+        while self.order.len() > self.capacity {
+            if let Some(hash) = self.order.pop_front() {
+                self.receipts.remove(&hash);
+            }
+        }
+    }
+
+    /// Logs of the kept receipts, in execution order.
+    pub fn logs(&self) -> impl Iterator<Item = &Log> {
+        self.order
+            .iter()
+            .filter_map(|hash| self.receipts.get(hash))
+            .flat_map(|receipt| receipt.logs.iter())
+    }
 }
 
 pub trait WithGenesisDb {
@@ -51,11 +118,94 @@ impl Default for State<CacheDB<EmptyDB>> {
             app_hash: Vec::new(),
             db: CacheDB::new(EmptyDB()),
             env: Default::default(),
+            block_receipts: Vec::new(),
+            block_gas_used: 0,
+        }
+    }
+}
+
+/// Decode a transaction delivered by consensus, either a signed RLP encoded transaction or
+/// an unsigned `TransactionRequest` encoded as json. Returns the hash of the transaction
+/// with the request.
+///
+/// Only signed transactions have a sender, recovered from their signature. Unsigned ones are
+/// the calls of the nodes themselves, executed from the zero address, and are rejected if
+/// they name a sender since nothing proves they come from it.
+///
+/// Signed transactions have their Ethereum hash. Unsigned ones have none, they are hashed
+/// from their signing hash and the zero address, so the hash doesn't depend on how the json
+/// is written.
+pub fn decode_transaction(bytes: &[u8]) -> Result<(H256, TransactionRequest)> {
+    if let Ok(tx) = serde_json::from_slice::<TransactionRequest>(bytes) {
+        if tx.from.is_some() {
+            bail!("unsigned transactions can't have a sender, send it signed");
         }
+        let hash = keccak256([tx.sighash().as_bytes(), Address::zero().as_bytes()].concat());
+        return Ok((H256::from(hash), tx));
     }
+
+    let (tx, signature) = TypedTransaction::decode_signed(&Rlp::new(bytes))
+        .map_err(|e| anyhow!("could not decode transaction: {e}"))?;
+    let hash = tx.hash(&signature);
+    let from = signature.recover(tx.sighash())?;
+    let tx = TransactionRequest {
+        from: Some(from),
+        to: tx.to().cloned(),
+        gas: tx.gas().copied(),
+        gas_price: tx.gas_price(),
+        value: tx.value().copied(),
+        data: tx.data().cloned(),
+        nonce: tx.nonce().copied(),
+        chain_id: tx.chain_id(),
+    };
+    Ok((hash, tx))
+}
+
+/// Whether `log` of a block at most `latest` is selected by `filter`.
+fn filter_matches(filter: &Filter, log: &Log, latest: u64) -> bool {
+    let block = |number: Option<BlockNumber>| match number {
+        Some(BlockNumber::Number(number)) => number.as_u64(),
+        Some(BlockNumber::Earliest) => 0,
+        _ => latest,
+    };
+    let (from, to) = match &filter.block_option {
+        FilterBlockOption::Range {
+            from_block,
+            to_block,
+        } => (block(*from_block), block(*to_block)),
+        // blocks don't have hashes
+        FilterBlockOption::AtBlockHash(_) => return false,
+    };
+    let number = log.block_number.unwrap_or_default().as_u64();
+    if number < from || number > to {
+        return false;
+    }
+
+    let address = match &filter.address {
+        None => true,
+        Some(ValueOrArray::Value(address)) => *address == log.address,
+        Some(ValueOrArray::Array(addresses)) => {
+            addresses.is_empty() || addresses.contains(&log.address)
+        }
+    };
+    let topics = filter.topics.iter().enumerate().all(|(i, topic)| {
+        let actual = log.topics.get(i);
+        match topic {
+            None | Some(ValueOrArray::Value(None)) => true,
+            Some(ValueOrArray::Value(Some(topic))) => actual == Some(topic),
+            Some(ValueOrArray::Array(topics)) => {
+                topics.is_empty()
+                    || topics
+                        .iter()
+                        .any(|topic| topic.is_none() || actual == topic.as_ref())
+            }
+        }
+    });
+    address && topics
 }
 
 impl<Db: DatabaseCommit + Database> State<Db> {
+    /// Execute `tx`, checking its nonce against the sender's when it has one.
     async fn execute(
         &mut self,
         tx: TransactionRequest,
@@ -74,7 +224,7 @@ impl<Db: DatabaseCommit + Database> State<Db> {
             },
             data: tx.data.clone().unwrap_or_default().0,
             chain_id: Some(self.env.cfg.chain_id.try_into().unwrap()),
-            nonce: None,
+            nonce: tx.nonce.map(|nonce| nonce.as_u64()),
             value: tx.value.unwrap_or_default().into(),
             gas_price: tx.gas_price.unwrap_or_default().into(),
             gas_priority_fee: Some(tx.gas_price.unwrap_or_default().into()),
@@ -85,18 +235,80 @@ impl<Db: DatabaseCommit + Database> State<Db> {
 
         let results = match evm.transact() {
             Ok(data) => data,
-            Err(_) => bail!("theres an err"),
+            Err(e) => bail!("could not execute transaction: {e:?}"),
         };
         if !read_only {
             self.db.commit(results.state);
         };
         Ok(results.result)
     }
+
+    /// Record the receipt and logs of a transaction executed in the current block.
+    fn record_receipt(&mut self, hash: H256, tx: &TransactionRequest, result: &ExecutionResult) {
+        let block_number = Some(U64::from(self.block_height as u64 + 1));
+        let transaction_index = U64::from(self.block_receipts.len());
+        let block_logs: usize = self
+            .block_receipts
+            .iter()
+            .map(|receipt| receipt.logs.len())
+            .sum();
+        self.block_gas_used += result.gas_used();
+
+        let logs: Vec<Log> = match result {
+            ExecutionResult::Success { logs, .. } => logs
+                .iter()
+                .enumerate()
+                .map(|(i, log)| Log {
+                    address: Address::from(log.address.0),
+                    topics: log.topics.iter().map(|topic| H256::from(topic.0)).collect(),
+                    data: log.data.clone().into(),
+                    block_number,
+                    transaction_hash: Some(hash),
+                    transaction_index: Some(transaction_index),
+                    log_index: Some((block_logs + i).into()),
+                    removed: Some(false),
+                    ..Default::default()
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let contract_address = match result {
+            ExecutionResult::Success {
+                output: Output::Create(_, Some(address)),
+                ..
+            } => Some(Address::from(address.0)),
+            _ => None,
+        };
+        let receipt = TransactionReceipt {
+            transaction_hash: hash,
+            transaction_index,
+            block_number,
+            from: tx.from.unwrap_or_default(),
+            to: tx.to.as_ref().and_then(|to| to.as_address().copied()),
+            cumulative_gas_used: self.block_gas_used.into(),
+            gas_used: Some(result.gas_used().into()),
+            contract_address,
+            logs,
+            status: Some(U64::from(result.is_success() as u64)),
+            effective_gas_price: Some(tx.gas_price.unwrap_or_default()),
+            ..Default::default()
+        };
+        self.block_receipts.push(receipt);
+    }
+
+    /// The account nonce of `address`.
+    fn nonce(&mut self, address: Address) -> Result<u64> {
+        match self.db.basic(address.to_fixed_bytes().into()) {
+            Ok(info) => Ok(info.unwrap_or_default().nonce),
+            Err(_) => bail!("could not load account {address:?}"),
+        }
+    }
 }
 
 pub struct Consensus<Db> {
     pub committed_state: Arc<Mutex<State<Db>>>,
     pub current_state: Arc<Mutex<State<Db>>>,
+    pub receipts: Arc<Mutex<Receipts>>,
 }
 
 impl<Db: Clone> Consensus<Db> {
@@ -107,10 +319,21 @@ impl<Db: Clone> Consensus<Db> {
         Consensus {
             committed_state,
             current_state,
+            receipts: Default::default(),
         }
     }
 }
 
+/// Response to a transaction that wasn't executed.
+fn rejected(message: &str) -> ResponseDeliverTx {
+    ResponseDeliverTx {
+        code: 1,
+        data: message.into(),
+        log: message.to_string(),
+        ..Default::default()
+    }
+}
+
 #[async_trait]
 impl<Db: AbciDb> ConsensusTrait for Consensus<Db> {
     #[tracing::instrument(skip(self))]
@@ -179,16 +402,46 @@ impl<Db: AbciDb> ConsensusTrait for Consensus<Db> {
         tracing::trace!("delivering tx");
         let mut state = self.current_state.lock().await;
 
-        let mut tx: TransactionRequest = match serde_json::from_slice(&deliver_tx_request.tx) {
+        let (hash, mut tx) = match decode_transaction(&deliver_tx_request.tx) {
             Ok(tx) => tx,
-            Err(_) => {
-                tracing::error!("could not decode request");
-                return ResponseDeliverTx {
-                    data: "could not decode request".into(),
-                    ..Default::default()
-                };
+            Err(err) => {
+                tracing::error!("could not decode request: {err:?}");
+                return rejected("could not decode request");
             }
         };
+        let chain_id: u64 = state.env.cfg.chain_id.try_into().unwrap();
+        if tx.chain_id.map_or(false, |id| id.as_u64() != chain_id) {
+            tracing::error!("transaction {hash:?} is for another chain");
+            return rejected("wrong chain id");
+        }
+
+        // Transactions of a sender must use its next nonce, so they can't be replayed.
+        // Transactions without a sender are the calls of the nodes themselves, such as the
+        // epoch change signals, executed from the zero address.
+        if let Some(from) = tx.from {
+            let nonce = match tx.nonce {
+                Some(nonce) => nonce.as_u64(),
+                None => {
+                    tracing::error!("transaction {hash:?} has no nonce");
+                    return rejected("missing nonce");
+                }
+            };
+            match state.nonce(from) {
+                Ok(expected) if nonce < expected => {
+                    tracing::error!("transaction {hash:?} reuses nonce {nonce} of {from:?}");
+                    return rejected("nonce too low");
+                }
+                Ok(expected) if nonce > expected => {
+                    tracing::error!("transaction {hash:?} has nonce {nonce}, expected {expected}");
+                    return rejected("nonce too high");
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    tracing::error!("could not check the nonce of {hash:?}: {err:?}");
+                    return rejected("could not check nonce");
+                }
+            }
+        }
 
         let mut to_epoch_contract: bool = false;
         // Resolve the `to`.
@@ -200,10 +453,17 @@ impl<Db: AbciDb> ConsensusTrait for Consensus<Db> {
                 tx.to = Some(addr.into())
             }
             None => (),
-            _ => panic!("not an address"),
+            _ => return rejected("not an address"),
         };
 
-        let result = state.execute(tx, false).await.unwrap();
+        let result = match state.execute(tx.clone(), false).await {
+            Ok(result) => result,
+            Err(err) => {
+                tracing::error!("could not execute {hash:?}: {err:?}");
+                return rejected("could not execute transaction");
+            }
+        };
+        state.record_receipt(hash, &tx, &result);
         tracing::trace!("executed tx");
 
         if to_epoch_contract {
@@ -243,9 +503,13 @@ impl<Db: AbciDb> ConsensusTrait for Consensus<Db> {
     #[tracing::instrument(skip(self))]
     async fn commit(&self, _commit_request: RequestCommit) -> ResponseCommit {
         tracing::trace!("taking lock");
-        let current_state = self.current_state.lock().await.clone();
+        let mut current_state = self.current_state.lock().await;
+        let block_receipts = std::mem::take(&mut current_state.block_receipts);
+        current_state.block_gas_used = 0;
+        let current_state = current_state.clone();
         let mut committed_state = self.committed_state.lock().await;
         *committed_state = current_state;
+        self.receipts.lock().await.extend(block_receipts);
         tracing::trace!("committed");
 
         ResponseCommit {
@@ -274,13 +538,23 @@ impl MempoolTrait for Mempool {
 #[derive(Debug, Clone)]
 pub struct Info<Db> {
     pub state: Arc<Mutex<State<Db>>>,
+    pub receipts: Arc<Mutex<Receipts>>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 #[allow(clippy::large_enum_variant)]
 pub enum Query {
+    /// Answered with the output of the call, or an error message.
     EthCall(TransactionRequest),
     Balance(Address),
+    /// Execute a transaction without committing it, answered with `QueryResponse::Tx`.
+    Execute(TransactionRequest),
+    Code(Address),
+    Nonce(Address),
+    Receipt(H256),
+    Logs(Filter),
+    BlockNumber,
+    ChainId,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -288,6 +562,12 @@ pub enum Query {
 pub enum QueryResponse {
     Tx(ExecutionResult),
     Balance(U256),
+    Code(Bytes),
+    Nonce(u64),
+    Receipt(Option<TransactionReceipt>),
+    Logs(Vec<Log>),
+    BlockNumber(i64),
+    ChainId(u64),
 }
 
 impl QueryResponse {
@@ -356,6 +636,57 @@ impl<Db: Send + Sync + Database + DatabaseCommit> InfoTrait for Info<Db> {
                 }
                 _ => "error retrieving balance".into(),
             },
+            Query::Execute(tx) => match state.execute(tx, true).await {
+                Ok(result) => serde_json::to_vec(&QueryResponse::Tx(result)).unwrap(),
+                Err(_) => "error executing transaction".into(),
+            },
+            Query::Code(address) => match state.db.basic(address.to_fixed_bytes().into()) {
+                Ok(info) => {
+                    let info = info.unwrap_or_default();
+                    let code = match info.code {
+                        Some(code) => Ok(code),
+                        None => state.db.code_by_hash(info.code_hash),
+                    };
+                    match code {
+                        Ok(code) => {
+                            let res = QueryResponse::Code(code.original_bytes().into());
+                            serde_json::to_vec(&res).unwrap()
+                        }
+                        _ => "error retrieving code".into(),
+                    }
+                }
+                _ => "error retrieving code".into(),
+            },
+            Query::Nonce(address) => match state.db.basic(address.to_fixed_bytes().into()) {
+                Ok(info) => {
+                    let res = QueryResponse::Nonce(info.unwrap_or_default().nonce);
+                    serde_json::to_vec(&res).unwrap()
+                }
+                _ => "error retrieving nonce".into(),
+            },
+            Query::Receipt(hash) => {
+                let receipt = self.receipts.lock().await.get(&hash).cloned();
+                serde_json::to_vec(&QueryResponse::Receipt(receipt)).unwrap()
+            }
+            Query::Logs(filter) => {
+                let latest = state.block_height as u64;
+                let logs = self
+                    .receipts
+                    .lock()
+                    .await
+                    .logs()
+                    .filter(|log| filter_matches(&filter, log, latest))
+                    .cloned()
+                    .collect();
+                serde_json::to_vec(&QueryResponse::Logs(logs)).unwrap()
+            }
+            Query::BlockNumber => {
+                serde_json::to_vec(&QueryResponse::BlockNumber(state.block_height)).unwrap()
+            }
+            Query::ChainId => {
+                let chain_id = state.env.cfg.chain_id.try_into().unwrap();
+                serde_json::to_vec(&QueryResponse::ChainId(chain_id)).unwrap()
+            }
         };
 
         ResponseQuery {
@@ -1,7 +1,7 @@
 use anyhow::{bail, Result};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::{mpsc, oneshot, Notify};
+use tokio::sync::{broadcast, mpsc, oneshot, Notify};
 use tokio::{pin, select, time};
 use tracing::warn;
 use ursa_utils::shutdown::ShutdownController;
@@ -34,6 +34,8 @@ pub struct Engine {
     rx_abci_queries: mpsc::Receiver<(oneshot::Sender<ResponseQuery>, AbciQueryQuery)>,
     rx_certificates: mpsc::Receiver<Vec<Batch>>,
     reconfigure_notifier: Arc<Notify>,
+    /// Publishes the height of every committed block.
+    tx_new_blocks: broadcast::Sender<i64>,
 }
 
 impl Engine {
@@ -52,6 +54,7 @@ impl Engine {
         let (tx_abci_queries, rx_abci_queries) = mpsc::channel(CHANNEL_CAPACITY);
         let (tx_certificates, rx_certificates) = mpsc::channel(CHANNEL_CAPACITY);
         let reconfigure_notifier = Arc::new(Notify::new());
+        let (tx_new_blocks, _) = broadcast::channel(CHANNEL_CAPACITY);
 
         // Instantiate a new client to not be locked in an Info connection.
         let client = ClientBuilder::default().connect(app_address).unwrap();
@@ -66,6 +69,7 @@ impl Engine {
             rx_abci_queries,
             rx_certificates,
             reconfigure_notifier,
+            tx_new_blocks,
         }
     }
 
@@ -104,6 +108,10 @@ impl Engine {
         self.reconfigure_notifier.clone()
     }
 
+    pub fn get_new_blocks_sender(&self) -> broadcast::Sender<i64> {
+        self.tx_new_blocks.clone()
+    }
+
     /// On each new certificate, increment the block height to proposed and run through the
     /// BeginBlock -> DeliverTx for each tx in the certificate -> EndBlock -> Commit event loop.
     fn handle_cert(&mut self, batch: Vec<Batch>) -> Result<()> {
@@ -120,6 +128,9 @@ impl Engine {
         self.end_block(proposed_block_height)?;
         self.commit()?;
 
+        // Sending only fails when nobody is subscribed.
+        let _ = self.tx_new_blocks.send(proposed_block_height);
+
         if change_epoch {
             self.reconfigure_notifier.notify_waiters();
         }
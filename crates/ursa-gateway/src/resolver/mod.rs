@@ -149,7 +149,9 @@ impl Resolver {
         }
     }
 
-    pub async fn resolve_content(&self, cid: &str) -> Result<Response<Body>, Error> {
+    /// Fetch `path` under the root `cid` from the closest node providing `cid`.
+    /// `path` is either empty or starts with a `/` or `?`, and may carry the query string.
+    pub async fn resolve_content(&self, cid: &str, path: &str) -> Result<Response<Body>, Error> {
         let endpoint = format!("{}/{cid}", self.indexer_cid_url);
 
         let uri = endpoint.parse::<Uri>().map_err(|e| {
@@ -243,7 +245,7 @@ impl Resolver {
         debug!("Provider addresses to query: {:?}", providers.neighbors);
 
         while let Some(addr) = providers.neighbors.next() {
-            let endpoint = format!("{addr}/ursa/v0/{cid}");
+            let endpoint = format!("{addr}/ursa/v0/{cid}{path}");
             let uri = match endpoint.parse::<Uri>() {
                 Ok(uri) => uri,
                 Err(e) => {
@@ -268,7 +270,7 @@ impl Resolver {
         }
 
         while let Some(addr) = providers.outsiders.next() {
-            let endpoint = format!("{addr}/ursa/v0/{cid}");
+            let endpoint = format!("{addr}/ursa/v0/{cid}{path}");
             let uri = match endpoint.parse::<Uri>() {
                 Ok(uri) => uri,
                 Err(e) => {
@@ -47,7 +47,9 @@ use crate::{
     resolver::Resolver,
     server::{
         model::HttpResponse,
-        route::api::v1::get::{check_car_handler, get_car_handler},
+        route::api::v1::get::{
+            check_car_handler, check_car_path_handler, get_car_handler, get_car_path_handler,
+        },
     },
     util::error::Error,
 };
@@ -122,6 +124,8 @@ pub async fn start(config: Arc<RwLock<GatewayConfig>>, shutdown_rx: Receiver<()>
             .route("/:cid", get(get_car_handler)) // ursa gateway
             .route("/ipfs/:cid", get(get_car_handler)) // ipfs gateway specs
             .route("/ipfs/:cid", head(check_car_handler)) // ipfs gateway specs
+            .route("/ipfs/:cid/*path", get(get_car_path_handler))
+            .route("/ipfs/:cid/*path", head(check_car_path_handler))
             .layer(Extension(resolver))
             .layer(CatchPanicLayer::custom(recover))
             .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
@@ -2,7 +2,7 @@ use std::{str::FromStr, sync::Arc};
 
 use axum::{
     extract::Path,
-    http::StatusCode,
+    http::{StatusCode, Uri},
     response::{IntoResponse, Response},
     Extension, Json,
 };
@@ -14,23 +14,53 @@ use crate::{resolver::Resolver, server::model::HttpResponse, util::error::Error}
 
 pub async fn check_car_handler(
     Path(cid): Path<String>,
+    uri: Uri,
     Extension(resolver): Extension<Arc<Resolver>>,
 ) -> StatusCode {
+    check_content(cid, &uri, resolver).await
+}
+
+pub async fn get_car_handler(
+    Path(cid): Path<String>,
+    uri: Uri,
+    Extension(resolver): Extension<Arc<Resolver>>,
+) -> Response {
+    get_content(cid, &uri, resolver).await
+}
+
+pub async fn check_car_path_handler(
+    Path((cid, _)): Path<(String, String)>,
+    uri: Uri,
+    Extension(resolver): Extension<Arc<Resolver>>,
+) -> StatusCode {
+    check_content(cid, &uri, resolver).await
+}
+
+pub async fn get_car_path_handler(
+    Path((cid, _)): Path<(String, String)>,
+    uri: Uri,
+    Extension(resolver): Extension<Arc<Resolver>>,
+) -> Response {
+    get_content(cid, &uri, resolver).await
+}
+
+async fn check_content(cid: String, uri: &Uri, resolver: Arc<Resolver>) -> StatusCode {
     let span = info_span!("Check car handler");
     if Cid::from_str(&cid).is_err() {
         return StatusCode::BAD_REQUEST;
     };
-    match resolver.resolve_content(&cid).instrument(span).await {
+    match resolver
+        .resolve_content(&cid, suffix(uri, &cid))
+        .instrument(span)
+        .await
+    {
         Ok(resp) => resp.status(),
         Err(Error::Internal(_)) => StatusCode::INTERNAL_SERVER_ERROR,
         Err(Error::Upstream(status, _)) => status,
     }
 }
 
-pub async fn get_car_handler(
-    Path(cid): Path<String>,
-    Extension(resolver): Extension<Arc<Resolver>>,
-) -> Response {
+async fn get_content(cid: String, uri: &Uri, resolver: Arc<Resolver>) -> Response {
     let span = info_span!("Get car handler");
     if Cid::from_str(&cid).is_err() {
         return error_handler(
@@ -40,7 +70,11 @@ pub async fn get_car_handler(
         .into_response();
     };
 
-    match resolver.resolve_content(&cid).instrument(span).await {
+    match resolver
+        .resolve_content(&cid, suffix(uri, &cid))
+        .instrument(span)
+        .await
+    {
         Ok(resp) => resp.into_response(),
         Err(Error::Internal(message)) => {
             error_handler(StatusCode::INTERNAL_SERVER_ERROR, message).into_response()
@@ -49,6 +83,17 @@ pub async fn get_car_handler(
     }
 }
 
+/// The still percent-encoded part of the request after the root cid, forwarded to the node,
+/// e.g. `/assets/logo.png?dag-scope=entity`.
+fn suffix<'a>(uri: &'a Uri, cid: &str) -> &'a str {
+    uri.path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or_default()
+        .split_once(cid)
+        .map(|(_, path)| path)
+        .unwrap_or_default()
+}
+
 fn error_handler(status_code: StatusCode, message: String) -> (StatusCode, Json<Value>) {
     (
         status_code,
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use ursa_store::StorageBackend;
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
 pub struct ProviderConfig {
@@ -13,6 +14,9 @@ pub struct ProviderConfig {
     /// database_path for index provider db
     #[serde(default = "ProviderConfig::default_database_path")]
     pub database_path: PathBuf,
+    /// Storage backend of the index provider db: memory, rocksdb, flatfile or paritydb.
+    #[serde(default)]
+    pub storage_backend: StorageBackend,
 }
 
 impl ProviderConfig {
@@ -30,6 +34,7 @@ impl Default for ProviderConfig {
             domain: None,
             indexer_url: Self::default_indexer_url(),
             database_path: Self::default_database_path(),
+            storage_backend: StorageBackend::default(),
         }
     }
 }
@@ -18,7 +18,7 @@ use rand;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::Write,
     sync::{Arc, RwLock},
 };
@@ -64,6 +64,41 @@ where
         let head_lock = self.head.read().unwrap();
         *head_lock
     }
+
+    /// Walk the advertisement chain from the head and return the roots
+    /// that are advertised and have not been removed since.
+    pub fn advertised_roots(&self) -> Result<Vec<Cid>> {
+        let mut roots = Vec::new();
+        let mut removed = HashSet::new();
+        let mut next = self.head();
+
+        while let Some(cid) = next.take() {
+            let ad = match self.store.blockstore().get_obj::<Ipld>(&cid)? {
+                Some(Ipld::Map(ad)) => ad,
+                Some(_) => return Err(anyhow!("advertisement {cid} is not a map")),
+                None => return Err(anyhow!("advertisement {cid} not found")),
+            };
+
+            let root = match ad.get("ContextID") {
+                Some(Ipld::Bytes(context_id)) => Cid::try_from(context_id.as_slice()).ok(),
+                _ => None,
+            };
+            if let Some(root) = root {
+                // newer advertisements come first, so a removal hides older ones
+                if matches!(ad.get("IsRm"), Some(Ipld::Bool(true))) {
+                    removed.insert(root);
+                } else if !removed.contains(&root) && !roots.contains(&root) {
+                    roots.push(root);
+                }
+            }
+
+            next = match ad.get("PreviousID") {
+                Some(Ipld::Link(previous)) => Some(*previous),
+                _ => None,
+            };
+        }
+        Ok(roots)
+    }
 }
 
 impl<S> Clone for Provider<S>
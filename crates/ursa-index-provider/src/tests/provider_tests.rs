@@ -1,13 +1,19 @@
 #[cfg(test)]
 mod tests {
     use crate::{
-        advertisement::Advertisement, provider::ProviderInterface, signed_head::SignedHead,
-        tests::provider_engine_init,
+        advertisement::Advertisement,
+        provider::{Provider, ProviderInterface},
+        signed_head::SignedHead,
+        tests::{get_store, provider_engine_init},
     };
 
     use anyhow::Error;
-    use libipld::multihash::{Code, MultihashDigest};
+    use libipld::{
+        multihash::{Code, MultihashDigest},
+        Cid,
+    };
     use libipld_core::ipld::Ipld;
+    use libp2p::{identity::Keypair, PeerId};
     use surf::Error as SurfError;
     use tokio::task;
     use tracing::{debug, error, info};
@@ -82,4 +88,22 @@ mod tests {
             Err(e) => panic!("{e}"),
         }
     }
+
+    #[test]
+    fn test_advertised_roots() -> anyhow::Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keypair.public());
+        let mut provider = Provider::new(keypair, get_store());
+
+        let first = Cid::new_v1(0x55, Code::Blake3_256.digest(b"first"));
+        let second = Cid::new_v1(0x55, Code::Blake3_256.digest(b"second"));
+        for (root, is_rm) in [(first, false), (second, false), (first, true)] {
+            let ad = Advertisement::new(root.to_bytes(), peer_id, vec![], is_rm, 0);
+            let id = provider.create(ad)?;
+            provider.publish(id)?;
+        }
+
+        assert_eq!(provider.advertised_roots()?, vec![second]);
+        Ok(())
+    }
 }
@@ -0,0 +1,16 @@
+use libp2p::dcutr::behaviour::Event;
+use metrics::increment_counter;
+
+impl super::Recorder for Event {
+    fn record(&self) {
+        increment_counter!(match self {
+            Event::RemoteInitiatedDirectConnectionUpgrade { .. } =>
+                "dcutr_remote_initiated_direct_connection_upgrade",
+            Event::InitiatedDirectConnectionUpgrade { .. } =>
+                "dcutr_initiated_direct_connection_upgrade",
+            Event::DirectConnectionUpgradeSucceeded { .. } =>
+                "dcutr_direct_connection_upgrade_succeeded",
+            Event::DirectConnectionUpgradeFailed { .. } => "dcutr_direct_connection_upgrade_failed",
+        });
+    }
+}
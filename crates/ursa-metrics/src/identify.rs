@@ -1,13 +1,15 @@
 use lazy_static::lazy_static;
 use libp2p::identify::Event;
 use libp2p::PeerId;
-use metrics::{increment_counter, increment_gauge, Label};
+use metrics::{decrement_gauge, increment_counter, increment_gauge, Label};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
 lazy_static! {
     pub static ref PEERS: Arc<RwLock<HashMap<PeerId, Vec<String>>>> =
         Arc::new(RwLock::new(HashMap::new()));
+    pub static ref AGENT_VERSIONS: Arc<RwLock<HashMap<PeerId, String>>> =
+        Arc::new(RwLock::new(HashMap::new()));
 }
 
 impl super::Recorder for Event {
@@ -24,6 +26,25 @@ impl super::Recorder for Event {
                         );
                     }
                 }
+
+                let mut agents = AGENT_VERSIONS.write().unwrap();
+                match agents.insert(*peer_id, info.agent_version.clone()) {
+                    Some(old) if old == info.agent_version => {}
+                    old => {
+                        if let Some(old) = old {
+                            decrement_gauge!(
+                                "identify_agent_versions",
+                                1.0,
+                                vec![Label::new("version", old)]
+                            );
+                        }
+                        increment_gauge!(
+                            "identify_agent_versions",
+                            1.0,
+                            vec![Label::new("version", info.agent_version.clone())]
+                        );
+                    }
+                }
             }
             Event::Sent { .. } => {
                 increment_counter!("identify_sent");
@@ -1,4 +1,4 @@
-use libp2p::relay::v2::relay::Event;
+use libp2p::relay::v2::{client::Event as ClientEvent, relay::Event};
 use metrics::increment_counter;
 
 impl super::Recorder for Event {
@@ -20,3 +20,24 @@ impl super::Recorder for Event {
         });
     }
 }
+
+impl super::Recorder for ClientEvent {
+    fn record(&self) {
+        increment_counter!(match self {
+            ClientEvent::ReservationReqAccepted { .. } => "relay_client_reservation_req_accepted",
+            ClientEvent::ReservationReqFailed { .. } => "relay_client_reservation_req_failed",
+            ClientEvent::OutboundCircuitEstablished { .. } =>
+                "relay_client_outbound_circuit_established",
+            ClientEvent::OutboundCircuitReqFailed { .. } =>
+                "relay_client_outbound_circuit_req_failed",
+            ClientEvent::InboundCircuitEstablished { .. } =>
+                "relay_client_inbound_circuit_established",
+            ClientEvent::InboundCircuitReqFailed { .. } =>
+                "relay_client_inbound_circuit_req_failed",
+            ClientEvent::InboundCircuitReqDenied { .. } =>
+                "relay_client_inbound_circuit_req_denied",
+            ClientEvent::InboundCircuitReqDenyFailed { .. } =>
+                "relay_client_inbound_circuit_req_deny_failed",
+        });
+    }
+}
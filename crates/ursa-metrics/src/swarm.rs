@@ -1,4 +1,4 @@
-use crate::identify::PEERS;
+use crate::identify::{AGENT_VERSIONS, PEERS};
 use crate::Recorder;
 use libp2p::swarm::SwarmEvent;
 use libp2p::{core::ConnectedPoint, PeerId};
@@ -47,6 +47,15 @@ impl<TBvEv, THandleErr> Recorder for SwarmEvent<TBvEv, THandleErr> {
                             );
                         }
                     }
+
+                    let mut agents = AGENT_VERSIONS.write().unwrap();
+                    if let Some(version) = agents.remove(peer_id) {
+                        decrement_gauge!(
+                            "identify_agent_versions",
+                            1.0,
+                            vec![Label::new("version", version)]
+                        );
+                    }
                 }
             }
             SwarmEvent::IncomingConnection { .. } => {
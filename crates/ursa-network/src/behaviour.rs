@@ -18,7 +18,8 @@ use compile_time_run::run_command_str;
 use db::Store;
 use fvm_ipld_blockstore::Blockstore;
 use graphsync::GraphSync;
-use libipld::{Cid, DefaultParams};
+use ipld_traversal::blockstore::Blockstore as GSBlockstore;
+use libipld::{Block, Cid, DefaultParams};
 use libp2p::swarm::behaviour::toggle::Toggle;
 use libp2p::{
     autonat::{Behaviour as Autonat, Config as AutonatConfig},
@@ -41,16 +42,17 @@ use libp2p::{
     swarm::NetworkBehaviour,
     Multiaddr, PeerId,
 };
-use libp2p_bitswap::{Bitswap, BitswapConfig};
+use libp2p_bitswap::{Bitswap, BitswapConfig, BitswapStore};
 use std::borrow::Cow;
 use std::iter;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
 
 use tracing::{info, warn};
 use ursa_metrics::BITSWAP_REGISTRY;
-use ursa_store::{BitswapStorage, UrsaStore};
+use ursa_store::{BitswapStorage, BlockError, UrsaStore};
 
 use crate::connection::Manager;
 use crate::gossipsub::build_gossipsub;
@@ -67,6 +69,88 @@ pub fn ursa_agent() -> String {
     format!("ursa/{COMMIT_HASH}")
 }
 
+/// A block received over bitswap, with its size and the blocks it references.
+pub(crate) type ReceivedBlock = (Cid, u64, Vec<Cid>);
+
+/// Channels the bitswap and graphsync stores report the blocks they write on.
+#[derive(Clone)]
+pub(crate) struct StoreReporter {
+    /// Blocks inserted by bitswap, to track fetch progress without walking the dag on every
+    /// block.
+    pub(crate) received: UnboundedSender<ReceivedBlock>,
+    /// Blocks received over graphsync that failed verification, to blame the peer serving
+    /// them.
+    pub(crate) invalid: UnboundedSender<Cid>,
+}
+
+/// [`BitswapStorage`] reporting the blocks bitswap inserts.
+struct ReportingStorage<S>
+where
+    S: Blockstore + Store + Send + Sync + 'static,
+{
+    inner: BitswapStorage<S>,
+    received: UnboundedSender<ReceivedBlock>,
+}
+
+impl<S> BitswapStore for ReportingStorage<S>
+where
+    S: Blockstore + Store + Send + Sync + 'static,
+{
+    type Params = DefaultParams;
+
+    fn contains(&mut self, cid: &Cid) -> Result<bool> {
+        self.inner.contains(cid)
+    }
+
+    fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+        self.inner.get(cid)
+    }
+
+    fn insert(&mut self, block: &Block<Self::Params>) -> Result<()> {
+        self.inner.insert(block)?;
+        let mut references = vec![];
+        if block.references(&mut references).is_ok() {
+            let size = block.data().len() as u64;
+            // the service may be gone on shutdown
+            let _ = self.received.send((*block.cid(), size, references));
+        }
+        Ok(())
+    }
+
+    fn missing_blocks(&mut self, cid: &Cid) -> Result<Vec<Cid>> {
+        self.inner.missing_blocks(cid)
+    }
+}
+
+/// [`UrsaStore`] for graphsync, reporting the blocks failing verification.
+#[derive(Clone)]
+pub(crate) struct GraphSyncStorage<S> {
+    inner: UrsaStore<S>,
+    invalid: UnboundedSender<Cid>,
+}
+
+impl<S> GSBlockstore for GraphSyncStorage<S>
+where
+    S: Blockstore + Store + Send + Sync + 'static,
+{
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        GSBlockstore::get(&self.inner, k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        GSBlockstore::put_keyed(&self.inner, k, block).map_err(|e| {
+            if e.downcast_ref::<BlockError>().is_some() {
+                let _ = self.invalid.send(*k);
+            }
+            e
+        })
+    }
+
+    fn delete_block(&self, k: &Cid) -> Result<()> {
+        GSBlockstore::delete_block(&self.inner, k)
+    }
+}
+
 /// Composes protocols for the behaviour of the node in the network.
 #[derive(NetworkBehaviour)]
 pub struct Behaviour<S>
@@ -107,7 +191,7 @@ where
     pub(crate) request_response: RequestResponse<UrsaExchangeCodec>,
 
     /// Graphsync for efficiently exchanging data between blocks between peers.
-    pub(crate) graphsync: GraphSync<UrsaStore<S>>,
+    pub(crate) graphsync: GraphSync<GraphSyncStorage<S>>,
 }
 
 impl<S> Behaviour<S>
@@ -120,6 +204,7 @@ where
         store: UrsaStore<S>,
         relay_client: Option<libp2p::relay::v2::client::Client>,
         peers: &mut Manager,
+        reporter: StoreReporter,
     ) -> Self {
         let local_public_key = keypair.public();
         let local_peer_id = PeerId::from(local_public_key.clone());
@@ -134,7 +219,10 @@ where
             .expect("PeerScoreParams and PeerScoreThresholds");
 
         // Setup the bitswap behaviour
-        let bitswap_store = BitswapStorage(Arc::new(store.clone()));
+        let bitswap_store = ReportingStorage {
+            inner: BitswapStorage(Arc::new(store.clone())),
+            received: reporter.received,
+        };
         let bitswap = Bitswap::new(BitswapConfig::default(), bitswap_store);
 
         if let Err(e) = bitswap.register_metrics(&BITSWAP_REGISTRY) {
@@ -206,7 +294,10 @@ where
         };
 
         // Set up the Graphsync behaviour.
-        let graphsync = GraphSync::new(store);
+        let graphsync = GraphSync::new(GraphSyncStorage {
+            inner: store,
+            invalid: reporter.invalid,
+        });
 
         // init bootstraps
         for addr in config.bootstrap_nodes.iter() {
@@ -78,8 +78,8 @@ impl RequestResponseCodec for UrsaExchangeCodec {
             return Err(io::ErrorKind::UnexpectedEof.into());
         }
 
-        let request: UrsaExchangeRequest =
-            serde_json::from_str(&String::from_utf8(vec).unwrap()).unwrap();
+        let request: UrsaExchangeRequest = serde_json::from_slice(&vec)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
         Ok(request)
     }
@@ -98,8 +98,8 @@ impl RequestResponseCodec for UrsaExchangeCodec {
             return Err(io::ErrorKind::UnexpectedEof.into());
         }
 
-        let response: UrsaExchangeResponse =
-            serde_json::from_str(&String::from_utf8(vec).unwrap()).unwrap();
+        let response: UrsaExchangeResponse = serde_json::from_slice(&vec)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
         Ok(response)
     }
@@ -113,7 +113,7 @@ impl RequestResponseCodec for UrsaExchangeCodec {
     where
         T: AsyncWrite + Unpin + Send,
     {
-        let data = serde_json::to_vec(&req).unwrap();
+        let data = serde_json::to_vec(&req)?;
         write_length_prefixed(io, &data).await?;
         io.close().await?;
 
@@ -129,7 +129,7 @@ impl RequestResponseCodec for UrsaExchangeCodec {
     where
         T: AsyncWrite + Unpin + Send,
     {
-        let data = serde_json::to_vec(&res).unwrap();
+        let data = serde_json::to_vec(&res)?;
         write_length_prefixed(io, &data).await?;
         io.close().await?;
 
@@ -1,6 +1,7 @@
 use libp2p::Multiaddr;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use ursa_store::StorageBackend;
 
 /// Ursa Configuration
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -18,6 +19,9 @@ pub struct NetworkConfig {
     /// Connections will attempt to upgrade using dcutr.
     #[serde(default = "NetworkConfig::default_relay_client")]
     pub relay_client: bool,
+    /// Number of relays to hold reservations with when not publicly reachable.
+    #[serde(default = "NetworkConfig::default_relay_reservations")]
+    pub relay_reservations: usize,
     /// set true if it is a bootstrap node. default = false
     #[serde(default = "NetworkConfig::default_bootstrapper")]
     pub bootstrapper: bool,
@@ -30,6 +34,15 @@ pub struct NetworkConfig {
     /// Database path.
     #[serde(default = "NetworkConfig::default_database_path")]
     pub database_path: PathBuf,
+    /// File the cache summary and routing table are persisted to across restarts.
+    #[serde(default = "NetworkConfig::default_state_path")]
+    pub state_path: PathBuf,
+    /// Storage backend of the blockstore: memory, rocksdb, flatfile or paritydb.
+    #[serde(default)]
+    pub storage_backend: StorageBackend,
+    /// Bytes of recently read blocks to keep in memory, 0 disables the cache. Defaults to 256 MiB
+    #[serde(default = "NetworkConfig::default_block_cache_size")]
+    pub block_cache_size: u64,
     /// user identity name
     #[serde(default = "NetworkConfig::default_identity")]
     pub identity: String,
@@ -42,9 +55,15 @@ pub struct NetworkConfig {
     /// Interval to run random kademlia walks to refresh the routing table. Defaults to 5 minutes
     #[serde(default = "NetworkConfig::default_kad_walk_interval")]
     pub kad_walk_interval: u64,
+    /// Seconds to wait for a bitswap fetch to complete before failing it. Defaults to 60 seconds
+    #[serde(default = "NetworkConfig::default_bitswap_timeout")]
+    pub bitswap_timeout: u64,
     /// Maximum number of cache summaries from other peers to store.
     #[serde(default = "NetworkConfig::default_max_cache_summaries")]
     pub max_cache_summaries: usize,
+    /// Disconnect from peers running a different version of ursa instead of only deprioritising them.
+    #[serde(default = "NetworkConfig::default_reject_incompatible_peers")]
+    pub reject_incompatible_peers: bool,
 }
 
 impl NetworkConfig {
@@ -57,6 +76,9 @@ impl NetworkConfig {
     fn default_relay_client() -> bool {
         true
     }
+    fn default_relay_reservations() -> usize {
+        2
+    }
     fn default_relay_server() -> bool {
         true
     }
@@ -78,9 +100,15 @@ impl NetworkConfig {
     fn default_database_path() -> PathBuf {
         "~/.ursa/data/ursa_db".into()
     }
+    fn default_state_path() -> PathBuf {
+        "~/.ursa/data/network_state".into()
+    }
     fn default_keystore_path() -> PathBuf {
         "~/.ursa/keystore".into()
     }
+    fn default_block_cache_size() -> u64 {
+        256 * 1024 * 1024
+    }
     fn default_identity() -> String {
         "default".to_string()
     }
@@ -90,9 +118,15 @@ impl NetworkConfig {
     fn default_kad_walk_interval() -> u64 {
         300
     }
+    fn default_bitswap_timeout() -> u64 {
+        60
+    }
     fn default_max_cache_summaries() -> usize {
         10
     }
+    fn default_reject_incompatible_peers() -> bool {
+        false
+    }
 }
 
 impl Default for NetworkConfig {
@@ -101,16 +135,22 @@ impl Default for NetworkConfig {
             mdns: Self::default_mdns(),
             autonat: Self::default_autonat(),
             relay_client: Self::default_relay_client(),
+            relay_reservations: Self::default_relay_reservations(),
             relay_server: Self::default_relay_server(),
             bootstrapper: Self::default_bootstrapper(),
             bootstrap_nodes: Self::default_bootstrap_nodes(),
             swarm_addrs: Self::default_swarm_addrs(),
             database_path: Self::default_database_path(),
+            state_path: Self::default_state_path(),
+            storage_backend: StorageBackend::default(),
+            block_cache_size: Self::default_block_cache_size(),
             identity: Self::default_identity(),
             keystore_path: Self::default_keystore_path(),
             kad_replication_factor: Self::default_kad_replication_factor(),
             kad_walk_interval: Self::default_kad_walk_interval(),
+            bitswap_timeout: Self::default_bitswap_timeout(),
             max_cache_summaries: Self::default_max_cache_summaries(),
+            reject_incompatible_peers: Self::default_reject_incompatible_peers(),
         }
     }
 }
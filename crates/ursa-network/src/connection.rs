@@ -1,21 +1,66 @@
-use libp2p::PeerId;
+use libp2p::{identify::Info, Multiaddr, PeerId};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
-    time::Duration,
+    time::{Duration, SystemTime},
 };
 use tracing::debug;
 
+use crate::behaviour::ursa_agent;
+
 #[cfg(not(test))]
 const REPLICATION_MAX_SIZE: usize = 2;
 #[cfg(not(test))]
 const MAX_RTT: Duration = Duration::from_millis(15);
 
+/// Information reported by a peer through the identify protocol.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct PeerInfo {
+    /// Agent version of the peer, e.g. `ursa/<commit hash>`.
+    pub agent_version: String,
+    /// Protocol version of the peer.
+    pub protocol_version: String,
+    /// Protocols supported by the peer.
+    pub protocols: Vec<String>,
+    /// Addresses the peer is listening on.
+    #[schemars(with = "Vec<String>")]
+    pub listen_addrs: Vec<Multiaddr>,
+    /// Our address as observed by the peer.
+    #[schemars(with = "String")]
+    pub observed_addr: Multiaddr,
+    /// Last time we received identify information from the peer.
+    pub last_seen: SystemTime,
+}
+
+impl PeerInfo {
+    /// Returns true if the peer runs the same version of ursa as we do.
+    pub fn is_compatible(&self) -> bool {
+        self.agent_version == ursa_agent()
+    }
+}
+
+impl From<Info> for PeerInfo {
+    fn from(info: Info) -> Self {
+        Self {
+            agent_version: info.agent_version,
+            protocol_version: info.protocol_version,
+            protocols: info.protocols,
+            listen_addrs: info.listen_addrs,
+            observed_addr: info.observed_addr,
+            last_seen: SystemTime::now(),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Manager {
     /// Connected peers.
     connected_peers: HashSet<PeerId>,
     /// Set of peers to use in content replication.
     replication_set: HashMap<PeerId, Duration>,
+    /// Identify information of connected peers.
+    peer_info: HashMap<PeerId, PeerInfo>,
 }
 
 impl Manager {
@@ -41,6 +86,7 @@ impl Manager {
 
     pub fn remove(&mut self, peer: &PeerId) -> bool {
         self.replication_set.remove(peer);
+        self.peer_info.remove(peer);
         self.connected_peers.remove(peer)
     }
 
@@ -48,6 +94,37 @@ impl Manager {
         self.replication_set.clone().into_keys().collect()
     }
 
+    /// Store the identify information of a peer, replacing any previous record.
+    /// Peers running an incompatible version are dropped from the replication set.
+    pub fn insert_peer_info(&mut self, peer: PeerId, info: PeerInfo) {
+        if !info.is_compatible() {
+            debug!(
+                "{peer} runs incompatible version {}, removing it from mesh",
+                info.agent_version
+            );
+            self.replication_set.remove(&peer);
+        }
+        self.peer_info.insert(peer, info);
+    }
+
+    pub fn peer_info(&self, peer: &PeerId) -> Option<&PeerInfo> {
+        self.peer_info.get(peer)
+    }
+
+    /// Returns false only if the peer is known to run an incompatible version.
+    /// Peers that have not been identified yet are given the benefit of the doubt.
+    pub fn is_compatible(&self, peer: &PeerId) -> bool {
+        self.peer_info
+            .get(peer)
+            .map(PeerInfo::is_compatible)
+            .unwrap_or(true)
+    }
+
+    /// Sort peers so that peers running a compatible version come first.
+    pub fn prioritize(&self, peers: &mut [PeerId]) {
+        peers.sort_by_key(|peer| !self.is_compatible(peer));
+    }
+
     #[cfg(not(test))]
     pub fn handle_rtt_received(&mut self, rtt: Duration, peer: PeerId) {
         debug!("Received {rtt:?} rtt for {peer}");
@@ -60,6 +137,10 @@ impl Manager {
             debug!("{peer} was not added because we don't have a connection for it");
             return;
         }
+        if !self.is_compatible(&peer) {
+            debug!("{peer} was not added because it runs an incompatible version");
+            return;
+        }
         if self.replication_set.len() >= REPLICATION_MAX_SIZE
             && !self.replication_set.contains_key(&peer)
         {
@@ -87,3 +168,59 @@ impl Manager {
         self.replication_set.insert(peer, rtt);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer_info(agent_version: String) -> PeerInfo {
+        PeerInfo {
+            agent_version,
+            protocol_version: "ipfs/0.1.0".to_string(),
+            protocols: vec![],
+            listen_addrs: vec![],
+            observed_addr: "/ip4/127.0.0.1/tcp/6009".parse().unwrap(),
+            last_seen: SystemTime::now(),
+        }
+    }
+
+    #[test]
+    fn test_incompatible_peer_removed_from_replication_set() {
+        let peer = PeerId::random();
+        let mut manager = Manager::new();
+        manager.insert(peer);
+        manager.handle_rtt_received(Duration::from_millis(1), peer);
+        assert_eq!(manager.replication_set(), vec![peer]);
+
+        manager.insert_peer_info(peer, peer_info("ursa/0000000".to_string()));
+        assert!(!manager.is_compatible(&peer));
+        assert!(manager.replication_set().is_empty());
+    }
+
+    #[test]
+    fn test_prioritize_compatible_peers() {
+        let compatible = PeerId::random();
+        let incompatible = PeerId::random();
+        let unknown = PeerId::random();
+        let mut manager = Manager::new();
+        manager.insert_peer_info(compatible, peer_info(ursa_agent()));
+        manager.insert_peer_info(incompatible, peer_info("go-ipfs/0.17.0".to_string()));
+
+        let mut peers = vec![incompatible, compatible, unknown];
+        manager.prioritize(&mut peers);
+        assert_eq!(peers.last(), Some(&incompatible));
+        assert!(manager.is_compatible(&unknown));
+    }
+
+    #[test]
+    fn test_remove_peer_info() {
+        let peer = PeerId::random();
+        let mut manager = Manager::new();
+        manager.insert(peer);
+        manager.insert_peer_info(peer, peer_info(ursa_agent()));
+        assert!(manager.peer_info(&peer).is_some());
+
+        manager.remove(&peer);
+        assert!(manager.peer_info(&peer).is_none());
+    }
+}
@@ -4,10 +4,13 @@ pub mod config;
 mod connection;
 mod gossipsub;
 mod measurements;
+mod relay;
 pub mod service;
 mod transport;
 mod utils;
 
 pub use self::behaviour::ursa_agent;
 pub use self::config::*;
+pub use self::connection::PeerInfo;
+pub use self::relay::{RelayReservation, RelayState};
 pub use self::service::*;
@@ -0,0 +1,263 @@
+//! # Relay reservation management.
+//!
+//! When autonat reports that the node is behind a NAT, the [`RelayManager`] picks up to
+//! `relay_reservations` relays out of the bootstrap nodes, preferring the ones with the
+//! lowest measured latency, and keeps a circuit listener open on each of them. Failed or
+//! closed reservations are rotated to the next best relay, and relays that failed are
+//! skipped until the next retry round.
+
+use libp2p::{core::transport::ListenerId, multiaddr::Protocol, Multiaddr, PeerId};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+/// A reservation on a relay node as exposed over rpc.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RelayReservation {
+    /// Peer id of the relay.
+    #[schemars(with = "String")]
+    pub peer_id: PeerId,
+    /// Circuit address we are listening on.
+    #[schemars(with = "String")]
+    pub addr: Multiaddr,
+    /// Whether the relay accepted the reservation.
+    pub accepted: bool,
+    /// Number of times the reservation was renewed.
+    pub renewals: u64,
+    /// Latest rtt measured to the relay.
+    pub rtt: Option<Duration>,
+}
+
+/// Snapshot of the relay client state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RelayState {
+    /// Whether autonat reported that we are behind a NAT.
+    pub private: bool,
+    /// Currently held reservations.
+    pub reservations: Vec<RelayReservation>,
+    /// Number of successful DCUtR connection upgrades.
+    pub dcutr_succeeded: u64,
+    /// Number of failed DCUtR connection upgrades.
+    pub dcutr_failed: u64,
+}
+
+struct Reservation {
+    listener_id: ListenerId,
+    addr: Multiaddr,
+    accepted: bool,
+    renewals: u64,
+}
+
+pub struct RelayManager {
+    /// Maximum number of relays to hold reservations with.
+    max_reservations: usize,
+    /// Whether autonat reported that we are behind a NAT.
+    private: bool,
+    /// Relay candidates and their addresses.
+    candidates: HashMap<PeerId, Multiaddr>,
+    /// Latest rtt measured to relay candidates.
+    rtt: HashMap<PeerId, Duration>,
+    /// Active reservations.
+    reservations: HashMap<PeerId, Reservation>,
+    /// Relays that failed since the last retry round.
+    failed: HashSet<PeerId>,
+    dcutr_succeeded: u64,
+    dcutr_failed: u64,
+}
+
+impl RelayManager {
+    pub fn new(max_reservations: usize, relays: &[Multiaddr]) -> Self {
+        let candidates = relays
+            .iter()
+            .filter_map(|addr| match addr.iter().last() {
+                Some(Protocol::P2p(mh)) => PeerId::from_multihash(mh)
+                    .ok()
+                    .map(|peer_id| (peer_id, addr.clone())),
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            max_reservations,
+            private: false,
+            candidates,
+            rtt: HashMap::new(),
+            reservations: HashMap::new(),
+            failed: HashSet::new(),
+            dcutr_succeeded: 0,
+            dcutr_failed: 0,
+        }
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+
+    pub fn set_private(&mut self, private: bool) {
+        self.private = private;
+    }
+
+    pub fn register_rtt(&mut self, peer_id: PeerId, rtt: Duration) {
+        if self.candidates.contains_key(&peer_id) {
+            self.rtt.insert(peer_id, rtt);
+        }
+    }
+
+    /// Returns the relay with the lowest latency we should reserve a slot on next, if any.
+    /// Relays without a latency measurement are tried last.
+    pub fn next_candidate(&self) -> Option<(PeerId, Multiaddr)> {
+        if !self.private || self.reservations.len() >= self.max_reservations {
+            return None;
+        }
+
+        self.candidates
+            .iter()
+            .filter(|(peer_id, _)| {
+                !self.reservations.contains_key(peer_id) && !self.failed.contains(peer_id)
+            })
+            .min_by_key(|(peer_id, _)| self.rtt.get(peer_id).copied().unwrap_or(Duration::MAX))
+            .map(|(peer_id, addr)| (*peer_id, addr.clone()))
+    }
+
+    pub fn insert(&mut self, peer_id: PeerId, listener_id: ListenerId, addr: Multiaddr) {
+        self.reservations.insert(
+            peer_id,
+            Reservation {
+                listener_id,
+                addr,
+                accepted: false,
+                renewals: 0,
+            },
+        );
+    }
+
+    pub fn handle_accepted(&mut self, peer_id: &PeerId, renewal: bool) {
+        if let Some(reservation) = self.reservations.get_mut(peer_id) {
+            reservation.accepted = true;
+            if renewal {
+                reservation.renewals += 1;
+            }
+        }
+    }
+
+    /// Mark a relay as failed, returning the listener of its reservation if there was one.
+    pub fn handle_failure(&mut self, peer_id: &PeerId) -> Option<ListenerId> {
+        self.failed.insert(*peer_id);
+        self.reservations
+            .remove(peer_id)
+            .map(|reservation| reservation.listener_id)
+    }
+
+    /// Returns the relay of a closed listener if it belonged to a reservation.
+    pub fn handle_listener_closed(&mut self, listener_id: &ListenerId) -> Option<PeerId> {
+        let peer_id = self
+            .reservations
+            .iter()
+            .find(|(_, reservation)| reservation.listener_id == *listener_id)
+            .map(|(peer_id, _)| *peer_id)?;
+        self.handle_failure(&peer_id);
+        Some(peer_id)
+    }
+
+    pub fn handle_dcutr(&mut self, success: bool) {
+        if success {
+            self.dcutr_succeeded += 1;
+        } else {
+            self.dcutr_failed += 1;
+        }
+    }
+
+    /// Forget failed relays so they are tried again.
+    pub fn reset_failed(&mut self) {
+        self.failed.clear();
+    }
+
+    /// Drop all reservations, returning their listeners.
+    pub fn clear(&mut self) -> Vec<ListenerId> {
+        self.reservations
+            .drain()
+            .map(|(_, reservation)| reservation.listener_id)
+            .collect()
+    }
+
+    pub fn state(&self) -> RelayState {
+        RelayState {
+            private: self.private,
+            reservations: self
+                .reservations
+                .iter()
+                .map(|(peer_id, reservation)| RelayReservation {
+                    peer_id: *peer_id,
+                    addr: reservation.addr.clone(),
+                    accepted: reservation.accepted,
+                    renewals: reservation.renewals,
+                    rtt: self.rtt.get(peer_id).copied(),
+                })
+                .collect(),
+            dcutr_succeeded: self.dcutr_succeeded,
+            dcutr_failed: self.dcutr_failed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relay_addr(peer_id: PeerId) -> Multiaddr {
+        format!("/ip4/127.0.0.1/tcp/6009/p2p/{peer_id}")
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_next_candidate_by_latency() {
+        let (fast, slow, unknown) = (PeerId::random(), PeerId::random(), PeerId::random());
+        let mut manager = RelayManager::new(
+            2,
+            &[relay_addr(slow), relay_addr(unknown), relay_addr(fast)],
+        );
+        assert!(manager.next_candidate().is_none());
+
+        manager.set_private(true);
+        manager.register_rtt(slow, Duration::from_millis(100));
+        manager.register_rtt(fast, Duration::from_millis(10));
+
+        let (peer_id, addr) = manager.next_candidate().unwrap();
+        assert_eq!(peer_id, fast);
+        manager.insert(peer_id, ListenerId::new(), addr);
+
+        let (peer_id, addr) = manager.next_candidate().unwrap();
+        assert_eq!(peer_id, slow);
+        manager.insert(peer_id, ListenerId::new(), addr);
+
+        // reservation limit reached
+        assert!(manager.next_candidate().is_none());
+    }
+
+    #[test]
+    fn test_rotate_on_failure() {
+        let (first, second) = (PeerId::random(), PeerId::random());
+        let mut manager = RelayManager::new(1, &[relay_addr(first), relay_addr(second)]);
+        manager.set_private(true);
+        manager.register_rtt(first, Duration::from_millis(10));
+
+        let listener_id = ListenerId::new();
+        manager.insert(first, listener_id, relay_addr(first));
+        manager.handle_accepted(&first, false);
+        manager.handle_accepted(&first, true);
+        assert_eq!(manager.state().reservations[0].renewals, 1);
+
+        assert_eq!(manager.handle_listener_closed(&listener_id), Some(first));
+        assert_eq!(manager.next_candidate().map(|(p, _)| p), Some(second));
+
+        manager.handle_failure(&second);
+        assert!(manager.next_candidate().is_none());
+
+        manager.reset_failed();
+        assert_eq!(manager.next_candidate().map(|(p, _)| p), Some(first));
+    }
+}
@@ -14,14 +14,16 @@
 use anyhow::{anyhow, Error, Result};
 use bytes::Bytes;
 use db::Store;
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 use futures_util::stream::StreamExt;
 use fvm_ipld_blockstore::Blockstore;
 use graphsync::{GraphSyncEvent, Request, RequestId as GraphSyncReqId};
 use ipld_traversal::{selector::RecursionLimit, Selector};
-use libipld::Cid;
+use libipld::{Block, Cid, DefaultParams};
 use libp2p::{
     autonat::{Event as AutonatEvent, NatStatus},
+    core::transport::ListenerId,
+    dcutr::behaviour::Event as DcutrEvent,
     gossipsub::{
         error::{PublishError, SubscriptionError},
         IdentTopic as Topic, MessageId, TopicHash,
@@ -32,7 +34,7 @@ use libp2p::{
     mdns::Event as MdnsEvent,
     multiaddr::Protocol,
     ping::Event as PingEvent,
-    relay::v2::client::Client as RelayClient,
+    relay::v2::client::{Client as RelayClient, Event as RelayClientEvent},
     request_response::{RequestId, RequestResponseEvent, RequestResponseMessage, ResponseChannel},
     swarm::{ConnectionHandler, IntoConnectionHandler, NetworkBehaviour},
     swarm::{ConnectionLimits, SwarmBuilder, SwarmEvent},
@@ -40,11 +42,15 @@ use libp2p::{
 };
 use libp2p_bitswap::{BitswapEvent, QueryId};
 use lru::LruCache;
-use rand::prelude::SliceRandom;
+use metrics::{increment_counter, Label};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
     fmt::Debug,
+    fs,
     num::{NonZeroU8, NonZeroUsize},
+    path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
 };
@@ -52,19 +58,22 @@ use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tokio::{
     select,
     sync::{
+        broadcast,
         mpsc::{unbounded_channel, Sender},
         oneshot,
     },
-    time::{sleep, Instant},
+    time::{sleep, timeout, Instant},
 };
 use tracing::{debug, error, info, trace, warn};
 use ursa_metrics::Recorder;
 use ursa_store::UrsaStore;
+use ursa_utils::shutdown::ShutdownController;
 
-use crate::behaviour::KAD_PROTOCOL;
+use crate::behaviour::{ReceivedBlock, StoreReporter, KAD_PROTOCOL};
 use crate::codec::protocol::{RequestType, ResponseType};
-use crate::connection::Manager;
+use crate::connection::{Manager, PeerInfo};
 use crate::measurements::MeasurementManager;
+use crate::relay::{RelayManager, RelayState};
 use crate::transport::build_transport;
 use crate::utils::cache_summary::CacheSummary;
 use crate::{
@@ -75,8 +84,57 @@ use crate::{
 
 pub const URSA_GLOBAL: &str = "/ursa/global";
 pub const MESSAGE_PROTOCOL: &[u8] = b"/ursa/message/0.0.1";
+/// Interval to retry relays that failed to accept a reservation.
+const RELAY_RETRY_INTERVAL: Duration = Duration::from_secs(60);
+/// Time given to connections to close on shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+/// Interval to check bitswap queries for timeouts and dropped receivers.
+const BITSWAP_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+/// Number of attempts to rebuild the swarm after a fatal error before giving up.
+const MAX_SWARM_RESTARTS: u32 = 5;
+/// Events buffered per subscriber before the slowest ones start missing events.
+const EVENT_BROADCAST_CAPACITY: usize = 1024;
 
 type BlockOneShotSender<T> = oneshot::Sender<Result<T, Error>>;
+
+/// State of the service persisted across restarts, kept in its own file next to the
+/// blockstore so it never mixes with content.
+#[derive(Default, Serialize, Deserialize)]
+struct NetworkState {
+    cache_summary: Option<Vec<u8>>,
+    kad_peers: Vec<(PeerId, Vec<Multiaddr>)>,
+}
+
+/// An error after which the swarm can no longer make progress and has to be rebuilt.
+///
+/// These are the only errors the service loop hands to [`UrsaService::restart_swarm`], from
+/// the swarm events and the commands alike. Every other handler error is logged and the
+/// event dropped: listener errors libp2p reports without closing the listener, a listener
+/// that closes while others remain, dial failures and protocol errors are all recoverable.
+#[derive(Debug)]
+enum FatalSwarmError {
+    /// The last configured listener closed and couldn't listen again, the node is unreachable.
+    NoListeners { addr: Multiaddr, reason: String },
+    /// The swarm stopped producing events.
+    StreamEnded,
+}
+
+impl std::fmt::Display for FatalSwarmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoListeners { addr, reason } => {
+                write!(
+                    f,
+                    "no listener left, failed to listen again on {addr}: {reason}"
+                )
+            }
+            Self::StreamEnded => write!(f, "swarm stream ended"),
+        }
+    }
+}
+
+impl std::error::Error for FatalSwarmError {}
+
 type SwarmEventType<S> = SwarmEvent<
 <Behaviour<S> as NetworkBehaviour>::OutEvent,
 <
@@ -109,7 +167,7 @@ pub enum GossipsubMessage {
     },
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum GossipsubEvent {
     /// A message has been received.
     Message {
@@ -139,7 +197,7 @@ pub enum GossipsubEvent {
 
 /// [network]'s events
 /// Requests and failure events emitted by the `NetworkBehaviour`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum NetworkEvent {
     /// An event trigger when remote peer connects.
     PeerConnected(PeerId),
@@ -157,11 +215,91 @@ pub enum NetworkEvent {
     PullComplete { cid: Cid, size: u64 },
 }
 
+/// Progress of a bitswap fetch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct BitswapProgress {
+    /// Blocks of the dag present in the local store.
+    pub blocks_fetched: usize,
+    /// Bytes of the dag present in the local store.
+    pub bytes_fetched: u64,
+    /// Blocks of the dag that are known to be missing.
+    pub blocks_missing: usize,
+}
+
+/// A bitswap fetch with progress subscribers, updated as the blocks of its dag arrive.
+struct FetchProgress {
+    progress: BitswapProgress,
+    /// Blocks of the dag seen so far, present or missing.
+    seen: FnvHashSet<Cid>,
+    /// Blocks of the dag known to be missing.
+    missing: FnvHashSet<Cid>,
+    subscribers: Vec<UnboundedSender<BitswapProgress>>,
+}
+
+impl FetchProgress {
+    fn new<S>(store: &UrsaStore<S>, root_cid: Cid) -> Result<Self>
+    where
+        S: Blockstore + Store + Send + Sync + 'static,
+    {
+        let mut fetch = Self {
+            progress: BitswapProgress::default(),
+            seen: Default::default(),
+            missing: Default::default(),
+            subscribers: Vec::new(),
+        };
+        fetch.add_references(store, vec![root_cid])?;
+        Ok(fetch)
+    }
+
+    /// Count the present blocks reachable from `stack` and remember the missing ones.
+    /// Every block is visited once over the whole fetch.
+    fn add_references<S>(&mut self, store: &UrsaStore<S>, mut stack: Vec<Cid>) -> Result<()>
+    where
+        S: Blockstore + Store + Send + Sync + 'static,
+    {
+        while let Some(cid) = stack.pop() {
+            if !self.seen.insert(cid) {
+                continue;
+            }
+            match store.get_block(&cid)? {
+                Some(data) => {
+                    self.progress.blocks_fetched += 1;
+                    self.progress.bytes_fetched += data.len() as u64;
+                    Block::<DefaultParams>::new_unchecked(cid, data).references(&mut stack)?;
+                }
+                None => {
+                    self.missing.insert(cid);
+                }
+            }
+        }
+        self.progress.blocks_missing = self.missing.len();
+        Ok(())
+    }
+
+    /// Account a block received over bitswap. Returns false if the dag wasn't waiting on it.
+    fn insert_block<S>(&mut self, store: &UrsaStore<S>, block: &ReceivedBlock) -> Result<bool>
+    where
+        S: Blockstore + Store + Send + Sync + 'static,
+    {
+        let (cid, size, references) = block;
+        if !self.missing.remove(cid) {
+            return Ok(false);
+        }
+        self.progress.blocks_fetched += 1;
+        self.progress.bytes_fetched += size;
+        self.add_references(store, references.clone())?;
+        Ok(true)
+    }
+}
+
 #[derive(Debug)]
 pub enum NetworkCommand {
+    /// Fetch a dag over bitswap. The request fails after `bitswap_timeout` and is
+    /// cancelled if `sender` is dropped. Progress is reported on `progress` if set.
     GetBitswap {
         cid: Cid,
         sender: BlockOneShotSender<()>,
+        progress: Option<UnboundedSender<BitswapProgress>>,
     },
 
     Put {
@@ -177,6 +315,15 @@ pub enum NetworkCommand {
         sender: oneshot::Sender<Vec<Multiaddr>>,
     },
 
+    GetPeerInfo {
+        peer_id: PeerId,
+        sender: oneshot::Sender<Option<PeerInfo>>,
+    },
+
+    GetRelayState {
+        sender: oneshot::Sender<RelayState>,
+    },
+
     SendRequest {
         peer_id: PeerId,
         request: Box<UrsaExchangeRequest>,
@@ -187,6 +334,29 @@ pub enum NetworkCommand {
         peer_id: PeerId,
         message: GossipsubMessage,
     },
+
+    /// A block failed hash verification. `peer_id` is the peer that served it, if any.
+    ReportInvalidBlock {
+        cid: Cid,
+        peer_id: Option<PeerId>,
+    },
+}
+
+impl NetworkCommand {
+    /// Short name of the command, used to label metrics.
+    fn kind(&self) -> &'static str {
+        match self {
+            NetworkCommand::GetBitswap { .. } => "get_bitswap",
+            NetworkCommand::Put { .. } => "put",
+            NetworkCommand::GetPeers { .. } => "get_peers",
+            NetworkCommand::GetListenerAddresses { .. } => "get_listener_addresses",
+            NetworkCommand::GetPeerInfo { .. } => "get_peer_info",
+            NetworkCommand::GetRelayState { .. } => "get_relay_state",
+            NetworkCommand::SendRequest { .. } => "send_request",
+            NetworkCommand::GossipsubMessage { .. } => "gossipsub_message",
+            NetworkCommand::ReportInvalidBlock { .. } => "report_invalid_block",
+        }
+    }
 }
 
 pub struct UrsaService<S>
@@ -197,16 +367,36 @@ where
     pub store: Arc<UrsaStore<S>>,
     /// The main libp2p swarm emitting events.
     swarm: Swarm<Behaviour<S>>,
+    /// Listeners on the configured swarm addresses.
+    listeners: HashMap<ListenerId, Multiaddr>,
+    /// Identity of the node, used to rebuild the swarm.
+    keypair: Keypair,
+    /// Network configuration, used to rebuild the swarm.
+    config: NetworkConfig,
     /// Handles outbound messages to peers.
     command_sender: UnboundedSender<NetworkCommand>,
     /// Handles inbound messages from peers.
     command_receiver: UnboundedReceiver<NetworkCommand>,
     /// Handles events emitted by the ursa network.
     event_sender: Sender<NetworkEvent>,
-    /// Bitswap pending queries.
-    bitswap_queries: FnvHashMap<QueryId, Cid>,
+    /// Publishes a copy of every event to the subscribers, i.e. rpc websocket clients.
+    event_broadcast: broadcast::Sender<NetworkEvent>,
+    /// Bitswap pending queries and their deadline.
+    bitswap_queries: FnvHashMap<QueryId, (Cid, Instant)>,
     /// hashmap for keeping track of rpc response channels.
     response_channels: FnvHashMap<Cid, Vec<BlockOneShotSender<()>>>,
+    /// Progress of the bitswap fetches with subscribers.
+    fetch_progress: FnvHashMap<Cid, FetchProgress>,
+    /// Blocks inserted by bitswap, reported by the swarm.
+    received_blocks: UnboundedReceiver<ReceivedBlock>,
+    /// Blocks received over graphsync that failed verification, reported by the swarm.
+    invalid_blocks: UnboundedReceiver<Cid>,
+    /// Handed to every swarm built to report the blocks written by bitswap and graphsync.
+    store_reporter: StoreReporter,
+    /// Blocks received over graphsync that failed verification, kept while requests are pending.
+    graphsync_invalid: FnvHashSet<Cid>,
+    /// Bitswap queries completing a dag graphsync left incomplete, nobody waits on them.
+    background_queries: FnvHashSet<QueryId>,
     /// Pending requests.
     _pending_requests: HashMap<RequestId, ResponseChannel<UrsaExchangeResponse>>,
     /// Pending responses.
@@ -215,8 +405,8 @@ where
     peers: Manager,
     /// Manages the peer measurements.
     measurement_manager: MeasurementManager,
-    /// Bootstrap multiaddrs.
-    bootstraps: Vec<Multiaddr>,
+    /// Manages relay reservations when behind a NAT.
+    relay_manager: RelayManager,
     /// Summarizes the cached content.
     cached_content: CacheSummary,
     /// Content summaries from other nodes.
@@ -225,8 +415,12 @@ where
     kad_walk_interval: u64,
     /// Public address reported from autonat
     pub public_addr: Option<Multiaddr>,
-    /// Graphsync pending requests.
-    graphsync_pending: HashMap<GraphSyncReqId, Cid>,
+    /// Graphsync pending requests and the peer they were sent to.
+    graphsync_pending: HashMap<GraphSyncReqId, (PeerId, Cid)>,
+    /// Disconnect from peers running an incompatible version.
+    reject_incompatible_peers: bool,
+    /// File the cache summary and routing table are persisted to, nothing is persisted if unset.
+    state_path: Option<PathBuf>,
 }
 
 impl<S> UrsaService<S>
@@ -252,6 +446,70 @@ where
         store: Arc<UrsaStore<S>>,
         event_sender: Sender<NetworkEvent>,
     ) -> Result<Self> {
+        let mut peers = Manager::new();
+        let (received, received_blocks) = unbounded_channel();
+        let (invalid, invalid_blocks) = unbounded_channel();
+        let store_reporter = StoreReporter { received, invalid };
+        let (swarm, listeners) =
+            Self::build_swarm(&keypair, config, &store, &mut peers, &store_reporter)?;
+
+        let (command_sender, command_receiver) = unbounded_channel();
+        let (event_broadcast, _) = broadcast::channel(EVENT_BROADCAST_CAPACITY);
+
+        let max_cache_summaries = NonZeroUsize::new(config.max_cache_summaries).unwrap();
+        let mut service = UrsaService {
+            swarm,
+            listeners,
+            keypair,
+            config: config.clone(),
+            store,
+            command_sender,
+            command_receiver,
+            event_sender,
+            event_broadcast,
+            response_channels: Default::default(),
+            fetch_progress: Default::default(),
+            received_blocks,
+            invalid_blocks,
+            store_reporter,
+            graphsync_invalid: Default::default(),
+            background_queries: Default::default(),
+            bitswap_queries: Default::default(),
+            _pending_requests: HashMap::default(),
+            pending_responses: HashMap::default(),
+            peers,
+            measurement_manager: MeasurementManager::default(),
+            relay_manager: RelayManager::new(config.relay_reservations, &config.bootstrap_nodes),
+            cached_content: CacheSummary::default(),
+            peer_cached_content: LruCache::new(max_cache_summaries),
+            kad_walk_interval: config.kad_walk_interval,
+            public_addr: None,
+            graphsync_pending: HashMap::default(),
+            reject_incompatible_peers: config.reject_incompatible_peers,
+            state_path: None,
+        };
+        service.restore_state();
+
+        Ok(service)
+    }
+
+    /// Persist the cache summary and routing table to `path` and restore them from it.
+    pub fn with_state_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.state_path = Some(path.as_ref().to_path_buf());
+        self.restore_state();
+        self
+    }
+
+    /// Build a [`Swarm`] listening on the configured addresses and dialing the bootstrap nodes.
+    /// Returns the swarm with its listeners.
+    #[allow(clippy::type_complexity)]
+    fn build_swarm(
+        keypair: &Keypair,
+        config: &NetworkConfig,
+        store: &UrsaStore<S>,
+        peers: &mut Manager,
+        store_reporter: &StoreReporter,
+    ) -> Result<(Swarm<Behaviour<S>>, HashMap<ListenerId, Multiaddr>)> {
         let local_peer_id = PeerId::from(keypair.public());
 
         let (relay_transport, relay_client) = if config.relay_client {
@@ -266,14 +524,14 @@ where
             (None, None)
         };
 
-        let transport = build_transport(&keypair, config, relay_transport);
-        let mut peers = Manager::new();
+        let transport = build_transport(keypair, config, relay_transport);
         let behaviour = Behaviour::new(
-            &keypair,
+            keypair,
             config,
-            store.as_ref().clone(),
+            store.clone(),
             relay_client,
-            &mut peers,
+            peers,
+            store_reporter.clone(),
         );
 
         let limits = ConnectionLimits::default()
@@ -294,10 +552,11 @@ where
             swarm.dial(to_dial.clone())?;
         }
 
+        let mut listeners = HashMap::new();
         for addr in &config.swarm_addrs {
-            Swarm::listen_on(&mut swarm, addr.clone())
-                .map_err(|err| anyhow!("{}", err))
-                .unwrap();
+            let listener_id =
+                Swarm::listen_on(&mut swarm, addr.clone()).map_err(|err| anyhow!("{}", err))?;
+            listeners.insert(listener_id, addr.clone());
         }
 
         // subscribe to topic
@@ -306,28 +565,133 @@ where
             warn!("Failed to subscribe to topic: {}", error);
         }
 
-        let (command_sender, command_receiver) = unbounded_channel();
+        Ok((swarm, listeners))
+    }
 
-        let max_cache_summaries = NonZeroUsize::new(config.max_cache_summaries).unwrap();
-        Ok(UrsaService {
-            swarm,
-            store,
-            command_sender,
-            command_receiver,
-            event_sender,
-            response_channels: Default::default(),
-            bitswap_queries: Default::default(),
-            _pending_requests: HashMap::default(),
-            pending_responses: HashMap::default(),
-            peers,
-            measurement_manager: MeasurementManager::default(),
-            bootstraps: config.bootstrap_nodes.clone(),
-            cached_content: CacheSummary::default(),
-            peer_cached_content: LruCache::new(max_cache_summaries),
-            kad_walk_interval: config.kad_walk_interval,
-            public_addr: None,
-            graphsync_pending: HashMap::default(),
-        })
+    /// Close the listeners and connections of the swarm, so that a new swarm can bind the
+    /// same ports. QUIC listeners can't share a port with the old swarm.
+    async fn release_swarm(&mut self) {
+        let mut closing: HashSet<ListenerId> = self.listeners.drain().map(|(id, _)| id).collect();
+        closing.extend(self.relay_manager.clear());
+        for listener_id in &closing {
+            self.swarm.remove_listener(*listener_id);
+        }
+        for peer_id in self.peers.peers() {
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+
+        let released = async {
+            while !closing.is_empty() || self.swarm.network_info().num_peers() > 0 {
+                match self.swarm.next().await {
+                    Some(SwarmEvent::ListenerClosed { listener_id, .. }) => {
+                        closing.remove(&listener_id);
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+        };
+        if timeout(SHUTDOWN_TIMEOUT, released).await.is_err() {
+            warn!("Timed out releasing the listeners and connections of the swarm");
+        }
+    }
+
+    /// Replace the swarm after a fatal transport error.
+    ///
+    /// Pending queries are failed and the routing table is carried over to the new swarm. The
+    /// old swarm is released before the new one binds its addresses. Gives up after
+    /// [`MAX_SWARM_RESTARTS`] consecutive failed attempts.
+    async fn restart_swarm(&mut self, error: Error) -> Result<()> {
+        error!("Restarting swarm after fatal error: {error:?}");
+        increment_counter!("network_swarm_restarts");
+
+        // graphsync requests are sent again on the new swarm
+        let graphsync_pending = std::mem::take(&mut self.graphsync_pending);
+        self.drain_pending_queries();
+        if let Err(e) = self.persist_state() {
+            warn!("Failed to persist network state before restart: {e:?}");
+        }
+        self.release_swarm().await;
+        self.relay_manager.set_private(false);
+
+        let mut backoff = Duration::from_secs(1);
+        for attempt in 1..=MAX_SWARM_RESTARTS {
+            let mut peers = Manager::new();
+            match Self::build_swarm(
+                &self.keypair,
+                &self.config,
+                &self.store,
+                &mut peers,
+                &self.store_reporter,
+            ) {
+                Ok((swarm, listeners)) => {
+                    // dropping the old swarm closes whatever it still holds
+                    self.swarm = swarm;
+                    self.listeners = listeners;
+                    self.peers = peers;
+                    self.restore_state();
+                    for (peer_id, cid) in graphsync_pending.into_values() {
+                        self.request_graphsync(peer_id, cid);
+                    }
+                    info!("Swarm restarted");
+                    return Ok(());
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to rebuild swarm (attempt {attempt}/{MAX_SWARM_RESTARTS}): {e:?}"
+                    );
+                    sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+        }
+
+        Self::fail_graphsync_requests(graphsync_pending, "restart");
+        Err(anyhow!(
+            "Failed to restart swarm after {MAX_SWARM_RESTARTS} attempts"
+        ))
+    }
+
+    /// Restart the swarm on a [`FatalSwarmError`], log and record any other handler error.
+    /// Fails only if the swarm couldn't be restarted.
+    async fn handle_result(&mut self, kind: &'static str, result: Result<()>) -> Result<()> {
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) if e.is::<FatalSwarmError>() => self.restart_swarm(e).await,
+            Err(e) => {
+                Self::record_handler_error(kind, &e);
+                Ok(())
+            }
+        }
+    }
+
+    /// Log a recoverable handler error and record it by event kind.
+    fn record_handler_error(kind: &'static str, error: &Error) {
+        error!("[{kind}] - failed to handle event: {error:?}");
+        increment_counter!("network_handler_errors", vec![Label::new("kind", kind)]);
+    }
+
+    fn swarm_event_kind(event: &SwarmEventType<S>) -> &'static str {
+        match event {
+            SwarmEvent::Behaviour(event) => match event {
+                BehaviourEvent::Ping(_) => "ping",
+                BehaviourEvent::Identify(_) => "identify",
+                BehaviourEvent::Autonat(_) => "autonat",
+                BehaviourEvent::RelayClient(_) => "relay_client",
+                BehaviourEvent::RelayServer(_) => "relay_server",
+                BehaviourEvent::Dcutr(_) => "dcutr",
+                BehaviourEvent::Mdns(_) => "mdns",
+                BehaviourEvent::Kad(_) => "kad",
+                BehaviourEvent::Bitswap(_) => "bitswap",
+                BehaviourEvent::Gossipsub(_) => "gossipsub",
+                BehaviourEvent::RequestResponse(_) => "request_response",
+                BehaviourEvent::Graphsync(_) => "graphsync",
+            },
+            SwarmEvent::ConnectionEstablished { .. } => "connection_established",
+            SwarmEvent::ConnectionClosed { .. } => "connection_closed",
+            SwarmEvent::ListenerClosed { .. } => "listener_closed",
+            _ => "swarm",
+        }
     }
 
     pub fn close_command_receiver(&mut self) {
@@ -338,7 +702,14 @@ where
         self.command_sender.clone()
     }
 
+    /// Sender of the event broadcast, new subscribers get every event emitted after they subscribed.
+    pub fn event_broadcast(&self) -> broadcast::Sender<NetworkEvent> {
+        self.event_broadcast.clone()
+    }
+
     fn emit_event(&mut self, event: NetworkEvent) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.event_broadcast.send(event.clone());
         let sender = self.event_sender.clone();
         tokio::task::spawn(async move {
             if let Err(error) = sender.send(event).await {
@@ -356,6 +727,7 @@ where
                     ping_event.peer.to_base58(),
                 );
                 self.peers.handle_rtt_received(rtt, ping_event.peer);
+                self.relay_manager.register_rtt(ping_event.peer, rtt);
                 self.measurement_manager.register_ping(ping_event.peer, rtt);
             }
             Ok(libp2p::ping::Success::Pong) => {
@@ -404,16 +776,39 @@ where
                 }
 
                 // check if received identify is from a peer on the same network
-                if info
+                let same_network = info
                     .protocols
                     .iter()
-                    .any(|name| name.as_bytes() == KAD_PROTOCOL)
-                {
+                    .any(|name| name.as_bytes() == KAD_PROTOCOL);
+                let listen_addrs = info.listen_addrs.clone();
+                let peer_info = PeerInfo::from(info);
+                let compatible = peer_info.is_compatible();
+
+                if same_network && !compatible {
+                    warn!(
+                        "[IdentifyEvent::Received] - peer {peer_id} runs incompatible version {}",
+                        peer_info.agent_version
+                    );
+                }
+                self.peers.insert_peer_info(peer_id, peer_info);
+
+                if same_network {
+                    if !compatible && self.reject_incompatible_peers {
+                        if self.swarm.disconnect_peer_id(peer_id).is_err() {
+                            debug!(
+                                "[IdentifyEvent::Received] - peer {peer_id} already disconnected"
+                            );
+                        }
+                        return Ok(());
+                    }
+
                     let behaviour = self.swarm.behaviour_mut();
 
-                    behaviour.gossipsub.add_explicit_peer(&peer_id);
+                    if compatible {
+                        behaviour.gossipsub.add_explicit_peer(&peer_id);
+                    }
 
-                    for address in info.listen_addrs {
+                    for address in listen_addrs {
                         behaviour.add_address(&peer_id, address);
                     }
                 }
@@ -428,29 +823,23 @@ where
     fn handle_autonat(&mut self, autonat_event: AutonatEvent) -> Result<(), Error> {
         match autonat_event {
             AutonatEvent::StatusChanged { old, new } => match (old, new) {
-                (NatStatus::Unknown, NatStatus::Private) => {
+                (_, NatStatus::Private) => {
                     if self.swarm.behaviour().relay_client.is_enabled() {
-                        if let Some(addr) = self.bootstraps.choose(&mut rand::thread_rng()) {
-                            let circuit_addr = addr.clone().with(Protocol::P2pCircuit);
-                            warn!(
-                                "Private NAT detected. Nodes should be publically accessable on 4890(udp) and 6009(tcp), as well as standard http(80) and https(443)! Falling back temporarily to public relay address on bootstrap node {}",
-                                circuit_addr
-                                    .clone()
-                                    .with(
-                                        Protocol::P2p(
-                                            self.swarm.local_peer_id().to_owned().into()
-                                        )
-                                    )
-                            );
-                            self.swarm
-                                .listen_on(circuit_addr)
-                                .expect("failed to listen on relay");
-                        }
+                        warn!(
+                            "Private NAT detected. Nodes should be publically accessable on 4890(udp) and 6009(tcp), as well as standard http(80) and https(443)! Falling back temporarily to public relay addresses on bootstrap nodes for {}",
+                            self.swarm.local_peer_id()
+                        );
+                        self.relay_manager.set_private(true);
+                        self.ensure_relay_reservations();
                     }
                 }
                 (_, NatStatus::Public(addr)) => {
                     info!("Public Nat verified! Public listening address: {}", addr);
                     self.public_addr = Some(addr);
+                    self.relay_manager.set_private(false);
+                    for listener_id in self.relay_manager.clear() {
+                        self.swarm.remove_listener(listener_id);
+                    }
                 }
                 (old, new) => {
                     warn!("NAT status changed from {:?} to {:?}", old, new);
@@ -461,6 +850,71 @@ where
         Ok(())
     }
 
+    /// Listen on the best available relays until we hold the configured number of reservations.
+    fn ensure_relay_reservations(&mut self) {
+        while let Some((peer_id, addr)) = self.relay_manager.next_candidate() {
+            let circuit_addr = addr.with(Protocol::P2pCircuit);
+            match self.swarm.listen_on(circuit_addr.clone()) {
+                Ok(listener_id) => {
+                    info!("Requesting relay reservation on {circuit_addr}");
+                    self.relay_manager
+                        .insert(peer_id, listener_id, circuit_addr);
+                }
+                Err(e) => {
+                    warn!("Failed to listen on relay {circuit_addr}: {e:?}");
+                    self.relay_manager.handle_failure(&peer_id);
+                }
+            }
+        }
+    }
+
+    fn handle_relay_client(&mut self, relay_event: RelayClientEvent) -> Result<()> {
+        match relay_event {
+            RelayClientEvent::ReservationReqAccepted {
+                relay_peer_id,
+                renewal,
+                ..
+            } => {
+                debug!("[RelayClientEvent::ReservationReqAccepted] - relay {relay_peer_id}, renewal: {renewal}");
+                self.relay_manager.handle_accepted(&relay_peer_id, renewal);
+            }
+            RelayClientEvent::ReservationReqFailed {
+                relay_peer_id,
+                error,
+                ..
+            } => {
+                warn!(
+                    "[RelayClientEvent::ReservationReqFailed] - relay {relay_peer_id}: {error:?}"
+                );
+                if let Some(listener_id) = self.relay_manager.handle_failure(&relay_peer_id) {
+                    self.swarm.remove_listener(listener_id);
+                }
+                self.ensure_relay_reservations();
+            }
+            event => trace!("[RelayClientEvent] - {event:?}"),
+        }
+        Ok(())
+    }
+
+    fn handle_dcutr(&mut self, dcutr_event: DcutrEvent) -> Result<()> {
+        match dcutr_event {
+            DcutrEvent::DirectConnectionUpgradeSucceeded { remote_peer_id } => {
+                debug!("[DcutrEvent::DirectConnectionUpgradeSucceeded] - {remote_peer_id}");
+                self.relay_manager.handle_dcutr(true);
+            }
+            DcutrEvent::DirectConnectionUpgradeFailed {
+                remote_peer_id,
+                error,
+            } => {
+                debug!("[DcutrEvent::DirectConnectionUpgradeFailed] - {remote_peer_id}: {error:?}");
+                self.relay_manager.handle_dcutr(false);
+            }
+            DcutrEvent::InitiatedDirectConnectionUpgrade { .. }
+            | DcutrEvent::RemoteInitiatedDirectConnectionUpgrade { .. } => (),
+        }
+        Ok(())
+    }
+
     fn handle_bitswap(&mut self, bitswap_event: BitswapEvent) -> Result<()> {
         match bitswap_event {
             BitswapEvent::Progress(query_id, _) => {
@@ -468,9 +922,23 @@ where
                     "[BitswapEvent::Progress] - bitswap request in progress with, id: {}",
                     query_id
                 );
+                self.report_progress()?;
             }
             BitswapEvent::Complete(query_id, result) => {
-                if let Some(cid) = self.bitswap_queries.remove(&query_id) {
+                if let Some((cid, _)) = self.bitswap_queries.remove(&query_id) {
+                    if result.is_ok() {
+                        self.report_progress()?;
+                    }
+                    if self.background_queries.remove(&query_id) {
+                        match result {
+                            Ok(()) => {
+                                let size = self.store.car_size(&cid)?;
+                                self.complete_pull(cid, size)?;
+                            }
+                            Err(_) => warn!("[BitswapEvent::Complete] - failed to complete {cid} after graphsync"),
+                        }
+                    }
+                    self.fetch_progress.remove(&cid);
                     if let Some(chans) = self.response_channels.remove(&cid) {
                         for chan in chans.into_iter() {
                             match result {
@@ -497,6 +965,74 @@ where
         Ok(())
     }
 
+    /// Account the blocks bitswap received since the last call and send the progress of the
+    /// fetches they belong to to their subscribers.
+    fn report_progress(&mut self) -> Result<()> {
+        while let Ok(block) = self.received_blocks.try_recv() {
+            for fetch in self.fetch_progress.values_mut() {
+                if fetch.insert_block(&self.store, &block)? {
+                    fetch
+                        .subscribers
+                        .retain(|chan| chan.send(fetch.progress).is_ok());
+                }
+            }
+        }
+        self.fetch_progress
+            .retain(|_, fetch| fetch.subscribers.iter().any(|chan| !chan.is_closed()));
+        Ok(())
+    }
+
+    /// Fail bitswap queries past their deadline and cancel the ones nobody waits on anymore.
+    fn sweep_bitswap_queries(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<(QueryId, Cid, bool)> = self
+            .bitswap_queries
+            .iter()
+            .filter_map(|(query_id, (cid, deadline))| {
+                let abandoned = !self.background_queries.contains(query_id)
+                    && self
+                        .response_channels
+                        .get(cid)
+                        .map(|chans| chans.iter().all(|chan| chan.is_closed()))
+                        .unwrap_or(true);
+                if abandoned || now >= *deadline {
+                    Some((*query_id, *cid, abandoned))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        for (query_id, cid, abandoned) in expired {
+            self.bitswap_queries.remove(&query_id);
+            self.background_queries.remove(&query_id);
+            self.swarm.behaviour_mut().bitswap.cancel(query_id);
+            if abandoned {
+                debug!("[BitswapEvent] - cancelled query {query_id} for {cid}, receiver dropped");
+                increment_counter!("bitswap_query_cancelled");
+            } else {
+                warn!("[BitswapEvent] - query {query_id} for {cid} timed out");
+                increment_counter!("bitswap_query_timeout");
+            }
+
+            // other queries for the same cid keep their channels
+            if self.bitswap_queries.values().any(|(c, _)| *c == cid) {
+                continue;
+            }
+            self.fetch_progress.remove(&cid);
+            for chan in self.response_channels.remove(&cid).unwrap_or_default() {
+                if chan
+                    .send(Err(anyhow!(
+                        "Timed out fetching the block with cid {cid:?} from peers"
+                    )))
+                    .is_err()
+                {
+                    trace!("[BitswapEvent] - response channel for {cid} already closed");
+                }
+            }
+        }
+    }
+
     fn handle_gossip(&mut self, gossip_event: libp2p::gossipsub::GossipsubEvent) -> Result<()> {
         match gossip_event {
             libp2p::gossipsub::GossipsubEvent::Message {
@@ -591,26 +1127,10 @@ where
                         RequestType::CacheRequest(cid) => {
                             info!("[BehaviourEvent::RequestMessage] cache request from {peer} for {cid}");
 
-                            let selector = Selector::ExploreRecursive {
-                                limit: RecursionLimit::None,
-                                sequence: Box::new(Selector::ExploreAll {
-                                    next: Box::new(Selector::ExploreRecursiveEdge),
-                                }),
-                                current: None,
-                            };
-
-                            let req = Request::builder()
-                                .root(cid.to_bytes())
-                                .selector(selector)
-                                .build()
-                                .unwrap();
-                            self.graphsync_pending.insert(*req.id(), cid);
-                            let swarm = self.swarm.behaviour_mut();
-                            let gs_req_id = req.id().urn().to_string();
-                            swarm.graphsync.request(peer, req);
-                            self.measurement_manager
-                                .register_request(peer, gs_req_id, 0);
-                            if swarm
+                            self.request_graphsync(peer, cid);
+                            if self
+                                .swarm
+                                .behaviour_mut()
                                 .request_response
                                 .send_response(
                                     channel,
@@ -674,6 +1194,42 @@ where
         Ok(())
     }
 
+    /// Pull the whole dag of `cid` from `peer` over graphsync.
+    fn request_graphsync(&mut self, peer: PeerId, cid: Cid) {
+        let selector = Selector::ExploreRecursive {
+            limit: RecursionLimit::None,
+            sequence: Box::new(Selector::ExploreAll {
+                next: Box::new(Selector::ExploreRecursiveEdge),
+            }),
+            current: None,
+        };
+
+        let req = Request::builder()
+            .root(cid.to_bytes())
+            .selector(selector)
+            .build()
+            .unwrap();
+        self.graphsync_pending.insert(*req.id(), (peer, cid));
+        let gs_req_id = req.id().urn().to_string();
+        self.swarm.behaviour_mut().graphsync.request(peer, req);
+        self.measurement_manager
+            .register_request(peer, gs_req_id, 0);
+    }
+
+    /// Give up on graphsync requests that can't complete anymore.
+    fn fail_graphsync_requests(
+        pending: HashMap<GraphSyncReqId, (PeerId, Cid)>,
+        reason: &'static str,
+    ) {
+        for (id, (peer_id, cid)) in pending {
+            warn!("Graphsync request {id} for {cid} from {peer_id} failed on {reason}");
+            increment_counter!(
+                "graphsync_requests_failed",
+                vec![Label::new("reason", reason)]
+            );
+        }
+    }
+
     fn handle_graphsync(&mut self, event: GraphSyncEvent) -> Result<()> {
         match event {
             GraphSyncEvent::Completed {
@@ -682,17 +1238,36 @@ where
                 received,
             } => {
                 info!("[GraphSyncEvent::Completed]: {peer_id} {received}");
-                if let Some(cid) = self.graphsync_pending.remove(&id) {
-                    self.measurement_manager.register_response(
-                        peer_id,
-                        id.urn().to_string(),
-                        received as u128,
+                if let Some((_, cid)) = self.graphsync_pending.remove(&id) {
+                    while let Ok(invalid) = self.invalid_blocks.try_recv() {
+                        self.graphsync_invalid.insert(invalid);
+                    }
+                    let invalid = if self.graphsync_pending.is_empty() {
+                        std::mem::take(&mut self.graphsync_invalid)
+                    } else {
+                        self.graphsync_invalid.clone()
+                    };
+                    let missing = self.store.missing_blocks(&cid)?;
+                    if missing.is_empty() {
+                        self.measurement_manager.register_response(
+                            peer_id,
+                            id.urn().to_string(),
+                            received as u128,
+                        );
+                        return self.complete_pull(cid, received as u64);
+                    }
+
+                    // blocks failing verification are not stored and leave holes in the dag,
+                    // other holes are blocks the peer doesn't have
+                    if let Some(invalid) = missing.iter().find(|cid| invalid.contains(cid)) {
+                        self.handle_invalid_block(*invalid, Some(peer_id));
+                    }
+                    info!(
+                        "[GraphSyncEvent::Completed]: {} blocks of {cid} missing, fetching them over bitswap",
+                        missing.len()
                     );
-                    self.update_and_share_cache_summary(&cid)?;
-                    self.emit_event(NetworkEvent::PullComplete {
-                        cid,
-                        size: received as u64,
-                    })
+                    increment_counter!("graphsync_requests_incomplete");
+                    self.fetch_in_background(cid);
                 } else {
                     error!("Failed to find CID for request {id}")
                 }
@@ -700,10 +1275,15 @@ where
             }
             GraphSyncEvent::Error { id, peer_id, error } => {
                 info!("[GraphSyncEvent::Error]: received {error} from {peer_id}");
-                if self.graphsync_pending.remove(&id).is_none() {
-                    debug!(
+                match self.graphsync_pending.remove(&id) {
+                    // the peer may have sent part of the dag before failing
+                    Some((_, cid)) if !self.store.missing_blocks(&cid)?.is_empty() => {
+                        self.fetch_in_background(cid);
+                    }
+                    Some(_) => (),
+                    None => debug!(
                         "[GraphSyncEvent::Error]: there was no pending request for request {id}"
-                    );
+                    ),
                 }
                 Ok(())
             }
@@ -751,8 +1331,14 @@ where
                     relay_event.record();
                     Ok(())
                 }
-                BehaviourEvent::RelayClient(_) => Ok(()),
-                BehaviourEvent::Dcutr(_) => Ok(()),
+                BehaviourEvent::RelayClient(relay_event) => {
+                    relay_event.record();
+                    self.handle_relay_client(relay_event)
+                }
+                BehaviourEvent::Dcutr(dcutr_event) => {
+                    dcutr_event.record();
+                    self.handle_dcutr(dcutr_event)
+                }
                 BehaviourEvent::Graphsync(event) => self.handle_graphsync(event),
             },
             SwarmEvent::ConnectionEstablished { peer_id, .. } => {
@@ -774,14 +1360,64 @@ where
                 }
                 Ok(())
             }
+            SwarmEvent::ListenerClosed {
+                listener_id,
+                addresses,
+                reason,
+            } => {
+                if let Some(peer_id) = self.relay_manager.handle_listener_closed(&listener_id) {
+                    warn!("Relay listener on {peer_id} closed, rotating reservation");
+                    self.ensure_relay_reservations();
+                    return Ok(());
+                }
+                let addr = match (self.listeners.remove(&listener_id), reason) {
+                    (Some(addr), Err(e)) => {
+                        warn!("Listener on {addresses:?} closed: {e}, listening again on {addr}");
+                        addr
+                    }
+                    // closed on purpose, or not one of the configured listeners
+                    _ => return Ok(()),
+                };
+                match self.swarm.listen_on(addr.clone()) {
+                    Ok(listener_id) => {
+                        self.listeners.insert(listener_id, addr);
+                        Ok(())
+                    }
+                    // the swarm is unreachable once its last listener is gone
+                    Err(e) if self.listeners.is_empty() => Err(FatalSwarmError::NoListeners {
+                        addr,
+                        reason: e.to_string(),
+                    }
+                    .into()),
+                    Err(e) => Err(anyhow!("Failed to listen again on {addr}: {e}")),
+                }
+            }
+            SwarmEvent::ListenerError { listener_id, error } => {
+                // the listener stays open, it is only replaced once it closes
+                warn!("Listener {listener_id:?} reported an error: {error}");
+                Ok(())
+            }
             _ => Ok(()),
         }
     }
 
+    /// Answer a bitswap request that couldn't be started with `error`.
+    fn fail_bitswap_request(sender: BlockOneShotSender<()>, cid: Cid, error: Error) -> Result<()> {
+        let message = format!("Failed to start bitswap query for {cid}: {error}");
+        if sender.send(Err(error)).is_err() {
+            debug!("Failed to send bitswap error for {cid}, receiver dropped");
+        }
+        Err(anyhow!(message))
+    }
+
     /// Handle commands
     pub fn handle_command(&mut self, command: NetworkCommand) -> Result<()> {
         match command {
-            NetworkCommand::GetBitswap { cid, sender } => {
+            NetworkCommand::GetBitswap {
+                cid,
+                sender,
+                progress,
+            } => {
                 info!("Getting cid {cid} via bitswap");
 
                 let peers = self.peers.peers();
@@ -796,13 +1432,17 @@ where
                     )))
                         .map_err(|_| anyhow!("Failed to get a bitswap block!"));
                 } else {
-                    if let Some(chans) = self.response_channels.get_mut(&cid) {
-                        chans.push(sender);
-                    } else {
-                        self.response_channels.insert(cid, vec![sender]);
-                    }
-
-                    let peers = peers
+                    // the sender is only registered with a running query, so the sweep of the
+                    // queries always answers it
+                    let new_progress = match (&progress, self.fetch_progress.contains_key(&cid)) {
+                        (Some(_), false) => match FetchProgress::new(&self.store, cid) {
+                            Ok(fetch) => Some(fetch),
+                            Err(e) => return Self::fail_bitswap_request(sender, cid, e),
+                        },
+                        _ => None,
+                    };
+
+                    let mut peers: Vec<PeerId> = peers
                         .iter()
                         .filter(|peer| {
                             if let Some(cache_summary) = self.peer_cached_content.get(*peer) {
@@ -812,18 +1452,25 @@ where
                         })
                         .copied()
                         .collect();
-
-                    let query = self.swarm.behaviour_mut().sync_block(cid, peers);
-
-                    if let Ok(query_id) = query {
-                        self.bitswap_queries.insert(query_id, cid);
-                        self.emit_event(NetworkEvent::BitswapWant { cid, query_id });
-                    } else {
-                        error!(
-                            "[NetworkCommand::BitswapWant] - no block found for cid {:?}.",
-                            cid
-                        )
+                    self.peers.prioritize(&mut peers);
+
+                    let query_id = match self.swarm.behaviour_mut().sync_block(cid, peers) {
+                        Ok(query_id) => query_id,
+                        Err(e) => return Self::fail_bitswap_request(sender, cid, e),
+                    };
+                    let deadline =
+                        Instant::now() + Duration::from_secs(self.config.bitswap_timeout);
+                    self.bitswap_queries.insert(query_id, (cid, deadline));
+                    self.response_channels.entry(cid).or_default().push(sender);
+                    if let Some(fetch) = new_progress {
+                        self.fetch_progress.insert(cid, fetch);
+                    }
+                    if let (Some(progress), Some(fetch)) =
+                        (progress, self.fetch_progress.get_mut(&cid))
+                    {
+                        fetch.subscribers.push(progress);
                     }
+                    self.emit_event(NetworkEvent::BitswapWant { cid, query_id });
                 }
             }
             NetworkCommand::Put { cid, sender } => {
@@ -856,6 +1503,16 @@ where
                     .send(addresses.into_iter().cloned().collect())
                     .map_err(|_| anyhow!("Failed to get listener addresses from network"))?;
             }
+            NetworkCommand::GetPeerInfo { peer_id, sender } => {
+                sender
+                    .send(self.peers.peer_info(&peer_id).cloned())
+                    .map_err(|_| anyhow!("Failed to get peer info for {peer_id}!"))?;
+            }
+            NetworkCommand::GetRelayState { sender } => {
+                sender
+                    .send(self.relay_manager.state())
+                    .map_err(|_| anyhow!("Failed to get relay state!"))?;
+            }
             NetworkCommand::SendRequest {
                 peer_id,
                 request,
@@ -923,10 +1580,52 @@ where
                         .map_err(|_| anyhow!("Failed to publish message!"))?;
                 }
             },
+            NetworkCommand::ReportInvalidBlock { cid, peer_id } => {
+                self.handle_invalid_block(cid, peer_id)
+            }
         }
         Ok(())
     }
 
+    /// Record a block that failed verification and drop the peer that served it.
+    fn handle_invalid_block(&mut self, cid: Cid, peer_id: Option<PeerId>) {
+        let source = if peer_id.is_some() { "peer" } else { "origin" };
+        increment_counter!("invalid_blocks", vec![Label::new("source", source)]);
+
+        match peer_id {
+            Some(peer_id) => {
+                warn!("Peer {peer_id} served invalid content for {cid}, disconnecting");
+                self.peer_cached_content.pop(&peer_id);
+                if self.swarm.disconnect_peer_id(peer_id).is_err() {
+                    debug!("Peer {peer_id} was not connected");
+                }
+            }
+            None => warn!("Origin served invalid content for {cid}"),
+        }
+    }
+
+    /// Fetch a dag over bitswap with nobody waiting on it, i.e. to complete a dag pulled
+    /// from a peer that lacked part of it.
+    fn fetch_in_background(&mut self, cid: Cid) {
+        let mut peers: Vec<PeerId> = self.peers.peers().into_iter().collect();
+        self.peers.prioritize(&mut peers);
+        match self.swarm.behaviour_mut().sync_block(cid, peers) {
+            Ok(query_id) => {
+                let deadline = Instant::now() + Duration::from_secs(self.config.bitswap_timeout);
+                self.bitswap_queries.insert(query_id, (cid, deadline));
+                self.background_queries.insert(query_id);
+            }
+            Err(e) => warn!("Failed to fetch {cid} over bitswap: {e:?}"),
+        }
+    }
+
+    /// Share a dag pulled in full from the network.
+    fn complete_pull(&mut self, cid: Cid, size: u64) -> Result<()> {
+        self.update_and_share_cache_summary(&cid)?;
+        self.emit_event(NetworkEvent::PullComplete { cid, size });
+        Ok(())
+    }
+
     fn update_and_share_cache_summary(&mut self, cid: &Cid) -> Result<()> {
         self.cached_content.insert(cid.to_bytes());
         let swarm = self.swarm.behaviour_mut();
@@ -945,6 +1644,175 @@ where
         Ok(())
     }
 
+    /// Load the cache summary and kademlia routing table persisted on the last shutdown.
+    fn restore_state(&mut self) {
+        let path = match &self.state_path {
+            Some(path) => path,
+            None => return,
+        };
+        let state = match fs::read(path) {
+            Ok(bytes) => match bincode::deserialize::<NetworkState>(&bytes) {
+                Ok(state) => state,
+                Err(e) => {
+                    warn!("Failed to decode network state {path:?}: {e}");
+                    return;
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!("Failed to read network state {path:?}: {e}");
+                return;
+            }
+        };
+
+        if let Some(bytes) = state.cache_summary {
+            match CacheSummary::deserialize(&bytes) {
+                Ok(cache_summary) => self.cached_content = cache_summary,
+                Err(e) => warn!("Failed to restore cache summary: {e}"),
+            }
+        }
+
+        info!(
+            "Restoring {} peers into the routing table",
+            state.kad_peers.len()
+        );
+        let kad = &mut self.swarm.behaviour_mut().kad;
+        for (peer_id, addrs) in state.kad_peers {
+            for addr in addrs {
+                kad.add_address(&peer_id, addr);
+            }
+        }
+    }
+
+    /// Persist the cache summary and kademlia routing table to the state file.
+    fn persist_state(&mut self) -> Result<()> {
+        // the store statistics are recounted if lost, they don't hold back the network state
+        if let Err(e) = self.store.persist_stats() {
+            warn!("Failed to persist store statistics: {e:?}");
+        }
+        let path = match &self.state_path {
+            Some(path) => path.clone(),
+            None => return Ok(()),
+        };
+
+        let mut kad_peers = Vec::new();
+        for bucket in self.swarm.behaviour_mut().kad.kbuckets() {
+            for entry in bucket.iter() {
+                kad_peers.push((
+                    *entry.node.key.preimage(),
+                    entry.node.value.clone().into_vec(),
+                ));
+            }
+        }
+        let peers = kad_peers.len();
+        let state = NetworkState {
+            cache_summary: Some(self.cached_content.serialize()?),
+            kad_peers,
+        };
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // write to a temporary file first so a crash never leaves a partial state
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bincode::serialize(&state)?)?;
+        fs::rename(tmp, &path)?;
+
+        info!("Persisted cache summary and {peers} routing table peers to {path:?}");
+        Ok(())
+    }
+
+    /// Fail a command that arrived while shutting down.
+    fn reject_command(command: NetworkCommand) {
+        let error = || anyhow!("The network service is shutting down");
+        let sent = match command {
+            NetworkCommand::GetBitswap { sender, .. } => sender.send(Err(error())).is_ok(),
+            NetworkCommand::Put { sender, .. } => sender.send(Err(error())).is_ok(),
+            NetworkCommand::SendRequest { channel, .. } => channel.send(Err(error())).is_ok(),
+            // dropping the remaining senders fails the receiving end
+            _ => true,
+        };
+        if !sent {
+            debug!("Failed to reject network command, receiver dropped");
+        }
+    }
+
+    /// Cancel in-flight queries and fail everyone waiting on them.
+    fn drain_pending_queries(&mut self) {
+        for (query_id, (cid, _)) in self.bitswap_queries.drain() {
+            debug!("Cancelling bitswap query {query_id} for {cid}");
+            self.swarm.behaviour_mut().bitswap.cancel(query_id);
+        }
+        self.fetch_progress.clear();
+        self.background_queries.clear();
+        for (cid, chans) in self.response_channels.drain() {
+            for chan in chans {
+                if chan
+                    .send(Err(anyhow!(
+                        "The network service shut down before {cid} was fetched"
+                    )))
+                    .is_err()
+                {
+                    debug!("Bitswap response channel for {cid} already closed");
+                }
+            }
+        }
+        for (request_id, channel) in self.pending_responses.drain() {
+            if channel
+                .send(Err(anyhow!("The network service is shutting down")))
+                .is_err()
+            {
+                debug!("Response channel for {request_id} already closed");
+            }
+        }
+        Self::fail_graphsync_requests(std::mem::take(&mut self.graphsync_pending), "shutdown");
+    }
+
+    /// Disconnect from all peers and wait for the connections to close.
+    async fn close_connections(&mut self) {
+        for peer_id in self.peers.peers() {
+            if self.swarm.disconnect_peer_id(peer_id).is_err() {
+                self.peers.remove(&peer_id);
+            }
+        }
+
+        let closed = async {
+            while !self.peers.ref_peers().is_empty() {
+                match self.swarm.next().await {
+                    Some(event) => {
+                        if let Err(e) = self.handle_swarm_event(event) {
+                            debug!("Error handling swarm event during shutdown: {e:?}");
+                        }
+                    }
+                    None => break,
+                }
+            }
+        };
+        if timeout(SHUTDOWN_TIMEOUT, closed).await.is_err() {
+            warn!("Timed out waiting for connections to close");
+        }
+    }
+
+    /// Gracefully shut the service down.
+    ///
+    /// Stops accepting commands, fails pending queries, persists the cache summary and
+    /// routing table, and closes all connections.
+    async fn shutdown(&mut self) {
+        info!("Shutting down the network service");
+        self.command_receiver.close();
+        while let Ok(command) = self.command_receiver.try_recv() {
+            Self::reject_command(command);
+        }
+
+        self.drain_pending_queries();
+
+        if let Err(e) = self.persist_state() {
+            error!("Failed to persist network state: {e:?}");
+        }
+
+        self.close_connections().await;
+    }
+
     /// Dial remote peer `peer_id` at `address`
     pub fn dial(
         &mut self,
@@ -970,12 +1838,23 @@ where
         }
     }
 
+    /// Start the ursa network service loop without a shutdown signal.
+    pub async fn start(self) -> Result<()> {
+        self.start_with_shutdown(ShutdownController::default())
+            .await
+    }
+
     /// Start the ursa network service loop.
     ///
     /// Poll `swarm` and `command_receiver` from [`UrsaService`].
     /// - `swarm` handles the network events [Event].
     /// - `command_receiver` handles inbound commands [Command].
-    pub async fn start(mut self) -> Result<()> {
+    ///
+    /// Once `shutdown_controller` fires, the service shuts down gracefully and returns.
+    pub async fn start_with_shutdown(
+        mut self,
+        shutdown_controller: ShutdownController,
+    ) -> Result<()> {
         info!(
             "Node starting up with peerId {:?}",
             self.swarm.local_peer_id()
@@ -984,23 +1863,55 @@ where
         let kad_walk_delay = sleep(Duration::from_secs(self.kad_walk_interval));
         tokio::pin!(kad_walk_delay);
 
+        let relay_retry_delay = sleep(RELAY_RETRY_INTERVAL);
+        tokio::pin!(relay_retry_delay);
+
+        let bitswap_sweep_delay = sleep(BITSWAP_SWEEP_INTERVAL);
+        tokio::pin!(bitswap_sweep_delay);
+
+        let shutdown_future = shutdown_controller.notify.notified();
+        tokio::pin!(shutdown_future);
+
         loop {
             select! {
                 event = self.swarm.next() => {
-                    let event = event.ok_or_else(|| anyhow!("Swarm Event invalid!"))?;
-                    self.handle_swarm_event(event).expect("Handle swarm event.");
+                    let event = match event {
+                        Some(event) => event,
+                        None => {
+                            self.restart_swarm(FatalSwarmError::StreamEnded.into()).await?;
+                            continue;
+                        }
+                    };
+                    let kind = Self::swarm_event_kind(&event);
+                    let result = self.handle_swarm_event(event);
+                    self.handle_result(kind, result).await?;
                 },
                 command = self.command_receiver.recv() => {
                     let command = command.ok_or_else(|| anyhow!("Command invalid!"))?;
-                    self.handle_command(command).expect("Handle rpc command.");
+                    let kind = command.kind();
+                    let result = self.handle_command(command);
+                    self.handle_result(kind, result).await?;
                 },
                 _ = &mut kad_walk_delay => {
                     info!("Starting random kademlia walk");
                     self.swarm.behaviour_mut().kad.get_closest_peers(PeerId::random());
                     kad_walk_delay.as_mut().reset(Instant::now() + Duration::from_secs(self.kad_walk_interval));
                 }
+                _ = &mut relay_retry_delay => {
+                    self.relay_manager.reset_failed();
+                    self.ensure_relay_reservations();
+                    relay_retry_delay.as_mut().reset(Instant::now() + RELAY_RETRY_INTERVAL);
+                }
+                _ = &mut bitswap_sweep_delay => {
+                    self.sweep_bitswap_queries();
+                    bitswap_sweep_delay.as_mut().reset(Instant::now() + BITSWAP_SWEEP_INTERVAL);
+                }
+                _ = &mut shutdown_future => break,
             }
         }
+
+        self.shutdown().await;
+        Ok(())
     }
 }
 
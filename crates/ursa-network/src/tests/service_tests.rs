@@ -23,11 +23,12 @@ use libp2p_bitswap::BitswapStore;
 use simple_logger::SimpleLogger;
 use std::path::Path;
 use std::{sync::Arc, time::Duration, vec};
-use tokio::sync::mpsc::channel;
+use tokio::sync::mpsc::{channel, unbounded_channel};
 use tokio::{select, sync::oneshot, time::timeout};
 use tracing::warn;
 use tracing::{error, info, log::LevelFilter};
 use ursa_store::{BitswapStorage, UrsaStore};
+use ursa_utils::shutdown::ShutdownController;
 
 fn create_block(ipld: Ipld) -> Block<DefaultParams> {
     Block::encode(DagCborCodec, Code::Blake3_256, &ipld).unwrap()
@@ -338,9 +339,11 @@ async fn test_bitswap_get() -> Result<()> {
     tokio::task::spawn(async move { node_2.start().await.unwrap() });
 
     let (sender, receiver) = oneshot::channel();
+    let (progress_sender, mut progress_receiver) = unbounded_channel();
     let msg = NetworkCommand::GetBitswap {
         cid: *block.cid(),
         sender,
+        progress: Some(progress_sender),
     };
 
     assert!(node_2_sender.send(msg).is_ok());
@@ -358,6 +361,11 @@ async fn test_bitswap_get() -> Result<()> {
                 store_1_block
             );
             assert_eq!(store_1_block, Some(block.data().to_vec()));
+
+            let progress = progress_receiver.recv().await.unwrap();
+            assert_eq!(progress.blocks_fetched, 1);
+            assert_eq!(progress.bytes_fetched, block.data().len() as u64);
+            assert_eq!(progress.blocks_missing, 0);
         }
         Err(e) => panic!("{e:?}"),
     }
@@ -365,6 +373,46 @@ async fn test_bitswap_get() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn test_bitswap_timeout() -> Result<()> {
+    setup_logger(LevelFilter::Info);
+    let mut config = NetworkConfig {
+        mdns: true,
+        bitswap_timeout: 1,
+        ..Default::default()
+    };
+
+    let (mut node_1, node_1_addrs, ..) = network_init(&mut config, None, None).await?;
+    let (node_2, ..) = network_init(&mut config, Some(node_1_addrs), None).await?;
+
+    loop {
+        if let SwarmEvent::ConnectionEstablished { .. } = node_1.swarm.select_next_some().await {
+            break;
+        }
+    }
+
+    let node_2_sender = node_2.command_sender();
+    tokio::task::spawn(async move { node_1.start().await.unwrap() });
+    tokio::task::spawn(async move { node_2.start().await.unwrap() });
+
+    // Neither node has the block, so the request times out instead of hanging.
+    let block = get_block(&b"missing"[..]);
+    let (sender, receiver) = oneshot::channel();
+    let msg = NetworkCommand::GetBitswap {
+        cid: *block.cid(),
+        sender,
+        progress: None,
+    };
+    assert!(node_2_sender.send(msg).is_ok());
+
+    let res = timeout(Duration::from_secs(10), receiver)
+        .await
+        .expect("bitswap request to time out")?;
+    assert!(res.is_err());
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_bitswap_sync() -> Result<()> {
     setup_logger(LevelFilter::Info);
@@ -414,6 +462,7 @@ async fn test_bitswap_sync() -> Result<()> {
     let msg = NetworkCommand::GetBitswap {
         cid: cids[0],
         sender,
+        progress: None,
     };
 
     assert!(node_2_sender.send(msg).is_ok());
@@ -597,3 +646,81 @@ async fn test_send_cache_summary() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn test_graceful_shutdown() -> Result<()> {
+    setup_logger(LevelFilter::Info);
+    let mut config = NetworkConfig::default();
+
+    let dir = tempfile::tempdir()?;
+    let state_path = dir.path().join("network_state");
+    let (node, .., store) = network_init(&mut config, None, None).await?;
+    let node = node.with_state_path(&state_path);
+    let node_sender = node.command_sender();
+
+    let shutdown_controller = ShutdownController::default();
+    let shutdown = shutdown_controller.clone();
+    let handle = tokio::task::spawn(async move { node.start_with_shutdown(shutdown).await });
+
+    // Cache a block so that it ends up in the cache summary.
+    let block = get_block(&b"hello world"[..]);
+    store.put_keyed(block.cid(), block.data()).unwrap();
+    let (sender, receiver) = oneshot::channel();
+    let request = NetworkCommand::Put {
+        cid: *block.cid(),
+        sender,
+    };
+    assert!(node_sender.send(request).is_ok());
+    assert!(receiver.await?.is_ok());
+
+    shutdown_controller.shutdown();
+    timeout(Duration::from_secs(10), handle)
+        .await
+        .expect("service to shut down")??;
+
+    // The service no longer accepts commands.
+    let (sender, _) = oneshot::channel();
+    let request = NetworkCommand::Put {
+        cid: *block.cid(),
+        sender,
+    };
+    assert!(node_sender.send(request).is_err());
+
+    // The state is kept out of the blockstore, a new service restores it from the state file.
+    assert!(state_path.exists());
+    let (sender, _) = channel(4096);
+    let node = UrsaService::new(Keypair::generate_ed25519(), &config, store, sender)?;
+    assert!(!node.cached_content.contains(block.cid().to_bytes()));
+    let node = node.with_state_path(&state_path);
+    assert!(node.cached_content.contains(block.cid().to_bytes()));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_command_error_does_not_stop_service() -> Result<()> {
+    setup_logger(LevelFilter::Info);
+    let mut config = NetworkConfig::default();
+
+    let (node, ..) = network_init(&mut config, None, None).await?;
+    let node_sender = node.command_sender();
+    let handle = tokio::task::spawn(async move { node.start().await });
+
+    // Dropping the receiver makes the handler fail.
+    let (sender, receiver) = oneshot::channel();
+    drop(receiver);
+    assert!(node_sender
+        .send(NetworkCommand::GetPeers { sender })
+        .is_ok());
+
+    // The service keeps processing commands.
+    let (sender, receiver) = oneshot::channel();
+    assert!(node_sender
+        .send(NetworkCommand::GetPeers { sender })
+        .is_ok());
+    assert!(timeout(Duration::from_secs(5), receiver).await?.is_ok());
+    assert!(!handle.is_finished());
+
+    handle.abort();
+    Ok(())
+}
@@ -3,55 +3,68 @@ use async_fs::{create_dir_all, File};
 use async_trait::async_trait;
 use axum::body::StreamBody;
 use db::Store;
-use ethers::core::types::TransactionRequest;
+use ethers::core::types::{Bytes, TransactionReceipt, TransactionRequest, H256};
 use futures::channel::mpsc::unbounded;
 use futures::io::BufReader;
-use futures::{AsyncRead, AsyncWriteExt, SinkExt};
+use futures::{stream, AsyncRead, AsyncWriteExt, SinkExt, StreamExt};
 use fvm_ipld_blockstore::Blockstore;
-use fvm_ipld_car::{load_car, CarHeader, CarReader};
+use fvm_ipld_car::CarHeader;
 use libipld::Cid;
 use libp2p::{Multiaddr, PeerId};
 use narwhal_types::{TransactionProto, TransactionsClient};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::{
     hash_map::{Entry, HashMap},
-    HashSet,
+    BTreeMap, HashSet,
 };
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 use std::task::{Context, Poll};
-use surf::{http::Method, Client, RequestBuilder};
 use tendermint_proto::abci::ResponseQuery;
+use thiserror::Error;
 use tokio::sync::{
+    broadcast,
     mpsc::{unbounded_channel, Sender as BoundedSender, UnboundedSender as Sender},
-    oneshot, RwLock,
+    oneshot, watch, RwLock,
 };
 use tokio::task;
 use tokio_util::{compat::TokioAsyncWriteCompatExt, io::ReaderStream};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use ursa_consensus::AbciQueryQuery;
 use ursa_index_provider::engine::ProviderCommand;
-use ursa_network::NetworkCommand;
-use ursa_store::UrsaStore;
+use ursa_network::{
+    BitswapProgress, GossipsubEvent, NetworkCommand, NetworkEvent, PeerInfo, RelayState,
+};
+use ursa_store::{AsyncStore, BlockError, DagSelection, StoreStats, UrsaStore};
 
-use crate::config::OriginConfig;
+use crate::origin::{OriginError, OriginHealth, Origins};
 
 pub const MAX_BLOCK_SIZE: usize = 1048576;
 pub const MAX_CHUNK_SIZE: usize = 104857600;
 pub const DEFAULT_CHUNK_SIZE: usize = 10 * 1024 * 1024; // chunk to ~10MB CARs
+/// Roots of a prefetch job fetched at once.
+const PREFETCH_CONCURRENCY: usize = 4;
+/// Prefetch jobs kept for status queries, the oldest finished ones are dropped first.
+const MAX_PREFETCH_JOBS: usize = 1000;
 
 /// Network Api
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct NetworkGetParams {
+    /// Cid of the block
     pub cid: String,
 }
 
 pub type NetworkGetResult = Vec<u8>;
 pub const NETWORK_GET: &str = "ursa_get_cid";
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct NetworkPutFileParams {
+    /// Path of a car file on the node's filesystem
     pub path: String,
 }
 
@@ -61,21 +74,238 @@ pub const NETWORK_PUT_FILE: &str = "ursa_put_file";
 pub type NetworkGetPeers = HashSet<PeerId>;
 pub const NETWORK_GET_PEERS: &str = "ursa_get_peers";
 
+pub type NetworkGetRelayState = RelayState;
+pub const NETWORK_GET_RELAY_STATE: &str = "ursa_get_relay_state";
+
+pub type NetworkStoreStats = StoreStats;
+pub const NETWORK_STORE_STATS: &str = "ursa_store_stats";
+
+pub type NetworkOriginHealth = Vec<OriginHealth>;
+pub const NETWORK_ORIGIN_HEALTH: &str = "ursa_origin_health";
+
 pub type NetworkGetListenerAddresses = Vec<Multiaddr>;
 pub const NETWORK_LISTENER_ADDRESSES: &str = "ursa_listener_addresses";
 
-#[derive(Deserialize, Serialize)]
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct NetworkGetPeerInfoParams {
+    /// Peer id of a connected peer
+    pub peer_id: String,
+}
+
+pub type NetworkGetPeerInfoResult = Option<PeerInfo>;
+pub const NETWORK_GET_PEER_INFO: &str = "ursa_get_peer_info";
+
+#[derive(Deserialize, Serialize, JsonSchema)]
 pub struct NetworkGetFileParams {
+    /// Directory on the node's filesystem to write `<cid>.car` to
     pub path: String,
+    /// Root cid of the dag
     pub cid: String,
+    /// Only write part of the dag, the whole dag by default
+    #[serde(flatten)]
+    pub selection: DagSelection,
 }
 pub const NETWORK_GET_FILE: &str = "ursa_get_file";
 
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct NetworkPrefetchParams {
+    /// Root cids to pull into the node, from the network or else the origins
+    pub cids: Vec<String>,
+    /// Also push the roots the node already holds to its replication set, fetched roots are
+    /// always pushed
+    #[serde(default)]
+    pub replicate: bool,
+}
+
+pub type NetworkPrefetchResult = PrefetchJob;
+pub const NETWORK_PREFETCH: &str = "ursa_prefetch";
+
+#[derive(Deserialize, Serialize, JsonSchema)]
+pub struct NetworkPrefetchStatusParams {
+    /// Id of the prefetch job
+    pub id: String,
+}
+
+pub type NetworkPrefetchStatusResult = Option<PrefetchJob>;
+pub const NETWORK_PREFETCH_STATUS: &str = "ursa_prefetch_status";
+
+/// Maximum number of roots of a prefetch job.
+pub const MAX_PREFETCH_ROOTS: usize = 1000;
+
+/// Why a prefetch job wasn't started.
+#[derive(Clone, Debug, Error)]
+pub enum PrefetchError {
+    #[error("A prefetch job takes between 1 and {MAX_PREFETCH_ROOTS} roots")]
+    InvalidRoots,
+    #[error("Too many prefetch jobs are running, retry once some of them finish")]
+    TooManyJobs,
+}
+
+/// State of a prefetch job or of one of its roots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PrefetchStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl PrefetchStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct PrefetchRoot {
+    pub cid: String,
+    pub status: PrefetchStatus,
+    /// Why the root couldn't be fetched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Progress of the network fetch of a running root
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub progress: Option<BitswapProgress>,
+}
+
+/// Roots pulled into the node in the background by `ursa_prefetch`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct PrefetchJob {
+    /// Random id, only known to the caller that started the job
+    pub id: String,
+    /// Completed once every root is, failed once every root finished and any of them failed
+    pub status: PrefetchStatus,
+    pub replicate: bool,
+    pub roots: Vec<PrefetchRoot>,
+}
+
+impl PrefetchJob {
+    pub fn new(id: String, roots: &[Cid], replicate: bool) -> Self {
+        Self {
+            id,
+            status: PrefetchStatus::Pending,
+            replicate,
+            roots: roots
+                .iter()
+                .map(|cid| PrefetchRoot {
+                    cid: cid.to_string(),
+                    status: PrefetchStatus::Pending,
+                    error: None,
+                    progress: None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Set the status of the root at `index`, and the status of the job that follows.
+    pub fn set_root(&mut self, index: usize, status: PrefetchStatus, error: Option<String>) {
+        if let Some(root) = self.roots.get_mut(index) {
+            root.status = status;
+            root.error = error;
+        }
+        let statuses = || self.roots.iter().map(|root| root.status);
+        self.status = if !statuses().all(|status| status.is_finished()) {
+            if statuses().all(|status| status == PrefetchStatus::Pending) {
+                PrefetchStatus::Pending
+            } else {
+                PrefetchStatus::Running
+            }
+        } else if statuses().any(|status| status == PrefetchStatus::Failed) {
+            PrefetchStatus::Failed
+        } else {
+            PrefetchStatus::Completed
+        };
+    }
+}
+
+/// Websocket only subscriptions, answered with the id of the new subscription.
+pub const SUBSCRIBE_NETWORK_EVENTS: &str = "ursa_subscribeNetworkEvents";
+pub const SUBSCRIBE_NEW_BLOCKS: &str = "ursa_subscribeNewBlocks";
+/// Cancel a subscription, params are `[subscription_id]`.
+pub const UNSUBSCRIBE: &str = "ursa_unsubscribe";
+/// Method of the notifications sent to subscribers.
+pub const SUBSCRIPTION: &str = "ursa_subscription";
+
+/// A network event sent to `ursa_subscribeNetworkEvents` subscribers.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum NetworkEventNotification {
+    PeerConnected {
+        peer_id: String,
+    },
+    PeerDisconnected {
+        peer_id: String,
+    },
+    PullComplete {
+        cid: String,
+        size: u64,
+    },
+    GossipsubMessage {
+        peer_id: String,
+        topic: String,
+        data: Vec<u8>,
+    },
+}
+
+impl NetworkEventNotification {
+    /// Events internal to the node, like bitswap queries, are not sent to subscribers.
+    pub fn from_event(event: &NetworkEvent) -> Option<Self> {
+        match event {
+            NetworkEvent::PeerConnected(peer_id) => Some(Self::PeerConnected {
+                peer_id: peer_id.to_string(),
+            }),
+            NetworkEvent::PeerDisconnected(peer_id) => Some(Self::PeerDisconnected {
+                peer_id: peer_id.to_string(),
+            }),
+            NetworkEvent::PullComplete { cid, size } => Some(Self::PullComplete {
+                cid: cid.to_string(),
+                size: *size,
+            }),
+            NetworkEvent::Gossipsub(GossipsubEvent::Message {
+                peer_id, message, ..
+            }) => Some(Self::GossipsubMessage {
+                peer_id: peer_id.to_string(),
+                topic: message.topic.to_string(),
+                data: message.data.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// A block committed by consensus, sent to `ursa_subscribeNewBlocks` subscribers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct NewBlockNotification {
+    pub height: i64,
+}
+
+/// Ethereum api, params are positional as in the Ethereum JSON-RPC spec. Block params are
+/// accepted but every query runs against the latest committed state.
 pub type EthSendTransactionParams = TransactionRequest;
+/// Hash of the submitted transaction.
+pub type EthSendTransactionResult = H256;
 pub const ETH_SEND_TRANSACTION: &str = "eth_sendTransaction";
+/// Params are `[signed_rlp_transaction]`.
+pub const ETH_SEND_RAW_TRANSACTION: &str = "eth_sendRawTransaction";
 
-pub type EthCall = Vec<u8>;
+pub type EthCall = Bytes;
 pub const ETH_CALL: &str = "eth_call";
+pub const ETH_ESTIMATE_GAS: &str = "eth_estimateGas";
+
+pub type EthGetTransactionReceipt = Option<TransactionReceipt>;
+pub const ETH_GET_TRANSACTION_RECEIPT: &str = "eth_getTransactionReceipt";
+
+pub const ETH_GET_BALANCE: &str = "eth_getBalance";
+pub const ETH_GET_CODE: &str = "eth_getCode";
+pub const ETH_GET_TRANSACTION_COUNT: &str = "eth_getTransactionCount";
+pub const ETH_GET_LOGS: &str = "eth_getLogs";
+pub const ETH_BLOCK_NUMBER: &str = "eth_blockNumber";
+pub const ETH_CHAIN_ID: &str = "eth_chainId";
+pub const ETH_GAS_PRICE: &str = "eth_gasPrice";
+
+/// The OpenRPC document of the api, see [`crate::schema`].
+pub const RPC_DISCOVER: &str = "rpc.discover";
 
 /// Abstraction of Ursa's server commands
 #[async_trait]
@@ -86,13 +316,24 @@ pub trait NetworkInterface: Sync + Send + 'static {
     /// Get content under a cid
     async fn get_data(&self, root_cid: Cid) -> Result<Vec<(Cid, Vec<u8>)>>;
 
+    /// Get the part of the content under a cid picked by a selection
+    async fn get_selected(
+        &self,
+        root_cid: Cid,
+        selection: DagSelection,
+    ) -> Result<Vec<(Cid, Vec<u8>)>>;
+
+    /// Resolve a path like `assets/logo.png` under a root cid to the cid it points to
+    async fn resolve_path(&self, root_cid: Cid, path: String) -> Result<Cid>;
+
     /// get the file locally via cli
-    async fn get_file(&self, path: String, cid: Cid) -> Result<()>;
+    async fn get_file(&self, path: String, cid: Cid, selection: DagSelection) -> Result<()>;
 
     /// Stream the car file from server
     async fn stream(
         &self,
         root_cid: Cid,
+        selection: DagSelection,
     ) -> Result<StreamBody<ReaderStream<tokio::io::DuplexStream>>>;
 
     /// Put a car file and start providing to the network
@@ -101,20 +342,58 @@ pub trait NetworkInterface: Sync + Send + 'static {
     /// Put a file using a local path
     async fn put_file(&self, path: String) -> Result<Vec<Cid>>;
 
+    /// Get the progress of an in-flight network fetch
+    async fn get_progress(&self, cid: Cid) -> Result<Option<BitswapProgress>>;
+
+    /// Receive the progress of an in-flight network fetch until it finishes
+    async fn subscribe_progress(
+        &self,
+        cid: Cid,
+    ) -> Result<Option<watch::Receiver<BitswapProgress>>>;
+
+    /// Start pulling the dags of `roots` into the node in the background, failing with a
+    /// [`PrefetchError`] if the job can't be started
+    async fn prefetch(&self, roots: Vec<Cid>, replicate: bool) -> Result<PrefetchJob>;
+
+    /// Get a prefetch job with the progress of its running roots
+    async fn get_prefetch(&self, id: String) -> Result<Option<PrefetchJob>>;
+
     /// Get peers from the network
     async fn get_peers(&self) -> Result<HashSet<PeerId>>;
 
+    /// Get the identify information of a connected peer
+    async fn get_peer_info(&self, peer_id: PeerId) -> Result<Option<PeerInfo>>;
+
+    /// Get the relay reservations held by the p2p node
+    async fn get_relay_state(&self) -> Result<RelayState>;
+
     /// Get the addresses that p2p node is listening on
     async fn get_listener_addresses(&self) -> Result<Vec<Multiaddr>>;
 
+    /// Get the block count, size and root sizes of the local store
+    async fn store_stats(&self) -> Result<StoreStats>;
+
+    /// Get the health of the origins, in the order they are tried
+    async fn origin_health(&self) -> Result<Vec<OriginHealth>>;
+
     /// Stream txn to the Narwhal worker mempool
     async fn submit_narwhal_txn(&self, txn: TransactionProto) -> Result<()>;
 
     /// Query the application layer through abci
     async fn query_abci(&self, txn: AbciQueryQuery) -> Result<ResponseQuery>;
+
+    /// Receive the events of the p2p node emitted from now on
+    fn subscribe_network_events(&self) -> broadcast::Receiver<NetworkEvent>;
+
+    /// Receive the height of every block committed from now on
+    fn subscribe_new_blocks(&self) -> broadcast::Receiver<i64>;
 }
 
 type PendingRequests = Arc<RwLock<HashMap<Cid, Vec<Sender<Result<u64>>>>>>;
+/// Progress of the network fetches and the number of requests waiting on each.
+type FetchProgress = Arc<RwLock<HashMap<Cid, (usize, watch::Sender<BitswapProgress>)>>>;
+/// Prefetch jobs by the order they were started in.
+type PrefetchJobs = Arc<RwLock<BTreeMap<u64, PrefetchJob>>>;
 
 #[derive(Clone)]
 pub struct NodeNetworkInterface<S>
@@ -122,13 +401,18 @@ where
     S: Blockstore + Store + Send + Sync + 'static,
 {
     pub store: Arc<UrsaStore<S>>,
+    async_store: AsyncStore<S>,
     pub network_send: Sender<NetworkCommand>,
     pub provider_send: Sender<ProviderCommand>,
     mempool_address: String,
     pending_requests: PendingRequests,
-    client: Arc<Client>,
-    origin_config: OriginConfig,
+    fetch_progress: FetchProgress,
+    origins: Arc<Origins>,
+    prefetch_jobs: PrefetchJobs,
+    next_prefetch_key: Arc<AtomicU64>,
     abci_send: BoundedSender<(oneshot::Sender<ResponseQuery>, AbciQueryQuery)>,
+    network_events: broadcast::Sender<NetworkEvent>,
+    new_blocks: broadcast::Sender<i64>,
 }
 
 #[async_trait]
@@ -139,7 +423,7 @@ where
     async fn get(&self, cid: Cid) -> Result<Vec<u8>> {
         self.sync_content(cid).await?;
         let content =
-            self.store.blockstore().get(&cid)?.ok_or_else(|| {
+            self.async_store.get(cid).await?.ok_or_else(|| {
                 anyhow!("content was fetched but could not be found in blockstore")
             })?;
         Ok(content)
@@ -147,13 +431,31 @@ where
 
     async fn get_data(&self, root_cid: Cid) -> Result<Vec<(Cid, Vec<u8>)>> {
         self.sync_content(root_cid).await?;
-        let dag = self.store.dag_traversal(&root_cid)?;
+        let dag = self.async_store.dag_traversal(root_cid).await?;
         info!("Dag traversal done, now streaming the file");
         Ok(dag)
     }
 
+    async fn get_selected(
+        &self,
+        root_cid: Cid,
+        selection: DagSelection,
+    ) -> Result<Vec<(Cid, Vec<u8>)>> {
+        self.sync_content(root_cid).await?;
+        self.async_store
+            .run(move |store| store.select(&root_cid, &selection))
+            .await
+    }
+
+    async fn resolve_path(&self, root_cid: Cid, path: String) -> Result<Cid> {
+        self.sync_content(root_cid).await?;
+        self.async_store
+            .run(move |store| store.resolve_path(&root_cid, &path))
+            .await
+    }
+
     /// Used through CLI
-    async fn get_file(&self, path: String, root_cid: Cid) -> Result<()> {
+    async fn get_file(&self, path: String, root_cid: Cid, selection: DagSelection) -> Result<()> {
         info!("getting and storing the file at: {path}");
 
         let header = CarHeader {
@@ -171,7 +473,7 @@ where
                 .await
                 .unwrap()
         });
-        let dag = self.get_data(root_cid).await?;
+        let dag = self.get_selected(root_cid, selection).await?;
 
         for (cid, data) in dag {
             tx.send((cid, data)).await?;
@@ -191,6 +493,7 @@ where
     async fn stream(
         &self,
         root_cid: Cid,
+        selection: DagSelection,
     ) -> Result<StreamBody<ReaderStream<tokio::io::DuplexStream>>> {
         let header = CarHeader {
             roots: vec![root_cid],
@@ -210,7 +513,7 @@ where
                 error!("Error while streaming the car file {err:?}");
             }
         });
-        let dag = self.get_data(root_cid).await?;
+        let dag = self.get_selected(root_cid, selection).await?;
 
         for (cid, data) in dag {
             tx.send((cid, data)).await?;
@@ -222,7 +525,7 @@ where
 
     async fn put_car<R: AsyncRead + Send + Unpin>(&self, car: Car<R>) -> Result<Vec<Cid>> {
         let size = car.size;
-        let cids = load_car(self.store.blockstore(), car).await?;
+        let cids = self.async_store.load_car(car).await?;
         let root_cid = cids[0];
         info!("The inserted cids are: {cids:?}");
         self.provide_cid(root_cid, size).await.map(|_| cids)
@@ -234,6 +537,75 @@ where
         self.put_car(Car::from_file(path).await?).await
     }
 
+    async fn get_progress(&self, cid: Cid) -> Result<Option<BitswapProgress>> {
+        let fetch_progress = self.fetch_progress.read().await;
+        Ok(fetch_progress.get(&cid).map(|(_, sender)| *sender.borrow()))
+    }
+
+    async fn subscribe_progress(
+        &self,
+        cid: Cid,
+    ) -> Result<Option<watch::Receiver<BitswapProgress>>> {
+        let fetch_progress = self.fetch_progress.read().await;
+        Ok(fetch_progress
+            .get(&cid)
+            .map(|(_, sender)| sender.subscribe()))
+    }
+
+    async fn prefetch(&self, roots: Vec<Cid>, replicate: bool) -> Result<PrefetchJob> {
+        if roots.is_empty() || roots.len() > MAX_PREFETCH_ROOTS {
+            return Err(PrefetchError::InvalidRoots.into());
+        }
+        // the id is the only access control of the job status, it can't be guessed
+        let id = hex::encode(rand::random::<[u8; 16]>());
+        let key = self.next_prefetch_key.fetch_add(1, Ordering::Relaxed);
+        let job = PrefetchJob::new(id.clone(), &roots, replicate);
+        {
+            let mut jobs = self.prefetch_jobs.write().await;
+            while jobs.len() >= MAX_PREFETCH_JOBS {
+                let finished = jobs
+                    .iter()
+                    .find(|(_, job)| job.status.is_finished())
+                    .map(|(key, _)| *key);
+                match finished {
+                    Some(finished) => jobs.remove(&finished),
+                    None => return Err(PrefetchError::TooManyJobs.into()),
+                };
+            }
+            jobs.insert(key, job.clone());
+        }
+
+        info!("Starting prefetch job {id} of {} roots", roots.len());
+        let interface = self.clone();
+        task::spawn(async move { interface.run_prefetch(key, id, roots, replicate).await });
+        Ok(job)
+    }
+
+    async fn get_prefetch(&self, id: String) -> Result<Option<PrefetchJob>> {
+        let mut job = match self
+            .prefetch_jobs
+            .read()
+            .await
+            .values()
+            .find(|job| job.id == id)
+        {
+            Some(job) => job.clone(),
+            None => return Ok(None),
+        };
+        let fetch_progress = self.fetch_progress.read().await;
+        for root in &mut job.roots {
+            if root.status == PrefetchStatus::Running {
+                root.progress = root
+                    .cid
+                    .parse()
+                    .ok()
+                    .and_then(|cid| fetch_progress.get(&cid))
+                    .map(|(_, sender)| *sender.borrow());
+            }
+        }
+        Ok(Some(job))
+    }
+
     async fn get_peers(&self) -> Result<HashSet<PeerId>> {
         let (sender, receiver) = oneshot::channel();
         let request = NetworkCommand::GetPeers { sender };
@@ -245,6 +617,30 @@ where
         }
     }
 
+    async fn get_peer_info(&self, peer_id: PeerId) -> Result<Option<PeerInfo>> {
+        let (sender, receiver) = oneshot::channel();
+        let request = NetworkCommand::GetPeerInfo { peer_id, sender };
+
+        self.network_send.send(request)?;
+        match receiver.await {
+            Ok(info) => Ok(info),
+            Err(e) => Err(anyhow!(format!("GetPeerInfo NetworkCommand failed {e:?}"))),
+        }
+    }
+
+    async fn get_relay_state(&self) -> Result<RelayState> {
+        let (sender, receiver) = oneshot::channel();
+        let request = NetworkCommand::GetRelayState { sender };
+
+        self.network_send.send(request)?;
+        match receiver.await {
+            Ok(state) => Ok(state),
+            Err(e) => Err(anyhow!(format!(
+                "GetRelayState NetworkCommand failed {e:?}"
+            ))),
+        }
+    }
+
     async fn get_listener_addresses(&self) -> Result<Vec<Multiaddr>> {
         let (sender, receiver) = oneshot::channel();
         let request = NetworkCommand::GetListenerAddresses { sender };
@@ -258,6 +654,14 @@ where
         }
     }
 
+    async fn store_stats(&self) -> Result<StoreStats> {
+        Ok(self.store.stats())
+    }
+
+    async fn origin_health(&self) -> Result<Vec<OriginHealth>> {
+        Ok(self.origins.health())
+    }
+
     async fn submit_narwhal_txn(&self, txn: TransactionProto) -> Result<()> {
         let mut client = TransactionsClient::connect(self.mempool_address.clone())
             .await
@@ -279,65 +683,172 @@ where
 
         rx.await.with_context(|| "Failure querying abci")
     }
+
+    fn subscribe_network_events(&self) -> broadcast::Receiver<NetworkEvent> {
+        self.network_events.subscribe()
+    }
+
+    fn subscribe_new_blocks(&self) -> broadcast::Receiver<i64> {
+        self.new_blocks.subscribe()
+    }
 }
 
 impl<S> NodeNetworkInterface<S>
 where
     S: Blockstore + Store + Send + Sync + 'static,
 {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         store: Arc<UrsaStore<S>>,
         network_send: Sender<NetworkCommand>,
         provider_send: Sender<ProviderCommand>,
-        origin_config: OriginConfig,
+        origins: Origins,
         mempool_address: String,
         abci_send: BoundedSender<(oneshot::Sender<ResponseQuery>, AbciQueryQuery)>,
+        network_events: broadcast::Sender<NetworkEvent>,
+        new_blocks: broadcast::Sender<i64>,
     ) -> Self {
         Self {
+            network_events,
+            new_blocks,
+            async_store: AsyncStore::new(Arc::clone(&store)),
             store,
             network_send,
             provider_send,
             mempool_address,
-            origin_config,
+            origins: Arc::new(origins),
             abci_send,
             pending_requests: Arc::new(RwLock::new(HashMap::new())),
-            client: Arc::new(Client::new()),
+            fetch_progress: Arc::new(RwLock::new(HashMap::new())),
+            prefetch_jobs: Arc::new(RwLock::new(BTreeMap::new())),
+            next_prefetch_key: Arc::new(AtomicU64::new(0)),
         }
     }
 
     /// Ensure a root cid is synced to the blockstore
     async fn sync_content(&self, cid: Cid) -> Result<()> {
-        if !self.store.blockstore().has(&cid)? {
-            info!("Requesting block with the cid {cid:?}");
-
-            let size = match self.get_network(cid).await {
-                Ok(_) => self.store.car_size(&cid)?,
-                Err(e) => {
-                    info!("Failed to get content from network: {}", e);
-                    self.get_origin(cid).await?
-                }
-            };
-            self.provide_cid(cid, size).await
+        if !self.async_store.has(cid).await? {
+            self.fetch_content(cid).await
         } else {
             Ok(())
         }
     }
 
+    /// Fetch the dag of a root cid from the network, or else the origins, and provide it.
+    async fn fetch_content(&self, cid: Cid) -> Result<()> {
+        info!("Requesting block with the cid {cid:?}");
+
+        let size = match self.get_network(cid).await {
+            Ok(_) => self.async_store.car_size(cid).await?,
+            Err(e) => {
+                info!("Failed to get content from network: {}", e);
+                self.get_origin(cid).await?
+            }
+        };
+        self.provide_cid(cid, size).await
+    }
+
+    /// Whether every block of the dag of `cid` is in the store.
+    async fn has_dag(&self, cid: Cid) -> Result<bool> {
+        let missing = self
+            .async_store
+            .run(move |store| store.missing_blocks(&cid))
+            .await?;
+        Ok(missing.is_empty())
+    }
+
+    /// Sync the roots of a prefetch job, a few at a time, recording their status.
+    async fn run_prefetch(&self, key: u64, id: String, roots: Vec<Cid>, replicate: bool) {
+        let id = &id;
+        stream::iter(roots.into_iter().enumerate())
+            .for_each_concurrent(PREFETCH_CONCURRENCY, |(index, cid)| async move {
+                self.set_prefetch_root(key, index, PrefetchStatus::Running, None)
+                    .await;
+                let (status, error) = match self.prefetch_root(cid, replicate).await {
+                    Ok(()) => (PrefetchStatus::Completed, None),
+                    Err(e) => {
+                        warn!("Prefetch job {id} failed to fetch {cid}: {e}");
+                        (PrefetchStatus::Failed, Some(e.to_string()))
+                    }
+                };
+                self.set_prefetch_root(key, index, status, error).await;
+            })
+            .await;
+        info!("Prefetch job {id} finished");
+    }
+
+    /// Pull the whole dag of `cid` into the store, a root without all its blocks is fetched
+    /// again.
+    async fn prefetch_root(&self, cid: Cid, replicate: bool) -> Result<()> {
+        if self.has_dag(cid).await? {
+            if replicate {
+                self.replicate(cid).await?;
+            }
+            return Ok(());
+        }
+        // fetched content is replicated by `fetch_content`
+        self.fetch_content(cid).await?;
+        if !self.has_dag(cid).await? {
+            return Err(anyhow!("The dag of {cid} is still missing blocks"));
+        }
+        Ok(())
+    }
+
+    async fn set_prefetch_root(
+        &self,
+        key: u64,
+        index: usize,
+        status: PrefetchStatus,
+        error: Option<String>,
+    ) {
+        if let Some(job) = self.prefetch_jobs.write().await.get_mut(&key) {
+            job.set_root(index, status, error);
+        }
+    }
+
     /// Fetch content from the network
     async fn get_network(&self, root_cid: Cid) -> Result<()> {
         info!("Fetching cid {root_cid} from network");
         let (send, recv) = oneshot::channel();
+        let (progress_send, mut progress_recv) = unbounded_channel();
         self.network_send.send(NetworkCommand::GetBitswap {
             cid: root_cid,
             sender: send,
+            progress: Some(progress_send),
         })?;
+
+        // concurrent fetches of a root share its bitswap query and its progress
+        match self.fetch_progress.write().await.entry(root_cid) {
+            Entry::Occupied(mut e) => e.get_mut().0 += 1,
+            Entry::Vacant(e) => {
+                e.insert((1, watch::channel(BitswapProgress::default()).0));
+            }
+        }
+
+        // track progress until the network service drops the sender
+        let fetch_progress = self.fetch_progress.clone();
+        task::spawn(async move {
+            while let Some(progress) = progress_recv.recv().await {
+                if let Some((_, sender)) = fetch_progress.read().await.get(&root_cid) {
+                    sender.send_replace(progress);
+                }
+            }
+            // the last request done with the root drops its progress, ending the streams
+            if let Entry::Occupied(mut e) = fetch_progress.write().await.entry(root_cid) {
+                e.get_mut().0 -= 1;
+                if e.get().0 == 0 {
+                    e.remove();
+                }
+            }
+        });
+
+        // dropping `recv` cancels the bitswap query
         recv.await?
     }
 
-    /// Fetch content from the origin.
-    /// Returns the size of the car file received
+    /// Fetch content from the origins.
+    /// Returns the size of the car file of the fetched dag
     async fn get_origin(&self, root_cid: Cid) -> Result<u64> {
-        info!("Fetching cid {root_cid} from origin (ipfs)");
         let pending = self.pending_requests.clone();
         let (tx, mut rx) = unbounded_channel();
         match self.pending_requests.write().await.entry(root_cid) {
@@ -355,63 +866,28 @@ where
         }
 
         // we are the first concurrent request for this cid
-        let client = self.client.clone();
-
-        let https = self
-            .origin_config
-            .use_https
-            .map(|v| if v { "https://" } else { "http://" })
-            .unwrap_or("https://");
-
-        let req = RequestBuilder::new(
-            Method::Get,
-            format!("{https}{}/ipfs/{root_cid}", self.origin_config.ipfs_gateway).parse()?,
-        )
-        .header("Accept", "application/vnd.ipld.car")
-        .build();
-
-        let store = self.store.db.clone();
+        let origins = self.origins.clone();
+        let store = self.async_store.clone();
+        let network_send = self.network_send.clone();
         task::spawn(async move {
-            // send the request
-            let result: Result<u64, String> = async {
-                let mut res = client.send(req).await.map_err(|e| {
-                    format!("Error getting content for cid {root_cid} from origin: {e}")
-                })?;
-
-                let body = res.body_bytes().await.map_err(|e| {
-                    format!("Error receiving content for cid {root_cid} from origin: {e}")
-                })?;
-                let len = body.len() as u64;
-
-                let mut car = CarReader::new(body.as_slice()).await.map_err(|e| {
-                    format!("Error reading car file for cid {root_cid} from origin: {e}")
-                })?;
-
-                if car.header.roots.contains(&root_cid) {
-                    let mut buf = Vec::with_capacity(100);
-                    while let Some(block) = car.next_block().await.unwrap() {
-                        buf.push((block.cid, block.data));
-                        if buf.len() > 1000 {
-                            store
-                                .put_many_keyed(buf.iter().map(|(k, v)| (*k, v)))
-                                .map_err(|e| {
-                                    format!("Error storing block with cid {0}: {e}", block.cid)
-                                })?;
-                            buf.clear();
-                        }
-                    }
-                    store
-                        .put_many_keyed(buf.iter().map(|(k, v)| (*k, v)))
-                        .map_err(|e| {
-                            format!("Error storing bulk block keys for cid {root_cid}: {e}")
-                        })?;
-
-                    Ok(len)
-                } else {
-                    Err(format!(
-                        "Error: cid {root_cid} not found in the origin response car file"
-                    ))
-                }
+            let result: Result<u64> = async {
+                let (store, network_send) = (&store, &network_send);
+                // the size of the fetched blocks gives the car size without walking the dag
+                let mut dag = Vec::new();
+                let mut seen = HashSet::new();
+                let dag_ref = &mut dag;
+                origins
+                    .fetch(root_cid, move |blocks| {
+                        dag_ref.extend(
+                            blocks
+                                .iter()
+                                .filter(|(cid, _)| seen.insert(*cid))
+                                .map(|(cid, data)| (*cid, data.len())),
+                        );
+                        Self::put_origin_blocks(store, network_send, blocks)
+                    })
+                    .await?;
+                store.insert_car_size(root_cid, dag).await
             }
             .await;
 
@@ -428,10 +904,15 @@ where
                     }
                 }
                 Err(e) => {
-                    error!(e);
+                    error!("{e}");
                     if let Some(senders) = pending.remove(&root_cid) {
                         for sender in senders {
-                            if sender.send(Err(anyhow!(e.clone()))).is_err() {
+                            // origin errors are passed on as is, so callers can tell them apart
+                            let e = match e.downcast_ref::<OriginError>() {
+                                Some(e) => e.clone().into(),
+                                None => anyhow!(e.to_string()),
+                            };
+                            if sender.send(Err(e)).is_err() {
                                 debug!("Failed to send origin status to channel");
                             }
                         }
@@ -445,19 +926,31 @@ where
             .ok_or_else(|| anyhow!("Failed to receive status from channel"))?
     }
 
+    /// Verify and store blocks received from the origin.
+    /// Invalid blocks are reported to the network layer and fail the whole batch.
+    async fn put_origin_blocks(
+        store: &AsyncStore<S>,
+        network_send: &Sender<NetworkCommand>,
+        blocks: Vec<(Cid, Vec<u8>)>,
+    ) -> Result<()> {
+        store.put_blocks(blocks).await.map_err(|e| {
+            if let Some(err) = e.downcast_ref::<BlockError>() {
+                let request = NetworkCommand::ReportInvalidBlock {
+                    cid: err.cid(),
+                    peer_id: None,
+                };
+                if network_send.send(request).is_err() {
+                    error!("Failed to report invalid block {} from origin", err.cid());
+                }
+            }
+            e
+        })
+    }
+
     /// Trigger the network and provider to start providing the content id.
     /// If the size is not provided, it will be calculated from the blockstore
     async fn provide_cid(&self, cid: Cid, size: u64) -> Result<()> {
-        // network content replication
-        let (sender, receiver) = oneshot::channel();
-        if let Err(e) = self.network_send.send(NetworkCommand::Put { cid, sender }) {
-            error!("Failed to send network command: {}", e);
-        } else {
-            match receiver.await {
-                Ok(res) => res?,
-                Err(e) => error!("Error receiving network put status for {cid}: {e}"),
-            }
-        }
+        self.replicate(cid).await?;
 
         // provider announcement
         let (sender, receiver) = oneshot::channel();
@@ -476,6 +969,41 @@ where
 
         Ok(())
     }
+
+    /// Ask the replication set of the node to cache the content id.
+    async fn replicate(&self, cid: Cid) -> Result<()> {
+        let (sender, receiver) = oneshot::channel();
+        if let Err(e) = self.network_send.send(NetworkCommand::Put { cid, sender }) {
+            error!("Failed to send network command: {}", e);
+        } else {
+            match receiver.await {
+                Ok(res) => res?,
+                Err(e) => error!("Error receiving network put status for {cid}: {e}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Announce the roots that were imported while the node was offline.
+    pub async fn announce_pending(&self) -> Result<()> {
+        for root in self.store.pending_announcements()? {
+            let size = match self.async_store.car_size(root).await {
+                Ok(size) => size,
+                Err(e) => {
+                    error!("Skipping announcement of incomplete dag {root}: {e:?}");
+                    continue;
+                }
+            };
+            match self.provide_cid(root, size).await {
+                Ok(()) => {
+                    info!("Announced imported root {root}");
+                    self.store.remove_pending_announcement(&root)?;
+                }
+                Err(e) => error!("Failed to announce imported root {root}: {e:?}"),
+            }
+        }
+        Ok(())
+    }
 }
 
 pub struct Car<R> {
@@ -0,0 +1,299 @@
+//! # RPC authentication and authorization.
+//!
+//! Clients authenticate with an API key signed by the node identity, sent as
+//! `Authorization: Bearer <token>`. A token is `<claims>.<signature>`, both base64url
+//! encoded, where the claims hold the granted [`Scope`]s and an optional expiry. The signature
+//! covers the encoded claims prefixed with [`TOKEN_CONTEXT`], so it can't be replayed as a
+//! signature of the node identity over anything else.
+//!
+//! A key is revoked by listing its [`key_id`] in the `revoked_keys` of the [`AuthConfig`].
+//!
+//! Requests on the public listener without a token get the anonymous scopes of the
+//! [`AuthConfig`]. The admin listener is meant to be bound to localhost and grants every
+//! scope without a token.
+
+use std::{
+    fmt,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use axum::{
+    extract::State,
+    http::{header::AUTHORIZATION, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use libp2p::identity::{Keypair, PublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::api::{
+    ETH_SEND_RAW_TRANSACTION, ETH_SEND_TRANSACTION, NETWORK_GET_FILE, NETWORK_PREFETCH,
+    NETWORK_PREFETCH_STATUS, NETWORK_PUT_FILE,
+};
+
+/// Permission scopes, each one includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Fetch content and read the node state.
+    Read,
+    /// Upload content and submit transactions.
+    Write,
+    /// Read and write files on the node's filesystem.
+    Admin,
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scope::Read => write!(f, "read"),
+            Scope::Write => write!(f, "write"),
+            Scope::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl std::str::FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(anyhow::anyhow!(
+                "Invalid scope {s:?}, expected read, write or admin"
+            )),
+        }
+    }
+}
+
+/// The scope required to call a JSON-RPC method.
+pub fn method_scope(method: &str) -> Scope {
+    match method {
+        // unsigned transactions run as the nodes themselves
+        NETWORK_GET_FILE | NETWORK_PUT_FILE | ETH_SEND_TRANSACTION => Scope::Admin,
+        ETH_SEND_RAW_TRANSACTION | NETWORK_PREFETCH | NETWORK_PREFETCH_STATUS => Scope::Write,
+        _ => Scope::Read,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuthConfig {
+    /// Require tokens on the public listener, everything is allowed when disabled
+    #[serde(default = "AuthConfig::default_enabled")]
+    pub enabled: bool,
+    /// Scopes of requests without a token
+    #[serde(default = "AuthConfig::default_anonymous_scopes")]
+    pub anonymous_scopes: Vec<Scope>,
+    /// Ids of revoked keys, as printed by `ursa token create`
+    #[serde(default)]
+    pub revoked_keys: Vec<String>,
+}
+
+impl AuthConfig {
+    fn default_enabled() -> bool {
+        true
+    }
+    fn default_anonymous_scopes() -> Vec<Scope> {
+        vec![Scope::Read]
+    }
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: Self::default_enabled(),
+            anonymous_scopes: Self::default_anonymous_scopes(),
+            revoked_keys: Vec::new(),
+        }
+    }
+}
+
+/// Claims of an API key.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Claims {
+    pub scopes: Vec<Scope>,
+    /// Expiry as seconds since the unix epoch, never expires if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    /// Free form label of the key holder.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    #[error("malformed token")]
+    Malformed,
+    #[error("invalid token signature")]
+    InvalidSignature,
+    #[error("token expired")]
+    Expired,
+    #[error("token revoked")]
+    Revoked,
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        (StatusCode::UNAUTHORIZED, self.to_string()).into_response()
+    }
+}
+
+/// Domain separation prefix of the bytes signed for a token.
+pub const TOKEN_CONTEXT: &[u8] = b"ursa-api-key:";
+
+fn signed_bytes(claims: &str) -> Vec<u8> {
+    [TOKEN_CONTEXT, claims.as_bytes()].concat()
+}
+
+/// Sign `claims` with the node identity.
+pub fn issue_token(keypair: &Keypair, claims: &Claims) -> Result<String> {
+    let claims = base64::encode_config(serde_json::to_vec(claims)?, base64::URL_SAFE_NO_PAD);
+    let signature = keypair.sign(&signed_bytes(&claims))?;
+    Ok(format!(
+        "{claims}.{}",
+        base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+    ))
+}
+
+/// Id of a key, used to revoke it: the first 8 bytes of the sha2-256 hash of the token, hex
+/// encoded.
+pub fn key_id(token: &str) -> String {
+    hex::encode(&Sha256::digest(token.as_bytes())[..8])
+}
+
+/// The scopes granted to a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Access {
+    scopes: Vec<Scope>,
+    /// Whether the request carried a valid token.
+    pub authenticated: bool,
+}
+
+impl Access {
+    pub fn admin() -> Self {
+        Self {
+            scopes: vec![Scope::Admin],
+            authenticated: true,
+        }
+    }
+
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.iter().any(|granted| *granted >= scope)
+    }
+
+    /// Rejection of a request missing `scope`, 401 without a token and 403 otherwise.
+    pub fn forbidden(&self, scope: Scope) -> Response {
+        let status = if self.authenticated {
+            StatusCode::FORBIDDEN
+        } else {
+            StatusCode::UNAUTHORIZED
+        };
+        (status, format!("{scope} scope required")).into_response()
+    }
+}
+
+pub struct Auth {
+    public_key: PublicKey,
+    config: AuthConfig,
+}
+
+impl Auth {
+    pub fn new(public_key: PublicKey, config: AuthConfig) -> Self {
+        Self { public_key, config }
+    }
+
+    /// Check the signature, expiry and revocation of a token.
+    pub fn verify(&self, token: &str) -> Result<Claims, AuthError> {
+        let (claims, signature) = token.split_once('.').ok_or(AuthError::Malformed)?;
+        if self.config.revoked_keys.contains(&key_id(token)) {
+            return Err(AuthError::Revoked);
+        }
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| AuthError::Malformed)?;
+        if !self.public_key.verify(&signed_bytes(claims), &signature) {
+            return Err(AuthError::InvalidSignature);
+        }
+
+        let claims = base64::decode_config(claims, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| AuthError::Malformed)?;
+        let claims: Claims = serde_json::from_slice(&claims).map_err(|_| AuthError::Malformed)?;
+        if let Some(exp) = claims.exp {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            if exp <= now {
+                return Err(AuthError::Expired);
+            }
+        }
+        Ok(claims)
+    }
+
+    /// The access of a request to the public listener with an optional bearer token.
+    pub fn access(&self, token: Option<&str>) -> Result<Access, AuthError> {
+        if !self.config.enabled {
+            return Ok(Access::admin());
+        }
+        match token {
+            Some(token) => Ok(Access {
+                scopes: self.verify(token)?.scopes,
+                authenticated: true,
+            }),
+            None => Ok(Access {
+                scopes: self.config.anonymous_scopes.clone(),
+                authenticated: false,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Listener {
+    Public,
+    Admin,
+}
+
+/// Middleware adding the [`Access`] of the request to its extensions.
+pub async fn authenticate<B>(
+    State((auth, listener)): State<(Arc<Auth>, Listener)>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let access = match listener {
+        Listener::Admin => Access::admin(),
+        Listener::Public => {
+            let token = req
+                .headers()
+                .get(AUTHORIZATION)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.strip_prefix("Bearer "));
+            match auth.access(token) {
+                Ok(access) => access,
+                Err(e) => return e.into_response(),
+            }
+        }
+    };
+    req.extensions_mut().insert(access);
+    next.run(req).await
+}
+
+/// Middleware rejecting requests without `scope`.
+pub async fn require_scope<B>(
+    State(scope): State<Scope>,
+    Extension(access): Extension<Access>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if access.allows(scope) {
+        next.run(req).await
+    } else {
+        access.forbidden(scope)
+    }
+}
@@ -1,34 +1,292 @@
-use jsonrpc_v2::Error;
+use std::{
+    pin::Pin,
+    str::FromStr,
+    task::{Context, Poll},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use crate::api::{
-    EthCall, EthSendTransactionParams, NetworkGetFileParams, NetworkGetParams, NetworkGetResult,
-    NetworkPutFileParams, NetworkPutFileResult, ETH_CALL, ETH_SEND_TRANSACTION, NETWORK_GET,
-    NETWORK_GET_FILE, NETWORK_PUT_FILE,
+use ethers::types::{Address, Bytes, Filter, Log, H256, U256, U64};
+use futures::{
+    io::{BufReader, Cursor},
+    AsyncRead, AsyncReadExt,
 };
+use libipld::Cid;
+use serde::de::Error as _;
+use serde_json::Value;
+use surf::{Body, StatusCode, Url};
+use ursa_network::BitswapProgress;
+use ursa_store::DagSelection;
 
-use super::{
-    call,
-    RpcMethod::{Post, Put},
+use crate::api::{
+    Car, EthCall, EthGetTransactionReceipt, EthSendTransactionParams, EthSendTransactionResult,
+    NetworkGetFileParams, NetworkGetListenerAddresses, NetworkGetParams, NetworkGetPeerInfoParams,
+    NetworkGetPeerInfoResult, NetworkGetPeers, NetworkGetRelayState, NetworkGetResult,
+    NetworkOriginHealth, NetworkPrefetchParams, NetworkPrefetchResult, NetworkPrefetchStatusParams,
+    NetworkPrefetchStatusResult, NetworkPutFileParams, NetworkPutFileResult, NetworkStoreStats,
+    ETH_BLOCK_NUMBER, ETH_CALL, ETH_CHAIN_ID, ETH_ESTIMATE_GAS, ETH_GET_BALANCE, ETH_GET_CODE,
+    ETH_GET_LOGS, ETH_GET_TRANSACTION_COUNT, ETH_GET_TRANSACTION_RECEIPT, ETH_SEND_RAW_TRANSACTION,
+    ETH_SEND_TRANSACTION, NETWORK_GET, NETWORK_GET_FILE, NETWORK_GET_PEERS, NETWORK_GET_PEER_INFO,
+    NETWORK_GET_RELAY_STATE, NETWORK_LISTENER_ADDRESSES, NETWORK_ORIGIN_HEALTH, NETWORK_PREFETCH,
+    NETWORK_PREFETCH_STATUS, NETWORK_PUT_FILE, NETWORK_STORE_STATS,
 };
 
-pub type Result<T> = anyhow::Result<T, Error>;
+use super::{ClientError, UrsaClient};
 
-pub async fn get_block(params: NetworkGetParams) -> Result<NetworkGetResult> {
-    call(NETWORK_GET, params, Post).await
-}
+pub type Result<T> = std::result::Result<T, ClientError>;
 
-pub async fn get_file(params: NetworkGetFileParams) -> Result<()> {
-    call(NETWORK_GET_FILE, params, Put).await
-}
+const CAR_CONTENT_TYPE: &str = "application/vnd.curl.car";
+/// Block param of the Ethereum methods, the node only serves the latest state.
+const LATEST: &str = "latest";
 
-pub async fn put_file(params: NetworkPutFileParams) -> Result<NetworkPutFileResult> {
-    call(NETWORK_PUT_FILE, params, Put).await
+/// A car file streamed from the node while it is read.
+pub struct CarDownload(Body);
+
+impl CarDownload {
+    /// Size of the car file, if the node sent it.
+    pub fn size(&self) -> Option<u64> {
+        self.0.len().map(|len| len as u64)
+    }
+
+    /// Read the whole car file into memory.
+    pub async fn into_bytes(self) -> Result<Vec<u8>> {
+        self.0
+            .into_bytes()
+            .await
+            .map_err(|e| ClientError::Transport(e.to_string()))
+    }
 }
 
-pub async fn eth_send_transaction(params: EthSendTransactionParams) -> Result<()> {
-    call(ETH_SEND_TRANSACTION, params, Post).await
+impl AsyncRead for CarDownload {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
 }
 
-pub async fn eth_call(params: EthSendTransactionParams) -> Result<EthCall> {
-    call(ETH_CALL, params, Put).await
+impl UrsaClient {
+    pub async fn get_block(&self, params: NetworkGetParams) -> Result<NetworkGetResult> {
+        self.read(NETWORK_GET, params).await
+    }
+
+    pub async fn get_file(&self, params: NetworkGetFileParams) -> Result<()> {
+        self.call(NETWORK_GET_FILE, params).await
+    }
+
+    pub async fn put_file(&self, params: NetworkPutFileParams) -> Result<NetworkPutFileResult> {
+        self.call(NETWORK_PUT_FILE, params).await
+    }
+
+    pub async fn get_peers(&self) -> Result<NetworkGetPeers> {
+        self.read(NETWORK_GET_PEERS, ()).await
+    }
+
+    pub async fn get_peer_info(
+        &self,
+        params: NetworkGetPeerInfoParams,
+    ) -> Result<NetworkGetPeerInfoResult> {
+        self.read(NETWORK_GET_PEER_INFO, params).await
+    }
+
+    pub async fn get_relay_state(&self) -> Result<NetworkGetRelayState> {
+        self.read(NETWORK_GET_RELAY_STATE, ()).await
+    }
+
+    pub async fn store_stats(&self) -> Result<NetworkStoreStats> {
+        self.read(NETWORK_STORE_STATS, ()).await
+    }
+
+    pub async fn origin_health(&self) -> Result<NetworkOriginHealth> {
+        self.read(NETWORK_ORIGIN_HEALTH, ()).await
+    }
+
+    pub async fn listener_addresses(&self) -> Result<NetworkGetListenerAddresses> {
+        self.read(NETWORK_LISTENER_ADDRESSES, ()).await
+    }
+
+    /// Start pulling dags into the node in the background.
+    pub async fn prefetch(&self, params: NetworkPrefetchParams) -> Result<NetworkPrefetchResult> {
+        self.call(NETWORK_PREFETCH, params).await
+    }
+
+    /// Status of a prefetch job, `None` if the node doesn't know the job.
+    pub async fn prefetch_status(
+        &self,
+        params: NetworkPrefetchStatusParams,
+    ) -> Result<NetworkPrefetchStatusResult> {
+        self.read(NETWORK_PREFETCH_STATUS, params).await
+    }
+
+    pub async fn eth_send_transaction(
+        &self,
+        params: EthSendTransactionParams,
+    ) -> Result<EthSendTransactionResult> {
+        self.call(ETH_SEND_TRANSACTION, [params]).await
+    }
+
+    /// Submit a signed RLP encoded transaction.
+    pub async fn eth_send_raw_transaction(
+        &self,
+        transaction: Bytes,
+    ) -> Result<EthSendTransactionResult> {
+        self.call(ETH_SEND_RAW_TRANSACTION, [transaction]).await
+    }
+
+    pub async fn eth_call(&self, params: EthSendTransactionParams) -> Result<EthCall> {
+        self.read(ETH_CALL, (params, LATEST)).await
+    }
+
+    pub async fn eth_estimate_gas(&self, params: EthSendTransactionParams) -> Result<U256> {
+        self.read(ETH_ESTIMATE_GAS, (params, LATEST)).await
+    }
+
+    pub async fn eth_get_transaction_receipt(
+        &self,
+        hash: H256,
+    ) -> Result<EthGetTransactionReceipt> {
+        self.read(ETH_GET_TRANSACTION_RECEIPT, [hash]).await
+    }
+
+    pub async fn eth_get_balance(&self, address: Address) -> Result<U256> {
+        self.read(ETH_GET_BALANCE, (address, LATEST)).await
+    }
+
+    pub async fn eth_get_code(&self, address: Address) -> Result<Bytes> {
+        self.read(ETH_GET_CODE, (address, LATEST)).await
+    }
+
+    pub async fn eth_get_transaction_count(&self, address: Address) -> Result<U256> {
+        self.read(ETH_GET_TRANSACTION_COUNT, (address, LATEST))
+            .await
+    }
+
+    pub async fn eth_get_logs(&self, filter: &Filter) -> Result<Vec<Log>> {
+        self.read(ETH_GET_LOGS, [filter]).await
+    }
+
+    pub async fn eth_block_number(&self) -> Result<U64> {
+        self.read(ETH_BLOCK_NUMBER, ()).await
+    }
+
+    pub async fn eth_chain_id(&self) -> Result<U64> {
+        self.read(ETH_CHAIN_ID, ()).await
+    }
+
+    /// Stream the car file of the blocks under `cid` picked by `selection`.
+    pub async fn get_car(&self, cid: &Cid, selection: &DagSelection) -> Result<CarDownload> {
+        self.get_body(self.car_url(&format!("ursa/v0/{cid}"), selection)?)
+            .await
+    }
+
+    /// Stream the car file of the dag at `path` under the directory `cid`.
+    pub async fn get_car_path(
+        &self,
+        cid: &Cid,
+        path: &str,
+        selection: &DagSelection,
+    ) -> Result<CarDownload> {
+        let path = path.trim_start_matches('/');
+        self.get_body(self.car_url(&format!("ursa/v0/{cid}/{path}"), selection)?)
+            .await
+    }
+
+    /// Progress of the fetch of `cid`, `None` if the node isn't fetching it.
+    pub async fn get_progress(&self, cid: &Cid) -> Result<Option<BitswapProgress>> {
+        let url = self.http_url(&format!("ursa/v0/progress/{cid}"))?;
+        let mut res = self
+            .send(|| Ok(self.http_client().get(url.clone())), self.retries())
+            .await?;
+        if res.status() == StatusCode::NotFound {
+            return Ok(None);
+        }
+        let body = Self::ok_body(&mut res).await?;
+        serde_json::from_str(&body)
+            .map(Some)
+            .map_err(ClientError::Decode)
+    }
+
+    /// Upload a car file, streamed as a multipart form. Returns the roots of the car file.
+    pub async fn upload_car<R>(&self, car: Car<R>) -> Result<Vec<Cid>>
+    where
+        R: AsyncRead + Send + Sync + Unpin + 'static,
+    {
+        let boundary = format!(
+            "ursa-car-{}",
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        );
+        let prefix = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"upload.car\"\r\nContent-Type: {CAR_CONTENT_TYPE}\r\n\r\n"
+        );
+        let suffix = format!("\r\n--{boundary}--\r\n");
+        let len = prefix.len() as u64 + car.size + suffix.len() as u64;
+        let reader = Cursor::new(prefix.into_bytes())
+            .chain(car)
+            .chain(Cursor::new(suffix.into_bytes()));
+
+        // the car is read once, so the body can only be sent once
+        let url = self.http_url("ursa/v0/")?;
+        let mut body = Some(Body::from_reader(
+            BufReader::new(reader),
+            Some(len as usize),
+        ));
+        let mut res = self
+            .send(
+                || {
+                    let body = body.take().ok_or_else(|| {
+                        ClientError::Transport("the upload body was already sent".to_string())
+                    })?;
+                    Ok(self
+                        .http_client()
+                        .post(url.clone())
+                        .content_type(format!("multipart/form-data; boundary={boundary}").as_str())
+                        .body(body))
+                },
+                0,
+            )
+            .await?;
+        let body = Self::ok_body(&mut res).await?;
+        let roots: Vec<String> = serde_json::from_str(&body).map_err(ClientError::Decode)?;
+        roots
+            .iter()
+            .map(|root| Cid::from_str(root))
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| ClientError::Decode(serde_json::Error::custom(e)))
+    }
+
+    fn car_url(&self, path: &str, selection: &DagSelection) -> Result<Url> {
+        let mut url = self.http_url(path)?;
+        if let Value::Object(fields) =
+            serde_json::to_value(selection).map_err(ClientError::Encode)?
+        {
+            if !fields.is_empty() {
+                let mut query = url.query_pairs_mut();
+                for (key, value) in fields {
+                    match value {
+                        Value::String(value) => query.append_pair(&key, &value),
+                        value => query.append_pair(&key, &value.to_string()),
+                    };
+                }
+            }
+        }
+        Ok(url)
+    }
+
+    async fn get_body(&self, url: Url) -> Result<CarDownload> {
+        let mut res = self
+            .send(|| Ok(self.http_client().get(url.clone())), self.retries())
+            .await?;
+        if res.status().is_success() {
+            Ok(CarDownload(res.take_body()))
+        } else {
+            Err(ClientError::Http {
+                status: res.status() as u16,
+                body: res.body_string().await.unwrap_or_default(),
+            })
+        }
+    }
 }
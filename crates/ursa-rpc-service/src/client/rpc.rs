@@ -1,16 +1,28 @@
-use anyhow::Result;
-use jsonrpc_v2::{Error, Id, RequestObject, V2};
-
-use crate::config::ServerConfig;
+use jsonrpc_v2::{Id, RequestObject, V2};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error, info};
+use serde_json::Value;
+use std::{env, time::Duration};
+use surf::{Client, RequestBuilder, Response, StatusCode, Url};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+use crate::config::ServerConfig;
+
+/// Environment variable overriding the endpoint of the rpc server.
+pub const RPC_URL_ENV: &str = "URSA_RPC_URL";
+/// Environment variable holding the API key sent with every request.
+pub const RPC_TOKEN_ENV: &str = "URSA_RPC_TOKEN";
+/// Delay before the first retry, doubled on every attempt.
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
 
 /// Error object in a response
 #[derive(Deserialize)]
 pub struct JsonRpcError {
     pub code: i64,
     pub message: String,
+    #[serde(default)]
+    pub data: Option<Value>,
 }
 
 #[derive(Deserialize)]
@@ -28,94 +40,236 @@ pub enum JsonRpcResponse<R> {
     },
 }
 
-pub enum RpcMethod {
-    Put,
-    Post,
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("invalid rpc endpoint {0:?}, expected an http or https url")]
+    InvalidEndpoint(String),
+    #[error("failed to encode the request: {0}")]
+    Encode(serde_json::Error),
+    #[error("failed to reach the rpc server: {0}")]
+    Transport(String),
+    #[error("rpc server responded with http status {status}: {body}")]
+    Http { status: u16, body: String },
+    #[error("rpc error {code}: {message}")]
+    Rpc {
+        code: i64,
+        message: String,
+        data: Option<Value>,
+    },
+    #[error("failed to decode the response: {0}")]
+    Decode(serde_json::Error),
 }
 
-/// Utility method for sending RPC requests over HTTP
-pub(crate) async fn call<P, R>(method_name: &str, params: P, method: RpcMethod) -> Result<R, Error>
-where
-    P: Serialize,
-    R: DeserializeOwned,
-{
-    if let Ok(value) = serde_json::to_value(params) {
-        let rpc_req = RequestObject::request()
-            .with_method(method_name)
-            .with_params(value)
-            .with_id(1)
-            .finish();
-
-        let ServerConfig { port, addr, .. } = ServerConfig::default();
-        let api_url = format!("http://{addr}:{port}/rpc/v0");
-
-        info!("Using JSON-RPC v2 HTTP URL: {api_url}");
-        debug!("rpc_req {:?}", rpc_req);
-
-        // TODO(arslan): Add authentication
-        if let Ok(from_json) = surf::Body::from_json(&rpc_req) {
-            let mut http_res = match method {
-                RpcMethod::Post => surf::post(api_url)
-                    .content_type("application/json")
-                    .body(from_json)
-                    .await
-                    .unwrap(),
-                RpcMethod::Put => surf::put(api_url)
-                    .content_type("application/json")
-                    .body(from_json)
-                    .await
-                    .unwrap(),
-            };
-            let res = http_res.body_string().await.unwrap();
+/// Client of the JSON-RPC and HTTP APIs of an ursa node.
+#[derive(Clone)]
+pub struct UrsaClient {
+    endpoint: Url,
+    token: Option<String>,
+    retries: u32,
+    client: Client,
+}
 
-            let code = http_res.status() as i64;
+impl UrsaClient {
+    /// A client of the rpc server at `endpoint`, e.g. `https://node.example.com/rpc/v0`.
+    pub fn new(endpoint: &str) -> Result<Self, ClientError> {
+        let endpoint = Url::parse(endpoint)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| ClientError::InvalidEndpoint(endpoint.to_string()))?;
+        Ok(Self {
+            endpoint,
+            token: None,
+            retries: 0,
+            client: Client::new(),
+        })
+    }
 
-            if code != 200 {
-                error!("[RPCClient] - server responded with http error code {code:?} - {res}");
-                return Err(Error::Full {
-                    message: format!("Error code from HTTP Response: {code}"),
-                    code,
-                    data: None,
-                });
+    /// A client of the local node. The endpoint is [`RPC_URL_ENV`] if set and the admin
+    /// listener of `config` otherwise, the API key is read from [`RPC_TOKEN_ENV`].
+    pub fn from_config(config: &ServerConfig) -> Result<Self, ClientError> {
+        let endpoint = match env::var(RPC_URL_ENV) {
+            Ok(url) => url,
+            Err(_) => {
+                let host = match config.admin_addr.as_str() {
+                    "0.0.0.0" => "127.0.0.1",
+                    addr => addr,
+                };
+                format!("http://{host}:{}/rpc/v0", config.admin_port)
             }
+        };
+        Self::new(&endpoint).map(Self::with_token_from_env)
+    }
+
+    /// Authenticate requests with an API key.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Authenticate requests with the API key in [`RPC_TOKEN_ENV`], if set.
+    pub fn with_token_from_env(self) -> Self {
+        match env::var(RPC_TOKEN_ENV) {
+            Ok(token) => self.with_token(token),
+            Err(_) => self,
+        }
+    }
+
+    /// Retry reads failing to reach the node or rejected with 429, 502, 503 or 504 up to
+    /// `retries` times, with exponential backoff. Calls changing the state of the node, like
+    /// uploads, prefetches and transactions, are never retried since a failed attempt may
+    /// still have been applied.
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
 
-            // Return the parsed RPC result
-            let rpc_res: JsonRpcResponse<R> = match serde_json::from_str(&res) {
-                Ok(r) => r,
-                Err(e) => {
-                    return Err(Error::Full {
-                        data: None,
-                        code: 200,
-                        message: format!("Parse Error: {e}\nData: {res}"),
-                    })
+    pub fn endpoint(&self) -> &Url {
+        &self.endpoint
+    }
+
+    pub(crate) fn http_client(&self) -> &Client {
+        &self.client
+    }
+
+    pub(crate) fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// The url of the relative `path` on the http server of the node. The http routes are
+    /// next to the rpc endpoint, so a prefix in front of its `rpc/v0` is kept.
+    pub(crate) fn http_url(&self, path: &str) -> Result<Url, ClientError> {
+        let mut base = self.endpoint.clone();
+        let endpoint_path = base.path().trim_end_matches('/');
+        let root = endpoint_path
+            .strip_suffix("rpc/v0")
+            .unwrap_or(endpoint_path)
+            .trim_end_matches('/')
+            .to_string();
+        base.set_path(&format!("{root}/"));
+        base.join(path)
+            .map_err(|_| ClientError::InvalidEndpoint(format!("{base}{path}")))
+    }
+
+    /// Send the request made by `build`, authenticated with the API key. The request
+    /// is rebuilt for every attempt since its body can only be sent once.
+    pub(crate) async fn send<F>(&self, mut build: F, retries: u32) -> Result<Response, ClientError>
+    where
+        F: FnMut() -> Result<RequestBuilder, ClientError>,
+    {
+        let mut attempt = 0;
+        loop {
+            let mut req = build()?;
+            if let Some(token) = &self.token {
+                req = req.header("Authorization", format!("Bearer {token}"));
+            }
+            let error = match req.await {
+                Ok(res) if attempt < retries && is_retryable(res.status()) => {
+                    format!("http status {}", res.status())
                 }
+                Ok(res) => return Ok(res),
+                Err(e) if attempt < retries => e.to_string(),
+                Err(e) => return Err(ClientError::Transport(e.to_string())),
             };
+            let backoff = RETRY_BACKOFF * 2u32.pow(attempt);
+            warn!("request failed with {error}, retrying in {backoff:?}");
+            tokio::time::sleep(backoff).await;
+            attempt += 1;
+        }
+    }
 
-            match rpc_res {
-                JsonRpcResponse::Result { result, .. } => Ok(result),
-                JsonRpcResponse::Error { error, .. } => Err(Error::Full {
-                    data: None,
-                    code: error.code,
-                    message: error.message,
-                }),
-            }
+    /// Read a response body, mapping unsuccessful statuses to [`ClientError::Http`].
+    pub(crate) async fn ok_body(res: &mut Response) -> Result<String, ClientError> {
+        let body = res
+            .body_string()
+            .await
+            .map_err(|e| ClientError::Transport(e.to_string()))?;
+        if res.status().is_success() {
+            Ok(body)
         } else {
-            error!("[RPCClient] - There was an while serializing the rpc request");
-            Err(Error::Full {
-                data: None,
-                code: 200,
-                message: "There was an error while serializing the rpc request".to_string(),
+            Err(ClientError::Http {
+                status: res.status() as u16,
+                body,
             })
         }
-    } else {
-        error!(
-            "[RPCClient] - There was an error while converting the params to serializable value"
-        );
-        Err(Error::Full {
-            data: None,
-            code: 200,
-            message: "There was an error while converting the params to serializable value"
-                .to_string(),
-        })
     }
+
+    /// Call `method` with `params` and decode the result. The call is sent once, use
+    /// [`UrsaClient::read`] for methods that are safe to retry.
+    pub async fn call<P, R>(&self, method: &str, params: P) -> Result<R, ClientError>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        self.call_with_retries(method, params, 0).await
+    }
+
+    /// Call a `method` that doesn't change the state of the node, retried as set with
+    /// [`UrsaClient::with_retries`].
+    pub async fn read<P, R>(&self, method: &str, params: P) -> Result<R, ClientError>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        self.call_with_retries(method, params, self.retries).await
+    }
+
+    async fn call_with_retries<P, R>(
+        &self,
+        method: &str,
+        params: P,
+        retries: u32,
+    ) -> Result<R, ClientError>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let params = serde_json::to_value(params).map_err(ClientError::Encode)?;
+        let rpc_req = RequestObject::request()
+            .with_method(method)
+            .with_params(params)
+            .with_id(1)
+            .finish();
+        debug!("rpc_req {:?} to {}", rpc_req, self.endpoint);
+
+        let body = serde_json::to_vec(&rpc_req).map_err(ClientError::Encode)?;
+        let mut http_res = self
+            .send(
+                || {
+                    Ok(self
+                        .client
+                        .post(self.endpoint.clone())
+                        .content_type("application/json")
+                        .body(body.clone()))
+                },
+                retries,
+            )
+            .await?;
+        let res = http_res
+            .body_string()
+            .await
+            .map_err(|e| ClientError::Transport(e.to_string()))?;
+        let status = http_res.status() as u16;
+
+        // Error responses carry a JSON-RPC error object, prefer it over the status code
+        match serde_json::from_str::<JsonRpcResponse<R>>(&res) {
+            Ok(JsonRpcResponse::Result { result, .. }) => Ok(result),
+            Ok(JsonRpcResponse::Error { error, .. }) => Err(ClientError::Rpc {
+                code: error.code,
+                message: error.message,
+                data: error.data,
+            }),
+            Err(_) if status != 200 => Err(ClientError::Http { status, body: res }),
+            Err(e) => Err(ClientError::Decode(e)),
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TooManyRequests
+            | StatusCode::BadGateway
+            | StatusCode::ServiceUnavailable
+            | StatusCode::GatewayTimeout
+    )
 }
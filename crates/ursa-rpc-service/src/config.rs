@@ -1,6 +1,8 @@
 use libp2p::Multiaddr;
 use serde::{Deserialize, Serialize};
 
+use crate::auth::AuthConfig;
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct ServerConfig {
     /// Public IP address of the node, eg. `/ip4/127.0.0.1`
@@ -12,6 +14,14 @@ pub struct ServerConfig {
     /// Address to bind to
     #[serde(default = "ServerConfig::default_addr")]
     pub addr: String,
+    /// Port of the admin listener, which allows every method without a token
+    #[serde(default = "ServerConfig::default_admin_port")]
+    pub admin_port: u16,
+    /// Address to bind the admin listener to, keep it private
+    #[serde(default = "ServerConfig::default_admin_addr")]
+    pub admin_addr: String,
+    #[serde(default)]
+    pub auth: AuthConfig,
     #[serde(default)]
     pub origin: OriginConfig,
 }
@@ -26,6 +36,12 @@ impl ServerConfig {
     fn default_addr() -> String {
         "0.0.0.0".to_string()
     }
+    fn default_admin_port() -> u16 {
+        4070
+    }
+    fn default_admin_addr() -> String {
+        "127.0.0.1".to_string()
+    }
 }
 
 impl Default for ServerConfig {
@@ -34,6 +50,9 @@ impl Default for ServerConfig {
             addresses: Self::default_addresses(),
             port: Self::default_port(),
             addr: Self::default_addr(),
+            admin_port: Self::default_admin_port(),
+            admin_addr: Self::default_admin_addr(),
+            auth: Default::default(),
             origin: Default::default(),
         }
     }
@@ -41,17 +60,63 @@ impl Default for ServerConfig {
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct OriginConfig {
-    /// Ipfs gateway url
+    /// Ipfs gateway url, used as a car gateway when no `origins` are configured
     #[serde(default = "OriginConfig::default_ipfs_gateway")]
     pub ipfs_gateway: String,
     /// Intended for testing purposes
     pub use_https: Option<bool>,
+    /// Origins tried in order until one of them returns the content
+    #[serde(default)]
+    pub origins: Vec<OriginSpec>,
+    /// Seconds to wait for an origin to deliver a dag before trying the next one. Defaults to 5 minutes
+    #[serde(default = "OriginConfig::default_timeout")]
+    pub timeout: u64,
+    /// Consecutive failures after which an origin is only tried when the others fail. Defaults to 3
+    #[serde(default = "OriginConfig::default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Seconds before an unhealthy origin is tried first again. Defaults to 60 seconds
+    #[serde(default = "OriginConfig::default_cooldown")]
+    pub cooldown: u64,
+    /// Maximum bytes of a dag fetched from the origins. Defaults to 1GiB
+    #[serde(default = "OriginConfig::default_max_size")]
+    pub max_size: u64,
+    /// Seconds to wait for an origin to start responding. Defaults to 10 seconds
+    #[serde(default = "OriginConfig::default_connect_timeout")]
+    pub connect_timeout: u64,
+    /// Seconds to wait for the next block of a response. Defaults to 30 seconds
+    #[serde(default = "OriginConfig::default_read_timeout")]
+    pub read_timeout: u64,
+    /// Seconds a failed fetch is answered from cache before the origins are asked again, 0
+    /// disables the cache. Defaults to 5 minutes
+    #[serde(default = "OriginConfig::default_negative_cache_ttl")]
+    pub negative_cache_ttl: u64,
 }
 
 impl OriginConfig {
     pub fn default_ipfs_gateway() -> String {
         "ipfs.io".to_string()
     }
+    fn default_timeout() -> u64 {
+        300
+    }
+    fn default_failure_threshold() -> u32 {
+        3
+    }
+    fn default_cooldown() -> u64 {
+        60
+    }
+    fn default_max_size() -> u64 {
+        1024 * 1024 * 1024
+    }
+    fn default_connect_timeout() -> u64 {
+        10
+    }
+    fn default_read_timeout() -> u64 {
+        30
+    }
+    fn default_negative_cache_ttl() -> u64 {
+        300
+    }
 }
 
 impl Default for OriginConfig {
@@ -59,6 +124,52 @@ impl Default for OriginConfig {
         Self {
             ipfs_gateway: Self::default_ipfs_gateway(),
             use_https: None,
+            origins: Vec::new(),
+            timeout: Self::default_timeout(),
+            failure_threshold: Self::default_failure_threshold(),
+            cooldown: Self::default_cooldown(),
+            max_size: Self::default_max_size(),
+            connect_timeout: Self::default_connect_timeout(),
+            read_timeout: Self::default_read_timeout(),
+            negative_cache_ttl: Self::default_negative_cache_ttl(),
         }
     }
 }
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct OriginSpec {
+    #[serde(flatten)]
+    pub kind: OriginKind,
+    /// Overrides the `timeout` of the origin config for this origin
+    pub timeout: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OriginKind {
+    /// Trustless gateway returning the dag as a car file, eg. `https://ipfs.io`
+    CarGateway { url: String },
+    /// Gateway fetching the dag block by block with `?format=raw`
+    PathGateway { url: String },
+    /// Plain http(s) url of a car file, `{cid}` is replaced with the root cid
+    Http { url: String },
+    /// S3 compatible bucket holding the dags as `<prefix><cid>.car`
+    S3 {
+        /// Url of the service, eg. `https://s3.us-east-1.amazonaws.com`
+        endpoint: String,
+        bucket: String,
+        #[serde(default = "OriginKind::default_region")]
+        region: String,
+        #[serde(default)]
+        prefix: String,
+        /// Requests are signed when both keys are set
+        access_key_id: Option<String>,
+        secret_access_key: Option<String>,
+    },
+}
+
+impl OriginKind {
+    fn default_region() -> String {
+        "us-east-1".to_string()
+    }
+}
@@ -1,36 +1,73 @@
 pub const BASE_PATH: &str = "./car_files";
 
-use crate::api::{Car, NetworkInterface, NodeNetworkInterface};
+use crate::{
+    api::{Car, NetworkInterface, NetworkPrefetchParams, PrefetchError},
+    auth::{require_scope, Scope},
+    origin::OriginError,
+    schema,
+};
 use axum::{
-    extract::{DefaultBodyLimit, Multipart, Path},
+    extract::{DefaultBodyLimit, Multipart, Path, Query},
     http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
-    response::{IntoResponse, Response},
+    middleware,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
     routing::{get, post},
     Extension, Json, Router,
 };
-use db::Store;
-use futures::io::Cursor;
-use fvm_ipld_blockstore::Blockstore;
+use futures::{io::Cursor, stream, Stream, StreamExt};
 use hyper::StatusCode;
 use libipld::Cid;
-use std::{str::FromStr, sync::Arc};
+use std::{convert::Infallible, str::FromStr, sync::Arc};
 use tokio::task;
 use tower_http::limit::RequestBodyLimitLayer;
 use tracing::{error, info};
+use ursa_store::{BlockError, DagSelection, PathError};
+
+/// Declares the http routes of the node, with the scope they require if any, building both
+/// the router and [`ROUTES`], which the OpenAPI document is tested against.
+macro_rules! http_routes {
+    ($($method:ident $path:literal => $handler:expr $(, $scope:expr)?;)*) => {
+        pub fn init<I: NetworkInterface>() -> Router {
+            Router::new()
+                $(.route($path, {
+                    let route = $method($handler);
+                    $(let route = route
+                        .route_layer(middleware::from_fn_with_state($scope, require_scope));)?
+                    route
+                }))*
+                .layer(DefaultBodyLimit::disable())
+                .layer(RequestBodyLimitLayer::new(250 * 1024 * 1024)) // 250mb
+        }
+
+        /// Method and path of every route of [`init`].
+        pub const ROUTES: &[(&str, &str)] = &[$((stringify!($method), $path)),*];
+    };
+}
 
-pub fn init<S: Blockstore + Store + Send + Sync + 'static>() -> Router {
-    Router::new()
-        .route("/ursa/v0/", post(upload_handler::<S>))
-        .route("/ursa/v0/:cid", get(get_handler::<S>))
-        .route("/ping", get(|| async { "pong" })) // to be used for TLS verification
-        .layer(DefaultBodyLimit::disable())
-        .layer(RequestBodyLimitLayer::new(250 * 1024 * 1024)) // 250mb
+http_routes! {
+    post "/ursa/v0/" => upload_handler::<I>, Scope::Write;
+    post "/ursa/v0/prefetch" => prefetch_handler::<I>, Scope::Write;
+    get "/ursa/v0/prefetch/:id" => prefetch_status_handler::<I>, Scope::Write;
+    get "/ursa/v0/:cid" => get_handler::<I>, Scope::Read;
+    get "/ursa/v0/:cid/*path" => get_path_handler::<I>, Scope::Read;
+    get "/ursa/v0/progress/:cid" => progress_handler::<I>, Scope::Read;
+    get "/ursa/v0/progress/:cid/events" => progress_events_handler::<I>, Scope::Read;
+    // to be used for TLS verification
+    get "/ping" => || async { "pong" };
+    get "/openapi.json" => || async { Json(schema::openapi()) };
 }
 
 pub enum NetworkError {
     NotFoundError(String),
     InternalError(String),
     BadRequest(String),
+    PayloadTooLarge(String),
+    BadGateway(String),
+    GatewayTimeout(String),
+    ServiceUnavailable(String),
 }
 impl IntoResponse for NetworkError {
     fn into_response(self) -> Response {
@@ -40,16 +77,35 @@ impl IntoResponse for NetworkError {
                 (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
             }
             NetworkError::BadRequest(e) => (StatusCode::BAD_REQUEST, e).into_response(),
+            NetworkError::PayloadTooLarge(e) => (StatusCode::PAYLOAD_TOO_LARGE, e).into_response(),
+            NetworkError::BadGateway(e) => (StatusCode::BAD_GATEWAY, e).into_response(),
+            NetworkError::GatewayTimeout(e) => (StatusCode::GATEWAY_TIMEOUT, e).into_response(),
+            NetworkError::ServiceUnavailable(e) => {
+                (StatusCode::SERVICE_UNAVAILABLE, e).into_response()
+            }
         }
     }
 }
 
-pub async fn upload_handler<S>(
-    Extension(interface): Extension<Arc<NodeNetworkInterface<S>>>,
+impl NetworkError {
+    /// Error of content that couldn't be fetched, with the status of the origin failure.
+    fn fetch(err: anyhow::Error) -> Self {
+        match err.downcast_ref::<OriginError>() {
+            Some(OriginError::NotFound(_)) => NetworkError::NotFoundError(err.to_string()),
+            Some(OriginError::TooLarge { .. }) => NetworkError::PayloadTooLarge(err.to_string()),
+            Some(OriginError::Timeout(_)) => NetworkError::GatewayTimeout(err.to_string()),
+            Some(OriginError::Failed { .. }) => NetworkError::BadGateway(err.to_string()),
+            None => NetworkError::InternalError(err.to_string()),
+        }
+    }
+}
+
+pub async fn upload_handler<I>(
+    Extension(interface): Extension<Arc<I>>,
     mut buf: Multipart,
 ) -> Result<impl IntoResponse, NetworkError>
 where
-    S: Blockstore + Store + Send + Sync + 'static,
+    I: NetworkInterface,
 {
     let upload_task = task::spawn(async move {
         info!("uploading file via http");
@@ -71,11 +127,18 @@ where
                     .put_car(Car::new(vec_data.len() as u64, reader))
                     .await
                 {
+                    Err(err) if err.is::<BlockError>() => {
+                        error!("{:?}", err);
+                        Err(NetworkError::BadRequest(err.to_string()))
+                    }
                     Err(err) => {
                         error!("{:?}", err);
                         Err(NetworkError::InternalError(err.to_string()))
                     }
-                    Ok(res) => Ok((StatusCode::OK, Json(format!("{res:?}")))),
+                    Ok(roots) => {
+                        let roots: Vec<String> = roots.iter().map(Cid::to_string).collect();
+                        Ok((StatusCode::OK, Json(roots)))
+                    }
                 }
             } else {
                 Err(NetworkError::BadRequest(
@@ -91,40 +154,185 @@ where
         .map_err(|err| NetworkError::InternalError(err.to_string()))?
 }
 
-pub async fn get_handler<S>(
+pub async fn get_handler<I>(
     Path(cid_str): Path<String>,
-    Extension(interface): Extension<Arc<NodeNetworkInterface<S>>>,
+    Query(selection): Query<DagSelection>,
+    Extension(interface): Extension<Arc<I>>,
 ) -> Result<impl IntoResponse, NetworkError>
 where
-    S: Blockstore + Store + Send + Sync + 'static,
+    I: NetworkInterface,
 {
     info!("Streaming file over http");
-    if let Ok(cid) = Cid::from_str(&cid_str) {
-        let mut res = Response::builder();
-        return match interface.stream(cid).await {
-            Ok(body) => {
-                let headers = res.headers_mut().unwrap();
-                headers.insert(
-                    CONTENT_TYPE,
-                    "application/vnd.curl.car; charset=utf-8".parse().unwrap(),
-                );
-                headers.insert(
-                    CONTENT_DISPOSITION,
-                    format!("attachment; filename=\"{cid_str}.car\"")
-                        .parse()
-                        .unwrap(),
-                );
-
-                Ok(res.status(StatusCode::OK).body(body).unwrap())
-            }
-            Err(err) => {
-                error!("{:?}", err);
-                Err(NetworkError::InternalError(err.to_string()))
-            }
-        };
-    } else {
-        Err(NetworkError::InternalError(format!(
+    let cid = Cid::from_str(&cid_str).map_err(|_| {
+        NetworkError::BadRequest(format!(
+            "Invalid Cid String, Cannot Parse {cid_str:?} to CID"
+        ))
+    })?;
+    let mut res = Response::builder();
+    match interface.stream(cid, selection).await {
+        Ok(body) => {
+            let headers = res.headers_mut().unwrap();
+            headers.insert(
+                CONTENT_TYPE,
+                "application/vnd.curl.car; charset=utf-8".parse().unwrap(),
+            );
+            headers.insert(
+                CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{cid_str}.car\"")
+                    .parse()
+                    .unwrap(),
+            );
+
+            Ok(res.status(StatusCode::OK).body(body).unwrap())
+        }
+        Err(err) => {
+            error!("{:?}", err);
+            Err(NetworkError::fetch(err))
+        }
+    }
+}
+
+pub async fn get_path_handler<I>(
+    Path((cid_str, path)): Path<(String, String)>,
+    Query(selection): Query<DagSelection>,
+    Extension(interface): Extension<Arc<I>>,
+) -> Result<impl IntoResponse, NetworkError>
+where
+    I: NetworkInterface,
+{
+    let root = Cid::from_str(&cid_str).map_err(|_| {
+        NetworkError::BadRequest(format!(
+            "Invalid Cid String, Cannot Parse {cid_str:?} to CID"
+        ))
+    })?;
+    let cid = match interface.resolve_path(root, path).await {
+        Ok(cid) => cid,
+        Err(err) if err.is::<PathError>() => {
+            return Err(NetworkError::NotFoundError(err.to_string()));
+        }
+        Err(err) => {
+            error!("{:?}", err);
+            return Err(NetworkError::fetch(err));
+        }
+    };
+    get_handler(
+        Path(cid.to_string()),
+        Query(selection),
+        Extension(interface),
+    )
+    .await
+    .map(IntoResponse::into_response)
+}
+
+pub async fn progress_handler<I>(
+    Path(cid_str): Path<String>,
+    Extension(interface): Extension<Arc<I>>,
+) -> Result<impl IntoResponse, NetworkError>
+where
+    I: NetworkInterface,
+{
+    let cid = Cid::from_str(&cid_str).map_err(|_| {
+        NetworkError::BadRequest(format!(
             "Invalid Cid String, Cannot Parse {cid_str:?} to CID"
-        )))
+        ))
+    })?;
+    match interface.get_progress(cid).await {
+        Ok(Some(progress)) => Ok(Json(progress)),
+        Ok(None) => Err(NetworkError::NotFoundError(format!(
+            "No fetch in progress for {cid_str}"
+        ))),
+        Err(err) => {
+            error!("{:?}", err);
+            Err(NetworkError::InternalError(err.to_string()))
+        }
+    }
+}
+
+/// Stream the progress of a network fetch as server-sent events, until the fetch finishes.
+pub async fn progress_events_handler<I>(
+    Path(cid_str): Path<String>,
+    Extension(interface): Extension<Arc<I>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, NetworkError>
+where
+    I: NetworkInterface,
+{
+    let cid = Cid::from_str(&cid_str).map_err(|_| {
+        NetworkError::BadRequest(format!(
+            "Invalid Cid String, Cannot Parse {cid_str:?} to CID"
+        ))
+    })?;
+    let receiver = match interface.subscribe_progress(cid).await {
+        Ok(Some(receiver)) => receiver,
+        Ok(None) => {
+            return Err(NetworkError::NotFoundError(format!(
+                "No fetch in progress for {cid_str}"
+            )))
+        }
+        Err(err) => {
+            error!("{:?}", err);
+            return Err(NetworkError::InternalError(err.to_string()));
+        }
+    };
+
+    let current = *receiver.borrow();
+    let updates = stream::unfold(receiver, |mut receiver| async move {
+        // errors once the fetch finished and dropped the sender
+        receiver.changed().await.ok()?;
+        let progress = *receiver.borrow();
+        Some((progress, receiver))
+    });
+    let events = stream::once(async move { current })
+        .chain(updates)
+        .map(|progress| Ok(Event::default().json_data(progress).unwrap_or_default()));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+pub async fn prefetch_handler<I>(
+    Extension(interface): Extension<Arc<I>>,
+    Json(params): Json<NetworkPrefetchParams>,
+) -> Result<impl IntoResponse, NetworkError>
+where
+    I: NetworkInterface,
+{
+    let roots = params
+        .cids
+        .iter()
+        .map(|cid| {
+            Cid::from_str(cid).map_err(|_| {
+                NetworkError::BadRequest(format!("Invalid Cid String, Cannot Parse {cid:?} to CID"))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match interface.prefetch(roots, params.replicate).await {
+        Ok(job) => Ok((StatusCode::ACCEPTED, Json(job))),
+        Err(err) => {
+            error!("{:?}", err);
+            Err(match err.downcast_ref::<PrefetchError>() {
+                Some(PrefetchError::InvalidRoots) => NetworkError::BadRequest(err.to_string()),
+                Some(PrefetchError::TooManyJobs) => {
+                    NetworkError::ServiceUnavailable(err.to_string())
+                }
+                None => NetworkError::InternalError(err.to_string()),
+            })
+        }
+    }
+}
+
+pub async fn prefetch_status_handler<I>(
+    Path(id): Path<String>,
+    Extension(interface): Extension<Arc<I>>,
+) -> Result<impl IntoResponse, NetworkError>
+where
+    I: NetworkInterface,
+{
+    match interface.get_prefetch(id.clone()).await {
+        Ok(Some(job)) => Ok(Json(job)),
+        Ok(None) => Err(NetworkError::NotFoundError(format!(
+            "No prefetch job with id {id}"
+        ))),
+        Err(err) => {
+            error!("{:?}", err);
+            Err(NetworkError::InternalError(err.to_string()))
+        }
     }
 }
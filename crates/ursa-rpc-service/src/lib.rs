@@ -1,8 +1,12 @@
 pub mod api;
+pub mod auth;
 pub mod client;
 pub mod config;
 pub mod http;
+pub mod mock;
+pub mod origin;
 pub mod rpc;
+pub mod schema;
 pub mod server;
 mod service;
 
@@ -0,0 +1,352 @@
+//! # Mock node for tests.
+//!
+//! [`MockNetworkInterface`] implements [`NetworkInterface`] on an in-memory store without a
+//! p2p node, consensus or origin, and [`MockServer`] serves it over the same rpc and http
+//! routes as a real node, so applications can test against [`UrsaClient`].
+
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Result};
+use async_fs::{create_dir_all, File};
+use async_trait::async_trait;
+use axum::{body::StreamBody, Extension, Router};
+use db::MemoryDB;
+use futures::{stream, AsyncRead, AsyncWrite, AsyncWriteExt};
+use fvm_ipld_car::CarHeader;
+use libipld::Cid;
+use libp2p::{Multiaddr, PeerId};
+use narwhal_types::TransactionProto;
+use tendermint_proto::abci::ResponseQuery;
+use tokio::{
+    sync::{broadcast, watch, RwLock},
+    task::{self, JoinHandle},
+};
+use tokio_util::{compat::TokioAsyncWriteCompatExt, io::ReaderStream};
+use tracing::error;
+use ursa_consensus::AbciQueryQuery;
+use ursa_network::{BitswapProgress, NetworkEvent, PeerInfo, RelayState};
+use ursa_store::{DagSelection, StoreStats, UrsaStore};
+
+use crate::{
+    api::{Car, NetworkInterface, PrefetchJob, PrefetchStatus},
+    auth::Access,
+    client::UrsaClient,
+    http,
+    origin::OriginHealth,
+    rpc::{self, RpcServer},
+};
+
+#[derive(Default)]
+struct MockState {
+    peers: HashSet<PeerId>,
+    peer_info: HashMap<PeerId, PeerInfo>,
+    relay_state: Option<RelayState>,
+    origin_health: Vec<OriginHealth>,
+    listener_addresses: Vec<Multiaddr>,
+    progress: HashMap<Cid, watch::Sender<BitswapProgress>>,
+    transactions: Vec<TransactionProto>,
+    prefetch_jobs: Vec<PrefetchJob>,
+    abci_response: ResponseQuery,
+}
+
+/// A [`NetworkInterface`] serving content from memory, with the network state set by the test.
+pub struct MockNetworkInterface {
+    pub store: Arc<UrsaStore<MemoryDB>>,
+    state: RwLock<MockState>,
+    network_events: broadcast::Sender<NetworkEvent>,
+    new_blocks: broadcast::Sender<i64>,
+}
+
+impl Default for MockNetworkInterface {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockNetworkInterface {
+    pub fn new() -> Self {
+        Self {
+            store: Arc::new(UrsaStore::new(Arc::new(MemoryDB::default()))),
+            state: Default::default(),
+            network_events: broadcast::channel(1024).0,
+            new_blocks: broadcast::channel(1024).0,
+        }
+    }
+
+    pub async fn set_peers(&self, peers: HashSet<PeerId>) {
+        self.state.write().await.peers = peers;
+    }
+
+    pub async fn set_peer_info(&self, peer_id: PeerId, info: PeerInfo) {
+        self.state.write().await.peer_info.insert(peer_id, info);
+    }
+
+    pub async fn set_relay_state(&self, relay_state: RelayState) {
+        self.state.write().await.relay_state = Some(relay_state);
+    }
+
+    pub async fn set_origin_health(&self, health: Vec<OriginHealth>) {
+        self.state.write().await.origin_health = health;
+    }
+
+    pub async fn set_listener_addresses(&self, addresses: Vec<Multiaddr>) {
+        self.state.write().await.listener_addresses = addresses;
+    }
+
+    pub async fn set_progress(&self, cid: Cid, progress: BitswapProgress) {
+        let mut state = self.state.write().await;
+        match state.progress.get(&cid) {
+            Some(sender) => {
+                sender.send_replace(progress);
+            }
+            None => {
+                state.progress.insert(cid, watch::channel(progress).0);
+            }
+        }
+    }
+
+    /// Finish the fetch of `cid`, ending its progress streams.
+    pub async fn clear_progress(&self, cid: Cid) {
+        self.state.write().await.progress.remove(&cid);
+    }
+
+    /// Response to every abci query.
+    pub async fn set_abci_response(&self, response: ResponseQuery) {
+        self.state.write().await.abci_response = response;
+    }
+
+    /// Transactions submitted so far.
+    pub async fn transactions(&self) -> Vec<TransactionProto> {
+        self.state.read().await.transactions.clone()
+    }
+
+    /// Send an event to the `ursa_subscribeNetworkEvents` subscribers.
+    pub fn emit_network_event(&self, event: NetworkEvent) {
+        let _ = self.network_events.send(event);
+    }
+
+    /// Send a block height to the `ursa_subscribeNewBlocks` subscribers.
+    pub fn emit_new_block(&self, height: i64) {
+        let _ = self.new_blocks.send(height);
+    }
+
+    fn missing(cid: Cid) -> anyhow::Error {
+        anyhow!("The block with cid {cid} is missing")
+    }
+}
+
+/// Write the blocks of `dag` as a car file with a single root.
+async fn write_car<W>(root_cid: Cid, dag: Vec<(Cid, Vec<u8>)>, writer: &mut W) -> Result<()>
+where
+    W: AsyncWrite + Send + Unpin,
+{
+    let header = CarHeader {
+        roots: vec![root_cid],
+        version: 1,
+    };
+    header
+        .write_stream_async(writer, &mut stream::iter(dag))
+        .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl NetworkInterface for MockNetworkInterface {
+    async fn get(&self, cid: Cid) -> Result<Vec<u8>> {
+        self.store
+            .get_block(&cid)?
+            .ok_or_else(|| Self::missing(cid))
+    }
+
+    async fn get_data(&self, root_cid: Cid) -> Result<Vec<(Cid, Vec<u8>)>> {
+        self.store.dag_traversal(&root_cid)
+    }
+
+    async fn get_selected(
+        &self,
+        root_cid: Cid,
+        selection: DagSelection,
+    ) -> Result<Vec<(Cid, Vec<u8>)>> {
+        self.store.select(&root_cid, &selection)
+    }
+
+    async fn resolve_path(&self, root_cid: Cid, path: String) -> Result<Cid> {
+        self.store.resolve_path(&root_cid, &path)
+    }
+
+    async fn get_file(&self, path: String, root_cid: Cid, selection: DagSelection) -> Result<()> {
+        let dag = self.get_selected(root_cid, selection).await?;
+        let file_path = PathBuf::from(path).join(format!("{root_cid}.car"));
+        if let Some(parent) = file_path.parent() {
+            create_dir_all(parent).await?;
+        }
+        let mut file = File::create(file_path).await?;
+        write_car(root_cid, dag, &mut file).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    async fn stream(
+        &self,
+        root_cid: Cid,
+        selection: DagSelection,
+    ) -> Result<StreamBody<ReaderStream<tokio::io::DuplexStream>>> {
+        let dag = self.get_selected(root_cid, selection).await?;
+        let (writer, reader) = tokio::io::duplex(1024 * 100);
+        task::spawn(async move {
+            if let Err(err) = write_car(root_cid, dag, &mut writer.compat_write()).await {
+                error!("Error while streaming the car file {err:?}");
+            }
+        });
+        Ok(StreamBody::new(ReaderStream::new(reader)))
+    }
+
+    async fn put_car<R: AsyncRead + Send + Unpin>(&self, car: Car<R>) -> Result<Vec<Cid>> {
+        self.store.load_car(car).await
+    }
+
+    async fn put_file(&self, path: String) -> Result<Vec<Cid>> {
+        self.put_car(Car::from_file(path).await?).await
+    }
+
+    async fn get_progress(&self, cid: Cid) -> Result<Option<BitswapProgress>> {
+        let state = self.state.read().await;
+        Ok(state.progress.get(&cid).map(|sender| *sender.borrow()))
+    }
+
+    async fn subscribe_progress(
+        &self,
+        cid: Cid,
+    ) -> Result<Option<watch::Receiver<BitswapProgress>>> {
+        let state = self.state.read().await;
+        Ok(state.progress.get(&cid).map(|sender| sender.subscribe()))
+    }
+
+    /// Finishes at once, roots missing from the store fail.
+    async fn prefetch(&self, roots: Vec<Cid>, replicate: bool) -> Result<PrefetchJob> {
+        let mut state = self.state.write().await;
+        let mut job = PrefetchJob::new(state.prefetch_jobs.len().to_string(), &roots, replicate);
+        for (index, cid) in roots.into_iter().enumerate() {
+            match self.store.get_block(&cid)? {
+                Some(_) => job.set_root(index, PrefetchStatus::Completed, None),
+                None => job.set_root(
+                    index,
+                    PrefetchStatus::Failed,
+                    Some(Self::missing(cid).to_string()),
+                ),
+            }
+        }
+        state.prefetch_jobs.push(job.clone());
+        Ok(job)
+    }
+
+    async fn get_prefetch(&self, id: String) -> Result<Option<PrefetchJob>> {
+        let state = self.state.read().await;
+        Ok(state.prefetch_jobs.iter().find(|job| job.id == id).cloned())
+    }
+
+    async fn get_peers(&self) -> Result<HashSet<PeerId>> {
+        Ok(self.state.read().await.peers.clone())
+    }
+
+    async fn get_peer_info(&self, peer_id: PeerId) -> Result<Option<PeerInfo>> {
+        Ok(self.state.read().await.peer_info.get(&peer_id).cloned())
+    }
+
+    async fn get_relay_state(&self) -> Result<RelayState> {
+        self.state
+            .read()
+            .await
+            .relay_state
+            .clone()
+            .ok_or_else(|| anyhow!("The mock relay state is not set"))
+    }
+
+    async fn get_listener_addresses(&self) -> Result<Vec<Multiaddr>> {
+        Ok(self.state.read().await.listener_addresses.clone())
+    }
+
+    async fn store_stats(&self) -> Result<StoreStats> {
+        Ok(self.store.stats())
+    }
+
+    async fn origin_health(&self) -> Result<Vec<OriginHealth>> {
+        Ok(self.state.read().await.origin_health.clone())
+    }
+
+    async fn submit_narwhal_txn(&self, txn: TransactionProto) -> Result<()> {
+        self.state.write().await.transactions.push(txn);
+        Ok(())
+    }
+
+    async fn query_abci(&self, _req: AbciQueryQuery) -> Result<ResponseQuery> {
+        Ok(self.state.read().await.abci_response.clone())
+    }
+
+    fn subscribe_network_events(&self) -> broadcast::Receiver<NetworkEvent> {
+        self.network_events.subscribe()
+    }
+
+    fn subscribe_new_blocks(&self) -> broadcast::Receiver<i64> {
+        self.new_blocks.subscribe()
+    }
+}
+
+/// The rpc and http routes of a node backed by `interface`, with every scope allowed.
+pub fn router<I>(interface: Arc<I>) -> Router
+where
+    I: NetworkInterface,
+{
+    Router::new()
+        .merge(rpc::routes::network::init::<I>())
+        .merge(http::routes::network::init::<I>())
+        .layer(Extension(RpcServer::new(Arc::clone(&interface))))
+        .layer(Extension(interface))
+        .layer(Extension(Access::admin()))
+}
+
+/// A server on a random local port, stopped when dropped.
+pub struct MockServer {
+    pub interface: Arc<MockNetworkInterface>,
+    address: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    pub fn start(interface: Arc<MockNetworkInterface>) -> Result<Self> {
+        let server = axum::Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))?
+            .serve(router(Arc::clone(&interface)).into_make_service());
+        let address = server.local_addr();
+        let task = task::spawn(async move {
+            if let Err(err) = server.await {
+                error!("[mock_server] - {:?}", err);
+            }
+        });
+        Ok(Self {
+            interface,
+            address,
+            task,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// A client of this server.
+    pub fn client(&self) -> UrsaClient {
+        UrsaClient::new(&format!("http://{}/rpc/v0", self.address))
+            .expect("The mock server address is a valid url")
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,72 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use libipld::Cid;
+use surf::{http::Method, Client, RequestBuilder, Url};
+
+use super::{base_url, car_blocks, send, BlockStream, Origin, CAR_CONTENT_TYPE};
+
+/// A trustless gateway, returning the whole dag as a car file from `/ipfs/<cid>`.
+pub struct CarGateway {
+    client: Client,
+    url: String,
+}
+
+impl CarGateway {
+    pub fn new(client: Client, url: &str) -> Result<Self> {
+        Ok(Self {
+            client,
+            url: base_url(url)?,
+        })
+    }
+}
+
+#[async_trait]
+impl Origin for CarGateway {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn fetch(&self, root: Cid, max_size: u64) -> Result<BlockStream> {
+        let url = Url::parse(&format!("{}/ipfs/{root}?format=car", self.url))?;
+        let req = RequestBuilder::new(Method::Get, url)
+            .header("Accept", CAR_CONTENT_TYPE)
+            .build();
+        let mut res = send(&self.client, req).await?;
+        car_blocks(root, res.take_body(), max_size).await
+    }
+}
+
+/// A car file at a plain http(s) url, with `{cid}` in the url replaced by the root cid.
+pub struct HttpOrigin {
+    client: Client,
+    url: String,
+}
+
+impl HttpOrigin {
+    pub fn new(client: Client, url: &str) -> Result<Self> {
+        if !url.contains("{cid}") {
+            return Err(anyhow!("Http origin url {url} has no {{cid}} placeholder"));
+        }
+        Url::parse(&url.replace("{cid}", "cid"))?;
+        Ok(Self {
+            client,
+            url: url.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl Origin for HttpOrigin {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn fetch(&self, root: Cid, max_size: u64) -> Result<BlockStream> {
+        let url = Url::parse(&self.url.replace("{cid}", &root.to_string()))?;
+        let req = RequestBuilder::new(Method::Get, url)
+            .header("Accept", CAR_CONTENT_TYPE)
+            .build();
+        let mut res = send(&self.client, req).await?;
+        car_blocks(root, res.take_body(), max_size).await
+    }
+}
@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use fnv::FnvHashSet;
+use futures::{stream, AsyncReadExt, StreamExt};
+use libipld::{Block, Cid, DefaultParams};
+use surf::{http::Method, Client, RequestBuilder, Url};
+
+use super::{base_url, send, BlockStream, Origin, OriginError};
+
+const RAW_CONTENT_TYPE: &str = "application/vnd.ipld.raw";
+
+/// A path gateway without car support, the dag is walked one `?format=raw` block at a time.
+pub struct PathGateway {
+    client: Client,
+    url: String,
+}
+
+impl PathGateway {
+    pub fn new(client: Client, url: &str) -> Result<Self> {
+        Ok(Self {
+            client,
+            url: base_url(url)?,
+        })
+    }
+}
+
+struct Walk {
+    client: Client,
+    url: String,
+    stack: Vec<Cid>,
+    seen: FnvHashSet<Cid>,
+    root: Cid,
+    max_size: u64,
+    /// Bytes of the dag received so far.
+    size: u64,
+}
+
+impl Walk {
+    /// Receive the block of `cid`, failing before buffering more than what is left of the
+    /// maximum size.
+    async fn block(&mut self, cid: Cid) -> Result<Vec<u8>> {
+        let url = Url::parse(&format!("{}/ipfs/{cid}?format=raw", self.url))?;
+        let req = RequestBuilder::new(Method::Get, url)
+            .header("Accept", RAW_CONTENT_TYPE)
+            .build();
+        let mut res = send(&self.client, req).await?;
+        let limit = self.max_size.saturating_sub(self.size);
+        let too_large = OriginError::TooLarge {
+            cid: self.root,
+            max_size: self.max_size,
+        };
+        if res.len().map_or(false, |len| len as u64 > limit) {
+            return Err(too_large.into());
+        }
+        let mut data = Vec::new();
+        res.take_body()
+            .take(limit.saturating_add(1))
+            .read_to_end(&mut data)
+            .await
+            .map_err(|e| anyhow!("Error receiving block {cid}: {e}"))?;
+        if data.len() as u64 > limit {
+            return Err(too_large.into());
+        }
+        self.size += data.len() as u64;
+        Ok(data)
+    }
+}
+
+#[async_trait]
+impl Origin for PathGateway {
+    fn name(&self) -> String {
+        self.url.clone()
+    }
+
+    async fn fetch(&self, root: Cid, max_size: u64) -> Result<BlockStream> {
+        let walk = Walk {
+            client: self.client.clone(),
+            url: self.url.clone(),
+            stack: vec![root],
+            seen: FnvHashSet::default(),
+            root,
+            max_size,
+            size: 0,
+        };
+        Ok(stream::try_unfold(walk, |mut walk| async move {
+            while let Some(cid) = walk.stack.pop() {
+                if !walk.seen.insert(cid) {
+                    continue;
+                }
+                let data = walk.block(cid).await?;
+                // the links are only followed from blocks matching their cid
+                Block::<DefaultParams>::new(cid, data.clone())?.references(&mut walk.stack)?;
+                return Ok(Some(((cid, data), walk)));
+            }
+            Ok(None)
+        })
+        .boxed())
+    }
+}
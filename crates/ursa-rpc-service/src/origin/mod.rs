@@ -0,0 +1,502 @@
+//! # Origins.
+//!
+//! Content that can't be found on the network is fetched from origins. [`Origins`] tries the
+//! configured origins in order with a timeout each, and moves origins that keep failing to the
+//! end of the list until their cooldown expires. Blocks are streamed to the caller as they
+//! arrive, the store verifies them against their cid. Dags larger than the maximum size are
+//! abandoned before the block crossing the limit is buffered, and failed fetches are
+//! remembered for a while so they aren't retried on every request.
+
+mod car;
+mod gateway;
+mod s3;
+
+pub use car::{CarGateway, HttpOrigin};
+pub use gateway::PathGateway;
+#[cfg(test)]
+pub(crate) use s3::sign;
+pub use s3::{S3Credentials, S3Origin};
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::Cursor,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::{
+    future,
+    stream::{self, BoxStream},
+    AsyncRead, AsyncReadExt, StreamExt, TryStreamExt,
+};
+use fvm_ipld_car::CarReader;
+use libipld::Cid;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use surf::{Body, Client, Request, Response, StatusCode, Url};
+use thiserror::Error;
+use tokio::time::{error::Elapsed, timeout};
+use tracing::{info, warn};
+use ursa_store::put_batched;
+
+use crate::config::{OriginConfig, OriginKind};
+
+pub(crate) const CAR_CONTENT_TYPE: &str = "application/vnd.ipld.car";
+/// Longest car header read, it only holds the roots.
+const MAX_CAR_HEADER_SIZE: u64 = 64 * 1024;
+/// Longest cid in a car block, the rest of the block is counted against the maximum size.
+const MAX_CID_SIZE: u64 = 256;
+
+/// Blocks of a dag, in the order the origin sends them.
+pub type BlockStream = BoxStream<'static, Result<(Cid, Vec<u8>)>>;
+
+/// Why a dag couldn't be fetched from the origins.
+#[derive(Clone, Debug, Error)]
+pub enum OriginError {
+    #[error("cid {0} wasn't found on any origin")]
+    NotFound(Cid),
+    #[error("the dag of cid {cid} exceeds the maximum size of {max_size} bytes")]
+    TooLarge { cid: Cid, max_size: u64 },
+    #[error("timed out fetching cid {0} from the origins")]
+    Timeout(Cid),
+    #[error("failed to fetch cid {cid} from the origins: [{message}]")]
+    Failed { cid: Cid, message: String },
+}
+
+/// Unsuccessful response of an origin.
+#[derive(Debug, Error)]
+#[error("{url} responded with status {status}")]
+pub struct StatusError {
+    pub url: Url,
+    pub status: StatusCode,
+}
+
+/// A source of content outside the network.
+#[async_trait]
+pub trait Origin: Send + Sync {
+    /// Name of the origin in logs and errors.
+    fn name(&self) -> String;
+
+    /// Stream the blocks of the dag under `root`, failing with [`OriginError::TooLarge`]
+    /// before buffering a block that takes the data of the dag past `max_size` bytes.
+    async fn fetch(&self, root: Cid, max_size: u64) -> Result<BlockStream>;
+}
+
+/// Send `req`, failing on transport errors and on any status but success.
+async fn send(client: &Client, req: Request) -> Result<Response> {
+    let url = req.url().clone();
+    let res = client
+        .send(req)
+        .await
+        .map_err(|e| anyhow!("Error requesting {url}: {e}"))?;
+    if !res.status().is_success() {
+        return Err(StatusError {
+            url,
+            status: res.status(),
+        }
+        .into());
+    }
+    Ok(res)
+}
+
+/// Read a varint length prefixed frame, failing before buffering it if it is longer than
+/// `limit`. Returns `None` at the end of `reader`.
+async fn read_frame<R>(
+    reader: &mut R,
+    limit: u64,
+    too_large: impl FnOnce() -> OriginError,
+) -> Result<Option<Vec<u8>>>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut len: u64 = 0;
+    for i in 0..10 {
+        let mut byte = [0];
+        if reader.read(&mut byte).await? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(anyhow!("Unexpected end of car file"));
+        }
+        len |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            if len > limit {
+                return Err(too_large().into());
+            }
+            let mut frame = vec![0; len as usize];
+            reader.read_exact(&mut frame).await?;
+            return Ok(Some(frame));
+        }
+    }
+    Err(anyhow!("Invalid varint in car file"))
+}
+
+/// Stream the blocks of the car file in `body`, which must have `root` among its roots.
+/// Blocks are rejected before they are buffered once the dag exceeds `max_size`.
+async fn car_blocks(root: Cid, mut body: Body, max_size: u64) -> Result<BlockStream> {
+    let too_large = move || OriginError::TooLarge {
+        cid: root,
+        max_size,
+    };
+    let header = read_frame(&mut body, MAX_CAR_HEADER_SIZE, too_large)
+        .await?
+        .ok_or_else(|| anyhow!("Empty car file"))?;
+    // the header is bounded, so the car reader can parse it from memory
+    let mut prefixed = Vec::with_capacity(header.len() + 10);
+    let mut len = header.len() as u64;
+    while len >= 0x80 {
+        prefixed.push(len as u8 | 0x80);
+        len >>= 7;
+    }
+    prefixed.push(len as u8);
+    prefixed.extend(header);
+    let car = CarReader::new(futures::io::Cursor::new(prefixed)).await?;
+    if !car.header.roots.contains(&root) {
+        return Err(anyhow!("Cid {root} not found in the roots of the car file"));
+    }
+
+    Ok(
+        stream::try_unfold((body, 0), move |(mut body, size)| async move {
+            let limit = max_size.saturating_sub(size).saturating_add(MAX_CID_SIZE);
+            let frame = match read_frame(&mut body, limit, too_large).await? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+            let mut cursor = Cursor::new(frame.as_slice());
+            let cid = Cid::read_bytes(&mut cursor)?;
+            let data = frame[cursor.position() as usize..].to_vec();
+            let size = size + data.len() as u64;
+            Ok(Some(((cid, data), (body, size))))
+        })
+        .boxed(),
+    )
+}
+
+/// Run `future`, failing with [`Elapsed`] if it takes longer than `duration`.
+async fn within<T, F>(duration: Option<Duration>, future: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    match duration {
+        Some(duration) => timeout(duration, future).await?,
+        None => future.await,
+    }
+}
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<StatusError>()
+        .map_or(false, |error| error.status == StatusCode::NotFound)
+}
+
+/// Base url without the trailing slash, so paths can be appended with `format!`.
+fn base_url(url: &str) -> Result<String> {
+    Url::parse(url)?;
+    Ok(url.trim_end_matches('/').to_string())
+}
+
+#[derive(Default)]
+struct Health {
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+struct Entry {
+    origin: Box<dyn Origin>,
+    timeout: Duration,
+    health: Mutex<Health>,
+}
+
+/// Health of an origin, as tracked by [`Origins`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, JsonSchema)]
+pub struct OriginHealth {
+    /// Name of the origin, its url
+    pub name: String,
+    /// Failures since the last success.
+    pub failures: u32,
+    /// Unhealthy origins are only tried after the healthy ones.
+    pub healthy: bool,
+}
+
+/// An ordered list of origins.
+pub struct Origins {
+    entries: Vec<Entry>,
+    failure_threshold: u32,
+    cooldown: Duration,
+    max_size: u64,
+    connect_timeout: Option<Duration>,
+    read_timeout: Option<Duration>,
+    negative_cache_ttl: Duration,
+    /// Failed fetches and when they expire.
+    failed_fetches: Mutex<HashMap<Cid, (Instant, OriginError)>>,
+}
+
+impl Default for Origins {
+    fn default() -> Self {
+        Self::from_config(&OriginConfig::default()).expect("The default origin is valid")
+    }
+}
+
+impl Origins {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            entries: Vec::new(),
+            failure_threshold,
+            cooldown,
+            max_size: u64::MAX,
+            connect_timeout: None,
+            read_timeout: None,
+            negative_cache_ttl: Duration::ZERO,
+            failed_fetches: Default::default(),
+        }
+    }
+
+    /// Abandon dags larger than `max_size` bytes.
+    pub fn with_max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Fail origins that don't start responding within `connect_timeout`.
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Fail origins that don't send the next block within `read_timeout`.
+    pub fn with_read_timeout(mut self, read_timeout: Duration) -> Self {
+        self.read_timeout = Some(read_timeout);
+        self
+    }
+
+    /// Answer fetches of a dag that failed from cache for `ttl`, zero disables the cache.
+    pub fn with_negative_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_cache_ttl = ttl;
+        self
+    }
+
+    /// Add an origin, tried after the ones already added.
+    pub fn with_origin<O: Origin + 'static>(mut self, origin: O, timeout: Duration) -> Self {
+        self.entries.push(Entry {
+            origin: Box::new(origin),
+            timeout,
+            health: Default::default(),
+        });
+        self
+    }
+
+    /// The origins of the config, or its ipfs gateway if there are none.
+    pub fn from_config(config: &OriginConfig) -> Result<Self> {
+        let client = Client::new();
+        let timeout = Duration::from_secs(config.timeout);
+        let mut origins = Self::new(
+            config.failure_threshold,
+            Duration::from_secs(config.cooldown),
+        )
+        .with_max_size(config.max_size)
+        .with_connect_timeout(Duration::from_secs(config.connect_timeout))
+        .with_read_timeout(Duration::from_secs(config.read_timeout))
+        .with_negative_cache_ttl(Duration::from_secs(config.negative_cache_ttl));
+
+        if config.origins.is_empty() {
+            let scheme = match config.use_https {
+                Some(false) => "http",
+                _ => "https",
+            };
+            let url = format!("{scheme}://{}", config.ipfs_gateway);
+            return Ok(origins.with_origin(CarGateway::new(client, &url)?, timeout));
+        }
+
+        for spec in &config.origins {
+            let timeout = spec.timeout.map(Duration::from_secs).unwrap_or(timeout);
+            let client = client.clone();
+            origins = match &spec.kind {
+                OriginKind::CarGateway { url } => {
+                    origins.with_origin(CarGateway::new(client, url)?, timeout)
+                }
+                OriginKind::PathGateway { url } => {
+                    origins.with_origin(PathGateway::new(client, url)?, timeout)
+                }
+                OriginKind::Http { url } => {
+                    origins.with_origin(HttpOrigin::new(client, url)?, timeout)
+                }
+                OriginKind::S3 {
+                    endpoint,
+                    bucket,
+                    region,
+                    prefix,
+                    access_key_id,
+                    secret_access_key,
+                } => {
+                    let credentials = match (access_key_id, secret_access_key) {
+                        (Some(access_key_id), Some(secret_access_key)) => Some(S3Credentials {
+                            access_key_id: access_key_id.clone(),
+                            secret_access_key: secret_access_key.clone(),
+                        }),
+                        (None, None) => None,
+                        _ => {
+                            return Err(anyhow!(
+                                "S3 origin {endpoint}/{bucket} needs both access keys or none"
+                            ))
+                        }
+                    };
+                    let origin = S3Origin::new(client, endpoint, bucket, region, prefix)?;
+                    origins.with_origin(origin.with_credentials(credentials), timeout)
+                }
+            };
+        }
+        Ok(origins)
+    }
+
+    /// Health of the origins, in the order they were added.
+    pub fn health(&self) -> Vec<OriginHealth> {
+        let now = Instant::now();
+        self.entries
+            .iter()
+            .map(|entry| {
+                let health = entry.health.lock().unwrap();
+                OriginHealth {
+                    name: entry.origin.name(),
+                    failures: health.failures,
+                    healthy: Self::is_healthy(&health, now),
+                }
+            })
+            .collect()
+    }
+
+    /// The error of a recent failed fetch of `root`, answered without asking the origins
+    /// again.
+    pub fn failed_fetch(&self, root: Cid) -> Option<OriginError> {
+        let failed_fetches = self.failed_fetches.lock().unwrap();
+        let (expires, error) = failed_fetches.get(&root)?;
+        (*expires > Instant::now()).then(|| error.clone())
+    }
+
+    fn remember_failure(&self, root: Cid, error: &OriginError) {
+        if self.negative_cache_ttl.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        let mut failed_fetches = self.failed_fetches.lock().unwrap();
+        failed_fetches.retain(|_, (expires, _)| *expires > now);
+        failed_fetches.insert(root, (now + self.negative_cache_ttl, error.clone()));
+    }
+
+    fn is_healthy(health: &Health, now: Instant) -> bool {
+        health.retry_at.map_or(true, |retry_at| retry_at <= now)
+    }
+
+    fn succeeded(&self, entry: &Entry) {
+        *entry.health.lock().unwrap() = Health::default();
+    }
+
+    fn failed(&self, entry: &Entry) {
+        let mut health = entry.health.lock().unwrap();
+        health.failures += 1;
+        if health.failures >= self.failure_threshold {
+            health.retry_at = Some(Instant::now() + self.cooldown);
+        }
+    }
+
+    /// Fetch the dag under `root` from the first origin that delivers it, passing its blocks
+    /// to `put` in batches. An error from `put` fails the origin, so the next one is tried.
+    ///
+    /// Failures are reported as an [`OriginError`]: not found if every origin answered 404,
+    /// which doesn't count against their health, and too large as soon as an origin crosses
+    /// the maximum size. They are remembered for the negative cache ttl.
+    pub async fn fetch<F, Fut>(&self, root: Cid, put: F) -> Result<()>
+    where
+        F: FnMut(Vec<(Cid, Vec<u8>)>) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        if let Some(error) = self.failed_fetch(root) {
+            return Err(error.into());
+        }
+        let result = self.fetch_uncached(root, put).await;
+        if let Err(error) = &result {
+            self.remember_failure(root, error);
+        }
+        result.map_err(Into::into)
+    }
+
+    async fn fetch_uncached<F, Fut>(&self, root: Cid, mut put: F) -> Result<(), OriginError>
+    where
+        F: FnMut(Vec<(Cid, Vec<u8>)>) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        // healthy origins first, the others are a last resort
+        let now = Instant::now();
+        let mut entries: Vec<&Entry> = self.entries.iter().collect();
+        entries.sort_by_key(|entry| !Self::is_healthy(&entry.health.lock().unwrap(), now));
+
+        let (mut failed, mut timed_out) = (false, false);
+        let mut errors = Vec::new();
+        for entry in entries {
+            let name = entry.origin.name();
+            info!("Fetching cid {root} from origin {name}");
+            let attempt = async {
+                let blocks = within(
+                    self.connect_timeout,
+                    entry.origin.fetch(root, self.max_size),
+                )
+                .await?;
+                let read_timeout = self.read_timeout;
+                let blocks = stream::try_unfold(blocks, move |mut blocks| async move {
+                    let block = within(read_timeout, blocks.try_next()).await?;
+                    Ok(block.map(|block| (block, blocks)))
+                });
+                let (max_size, mut size) = (self.max_size, 0);
+                let blocks = blocks.and_then(move |block| {
+                    size += block.1.len() as u64;
+                    future::ready(if size > max_size {
+                        Err(OriginError::TooLarge {
+                            cid: root,
+                            max_size,
+                        }
+                        .into())
+                    } else {
+                        Ok(block)
+                    })
+                });
+                put_batched(Box::pin(blocks), &mut put).await
+            };
+
+            let error = match timeout(entry.timeout, attempt).await {
+                Ok(Ok(())) => {
+                    self.succeeded(entry);
+                    return Ok(());
+                }
+                Ok(Err(e)) => e,
+                Err(e) => e.into(),
+            };
+            if let Some(error @ OriginError::TooLarge { .. }) = error.downcast_ref::<OriginError>()
+            {
+                warn!("Abandoned cid {root} from origin {name}: {error}");
+                return Err(error.clone());
+            }
+            if is_not_found(&error) {
+                info!("Cid {root} not found on origin {name}");
+            } else {
+                warn!("Failed to fetch cid {root} from origin {name}: {error}");
+                failed = true;
+                timed_out |= error.is::<Elapsed>();
+                self.failed(entry);
+            }
+            errors.push(format!("{name}: {error}"));
+        }
+
+        Err(if !failed {
+            OriginError::NotFound(root)
+        } else if timed_out {
+            OriginError::Timeout(root)
+        } else {
+            OriginError::Failed {
+                cid: root,
+                message: errors.join(", "),
+            }
+        })
+    }
+}
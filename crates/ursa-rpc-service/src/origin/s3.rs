@@ -0,0 +1,119 @@
+use std::time::SystemTime;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use aws_sigv4::http_request::{
+    sign as sign_request, PayloadChecksumKind, PercentEncodingMode, SignableBody, SignableRequest,
+    SigningParams, SigningSettings, UriPathNormalizationMode,
+};
+use axum::http::{HeaderMap, Method, Uri};
+use libipld::Cid;
+use surf::{Client, RequestBuilder, Url};
+
+use super::{base_url, car_blocks, send, BlockStream, Origin};
+
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+/// An S3 compatible bucket holding car files named `<prefix><cid>.car`, addressed path style
+/// so that any endpoint works. Requests are signed with AWS signature v4 when credentials are
+/// set, public buckets don't need them.
+pub struct S3Origin {
+    client: Client,
+    endpoint: String,
+    bucket: String,
+    region: String,
+    prefix: String,
+    credentials: Option<S3Credentials>,
+}
+
+impl S3Origin {
+    pub fn new(
+        client: Client,
+        endpoint: &str,
+        bucket: &str,
+        region: &str,
+        prefix: &str,
+    ) -> Result<Self> {
+        Ok(Self {
+            client,
+            endpoint: base_url(endpoint)?,
+            bucket: bucket.to_string(),
+            region: region.to_string(),
+            prefix: prefix.to_string(),
+            credentials: None,
+        })
+    }
+
+    pub fn with_credentials(mut self, credentials: Option<S3Credentials>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+}
+
+#[async_trait]
+impl Origin for S3Origin {
+    fn name(&self) -> String {
+        format!("{}/{}", self.endpoint, self.bucket)
+    }
+
+    async fn fetch(&self, root: Cid, max_size: u64) -> Result<BlockStream> {
+        let key = encode_key(&format!("{}{root}.car", self.prefix));
+        let url = Url::parse(&format!("{}/{}/{key}", self.endpoint, self.bucket))?;
+        let mut req = RequestBuilder::new(surf::http::Method::Get, url.clone());
+        if let Some(credentials) = &self.credentials {
+            for (name, value) in sign(credentials, &self.region, &url, SystemTime::now())? {
+                req = req.header(name.as_str(), value);
+            }
+        }
+        let mut res = send(&self.client, req.build()).await?;
+        car_blocks(root, res.take_body(), max_size).await
+    }
+}
+
+/// Percent-encode an object key as S3 expects, keeping the `/` separators.
+fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+/// Headers signing a `GET` of `url` with AWS signature v4, as S3 expects it: the path is
+/// already percent-encoded and not normalized, and the payload isn't signed.
+pub(crate) fn sign(
+    credentials: &S3Credentials,
+    region: &str,
+    url: &Url,
+    time: SystemTime,
+) -> Result<Vec<(String, String)>> {
+    let mut settings = SigningSettings::default();
+    settings.percent_encoding_mode = PercentEncodingMode::Single;
+    settings.payload_checksum_kind = PayloadChecksumKind::XAmzSha256;
+    settings.uri_path_normalization_mode = UriPathNormalizationMode::Disabled;
+    let params = SigningParams::builder()
+        .access_key(&credentials.access_key_id)
+        .secret_key(&credentials.secret_access_key)
+        .region(region)
+        .service_name("s3")
+        .time(time)
+        .settings(settings)
+        .build()?;
+
+    let uri: Uri = url.as_str().parse()?;
+    let headers = HeaderMap::new();
+    let request = SignableRequest::new(&Method::GET, &uri, &headers, SignableBody::UnsignedPayload);
+    let (mut instructions, _) = sign_request(request, &params)?.into_parts();
+    Ok(instructions
+        .take_headers()
+        .into_iter()
+        .flatten()
+        .filter_map(|(name, value)| Some((name?.to_string(), value.to_str().ok()?.to_string())))
+        .collect())
+}
@@ -1,57 +1,305 @@
 use std::sync::Arc;
 
-use anyhow::Result;
 use axum::{
+    body::Bytes,
     http::StatusCode,
     response::{IntoResponse, Response},
     Extension, Json,
 };
-use jsonrpc_v2::{Data, Error, MapRouter, RequestObject, ResponseObject, ResponseObjects, Server};
+use ethers::core::types::{Bytes as EthBytes, H256, U256, U64};
+use jsonrpc_v2::{Data, Error, MapRouter, ResponseObject, ResponseObjects, Server};
+use serde_json::{json, Value};
 
 use self::routes::{eth, network};
-use crate::api::NetworkInterface;
+use crate::{
+    api::{
+        NetworkGetFileParams, NetworkGetParams, NetworkGetPeerInfoParams, NetworkGetPeerInfoResult,
+        NetworkGetRelayState, NetworkGetResult, NetworkInterface, NetworkOriginHealth,
+        NetworkPrefetchParams, NetworkPrefetchResult, NetworkPrefetchStatusParams,
+        NetworkPrefetchStatusResult, NetworkPutFileParams, NetworkPutFileResult, NetworkStoreStats,
+        ETH_BLOCK_NUMBER, ETH_CALL, ETH_CHAIN_ID, ETH_ESTIMATE_GAS, ETH_GAS_PRICE, ETH_GET_BALANCE,
+        ETH_GET_CODE, ETH_GET_LOGS, ETH_GET_TRANSACTION_COUNT, ETH_GET_TRANSACTION_RECEIPT,
+        ETH_SEND_RAW_TRANSACTION, ETH_SEND_TRANSACTION, NETWORK_GET, NETWORK_GET_FILE,
+        NETWORK_GET_PEERS, NETWORK_GET_PEER_INFO, NETWORK_GET_RELAY_STATE,
+        NETWORK_LISTENER_ADDRESSES, NETWORK_ORIGIN_HEALTH, NETWORK_PREFETCH,
+        NETWORK_PREFETCH_STATUS, NETWORK_PUT_FILE, NETWORK_STORE_STATS, RPC_DISCOVER,
+    },
+    auth::{method_scope, Access},
+    schema::{
+        address_params, fields, filter_params, hash_params, hex, logs, multiaddrs, peer_ids,
+        raw_transaction_params, receipt, schema, transaction_param, transaction_params, Method,
+    },
+};
 
 pub mod routes;
+pub mod ws;
+
+/// Standard JSON-RPC 2.0 error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Server error code of calls to a method outside the scopes of the request.
+const UNAUTHORIZED: i64 = -32001;
+/// Server error code of requests the node is too busy to take, they can be retried later.
+pub(crate) const LIMIT_EXCEEDED: i64 = -32005;
 
 #[derive(Clone)]
 pub struct RpcServer(Arc<Server<MapRouter>>);
 
-pub enum ServerErrors {
-    ApiError(Error),
+/// HTTP status of a failed single request, following the JSON-RPC over HTTP conventions.
+fn error_status(error: &Error) -> StatusCode {
+    let code = match error {
+        Error::Full { code, .. } | Error::Provided { code, .. } => *code,
+    };
+    match code {
+        PARSE_ERROR | INVALID_REQUEST | INVALID_PARAMS => StatusCode::BAD_REQUEST,
+        METHOD_NOT_FOUND => StatusCode::NOT_FOUND,
+        LIMIT_EXCEEDED => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
 }
-impl IntoResponse for ServerErrors {
-    fn into_response(self) -> Response {
-        let body = match self {
-            ServerErrors::ApiError(e) => Json(e),
-        };
-        (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+
+/// Error response to a request, or batch of requests, calling a method outside the scopes
+/// of `access`. A denied batch is rejected as a whole, with an error for each of its calls.
+/// Malformed requests are left to the server.
+pub(crate) fn check_access(access: &Access, body: &[u8]) -> Option<Value> {
+    let error = |call: &Value, message: String| {
+        json!({
+            "jsonrpc": "2.0",
+            "error": { "code": UNAUTHORIZED, "message": message },
+            "id": call.get("id").cloned().unwrap_or(Value::Null),
+        })
+    };
+    let denied = |call: &Value| {
+        let method = call.get("method")?.as_str()?;
+        let scope = method_scope(method);
+        (!access.allows(scope)).then(|| format!("{method} requires the {scope} scope"))
+    };
+
+    match serde_json::from_slice::<Value>(body).ok()? {
+        Value::Array(calls) => {
+            let reason = calls.iter().find_map(denied)?;
+            let errors = calls
+                .iter()
+                .map(|call| {
+                    let message =
+                        denied(call).unwrap_or_else(|| format!("Batch rejected, {reason}"));
+                    error(call, message)
+                })
+                .collect();
+            Some(Value::Array(errors))
+        }
+        call => denied(&call).map(|message| error(&call, message)),
     }
 }
 
+/// Handle a single request, a batch of requests or notifications.
+///
+/// Batches always answer with 200 and an error object per failed request, notifications
+/// (requests without an id) and batches made only of notifications answer with 204.
+/// A batch with a call outside the scopes of the request is rejected as a whole, with an
+/// error object per call.
 pub async fn rpc_handler(
     Extension(server): Extension<RpcServer>,
-    Json(req): Json<RequestObject>,
-) -> Result<Json<ResponseObjects>, ServerErrors> {
-    match server.0.handle(req).await {
-        ResponseObjects::One(r) => match r {
-            ResponseObject::Result {
-                jsonrpc,
-                result,
-                id,
-            } => Ok(Json(ResponseObjects::One(ResponseObject::Result {
-                jsonrpc,
-                result,
-                id,
-            }))),
-            ResponseObject::Error {
-                jsonrpc: _,
-                error,
-                id: _,
-            } => Err(ServerErrors::ApiError(error)),
-        },
-        ResponseObjects::Many(_) => todo!(),
-        ResponseObjects::Empty => todo!(),
+    Extension(access): Extension<Access>,
+    body: Bytes,
+) -> Response {
+    if let Some(error) = check_access(&access, &body) {
+        let status = if access.authenticated {
+            StatusCode::FORBIDDEN
+        } else {
+            StatusCode::UNAUTHORIZED
+        };
+        return (status, Json(error)).into_response();
     }
+
+    match server.0.handle(body).await {
+        ResponseObjects::One(response) => {
+            let status = match &response {
+                ResponseObject::Result { .. } => StatusCode::OK,
+                ResponseObject::Error { error, .. } => error_status(error),
+            };
+            (status, Json(ResponseObjects::One(response))).into_response()
+        }
+        ResponseObjects::Many(responses) => {
+            (StatusCode::OK, Json(ResponseObjects::Many(responses))).into_response()
+        }
+        ResponseObjects::Empty => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+/// Registers the methods with the server, and documents them in the OpenRPC document, from
+/// a single list so that every registered method is documented.
+macro_rules! rpc_methods {
+    ($(
+        $name:ident => $handler:expr, {
+            summary: $summary:literal,
+            by_position: $by_position:literal,
+            params: $params:expr,
+            result: $result:expr $(,)?
+        }
+    ),* $(,)?) => {
+        fn server<I: NetworkInterface>(interface: Arc<I>) -> Arc<Server<MapRouter>> {
+            Server::new()
+                .with_data(Data::new(interface))
+                $(.with_method($name, $handler))*
+                .with_method(RPC_DISCOVER, network::discover)
+                .finish()
+        }
+
+        /// The registered methods, `rpc.discover` aside.
+        pub(crate) fn methods() -> Vec<Method> {
+            vec![$(Method {
+                name: $name,
+                summary: $summary,
+                by_position: $by_position,
+                params: $params,
+                result: $result,
+            }),*]
+        }
+    };
+}
+
+rpc_methods! {
+    NETWORK_GET => network::get_cid_handler::<I>, {
+        summary: "Get a block, fetching it from the network if missing",
+        by_position: false,
+        params: fields::<NetworkGetParams>,
+        result: schema::<NetworkGetResult>,
+    },
+    NETWORK_GET_FILE => network::get_file_handler::<I>, {
+        summary: "Write the car file of a dag to the node's filesystem",
+        by_position: false,
+        params: fields::<NetworkGetFileParams>,
+        result: schema::<()>,
+    },
+    NETWORK_PUT_FILE => network::put_file_handler::<I>, {
+        summary: "Import a car file from the node's filesystem",
+        by_position: false,
+        params: fields::<NetworkPutFileParams>,
+        result: schema::<NetworkPutFileResult>,
+    },
+    NETWORK_GET_PEERS => network::get_peers::<I>, {
+        summary: "Peer ids of the connected peers",
+        by_position: false,
+        params: fields::<()>,
+        result: peer_ids,
+    },
+    NETWORK_GET_PEER_INFO => network::get_peer_info::<I>, {
+        summary: "Identify information of a connected peer",
+        by_position: false,
+        params: fields::<NetworkGetPeerInfoParams>,
+        result: schema::<NetworkGetPeerInfoResult>,
+    },
+    NETWORK_GET_RELAY_STATE => network::get_relay_state::<I>, {
+        summary: "Reservations on relays and hole punching statistics",
+        by_position: false,
+        params: fields::<()>,
+        result: schema::<NetworkGetRelayState>,
+    },
+    NETWORK_STORE_STATS => network::store_stats::<I>, {
+        summary: "Blocks and bytes held by the node",
+        by_position: false,
+        params: fields::<()>,
+        result: schema::<NetworkStoreStats>,
+    },
+    NETWORK_ORIGIN_HEALTH => network::origin_health::<I>, {
+        summary: "Failures and health of the origins, in the order they are tried",
+        by_position: false,
+        params: fields::<()>,
+        result: schema::<NetworkOriginHealth>,
+    },
+    NETWORK_PREFETCH => network::prefetch_handler::<I>, {
+        summary: "Start pulling dags into the node in the background, returns the job",
+        by_position: false,
+        params: fields::<NetworkPrefetchParams>,
+        result: schema::<NetworkPrefetchResult>,
+    },
+    NETWORK_PREFETCH_STATUS => network::prefetch_status_handler::<I>, {
+        summary: "Status and progress of a prefetch job",
+        by_position: false,
+        params: fields::<NetworkPrefetchStatusParams>,
+        result: schema::<NetworkPrefetchStatusResult>,
+    },
+    ETH_SEND_TRANSACTION => eth::eth_send_transaction::<I>, {
+        summary: "Submit an unsigned transaction without a sender, run from the zero address, returns its hash",
+        by_position: true,
+        params: transaction_param,
+        result: hex::<H256>,
+    },
+    ETH_SEND_RAW_TRANSACTION => eth::eth_send_raw_transaction::<I>, {
+        summary: "Submit a signed RLP encoded transaction to consensus, returns its hash",
+        by_position: true,
+        params: raw_transaction_params,
+        result: hex::<H256>,
+    },
+    ETH_CALL => eth::eth_call::<I>, {
+        summary: "Execute a call against the application state",
+        by_position: true,
+        params: transaction_params,
+        result: hex::<EthBytes>,
+    },
+    ETH_ESTIMATE_GAS => eth::eth_estimate_gas::<I>, {
+        summary: "Gas used by a transaction executed against the application state",
+        by_position: true,
+        params: transaction_params,
+        result: hex::<U256>,
+    },
+    ETH_GET_TRANSACTION_RECEIPT => eth::eth_get_transaction_receipt::<I>, {
+        summary: "Receipt of an executed transaction, null if unknown",
+        by_position: true,
+        params: hash_params,
+        result: receipt,
+    },
+    ETH_GET_BALANCE => eth::eth_get_balance::<I>, {
+        summary: "Balance of an account",
+        by_position: true,
+        params: address_params,
+        result: hex::<U256>,
+    },
+    ETH_GET_CODE => eth::eth_get_code::<I>, {
+        summary: "Code of a contract",
+        by_position: true,
+        params: address_params,
+        result: hex::<EthBytes>,
+    },
+    ETH_GET_TRANSACTION_COUNT => eth::eth_get_transaction_count::<I>, {
+        summary: "Nonce of an account",
+        by_position: true,
+        params: address_params,
+        result: hex::<U256>,
+    },
+    ETH_GET_LOGS => eth::eth_get_logs::<I>, {
+        summary: "Logs of the executed transactions matching a filter",
+        by_position: true,
+        params: filter_params,
+        result: logs,
+    },
+    ETH_BLOCK_NUMBER => eth::eth_block_number::<I>, {
+        summary: "Height of the latest committed block",
+        by_position: true,
+        params: fields::<()>,
+        result: hex::<U64>,
+    },
+    ETH_CHAIN_ID => eth::eth_chain_id::<I>, {
+        summary: "Chain id of the application",
+        by_position: true,
+        params: fields::<()>,
+        result: hex::<U64>,
+    },
+    ETH_GAS_PRICE => eth::eth_gas_price, {
+        summary: "Gas price, always zero",
+        by_position: true,
+        params: fields::<()>,
+        result: hex::<U256>,
+    },
+    NETWORK_LISTENER_ADDRESSES => network::get_listener_addresses::<I>, {
+        summary: "Addresses the node is listening on",
+        by_position: false,
+        params: fields::<()>,
+        result: multiaddrs,
+    },
 }
 
 impl RpcServer {
@@ -59,19 +307,6 @@ impl RpcServer {
     where
         I: NetworkInterface,
     {
-        let server = Server::new()
-            .with_data(Data::new(interface))
-            .with_method("ursa_get_cid", network::get_cid_handler::<I>)
-            .with_method("ursa_get_file", network::get_file_handler::<I>)
-            .with_method("ursa_put_file", network::put_file_handler::<I>)
-            .with_method("ursa_get_peers", network::get_peers::<I>)
-            .with_method("eth_sendTransaction", eth::eth_send_raw_transaction::<I>)
-            .with_method("eth_call", eth::eth_call::<I>)
-            .with_method(
-                "ursa_listener_addresses",
-                network::get_listener_addresses::<I>,
-            );
-
-        RpcServer(server.finish())
+        RpcServer(server(interface))
     }
 }
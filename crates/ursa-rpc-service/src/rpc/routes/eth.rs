@@ -1,49 +1,267 @@
-use bytes::Bytes;
+use ethers::types::{Address, Bytes, Filter, Log, H256, U256, U64};
 use jsonrpc_v2::{Data, Error, Params};
 use narwhal_types::TransactionProto;
+use revm::primitives::{ExecutionResult, Output};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
 use std::sync::Arc;
-use ursa_application::types::Query;
+use ursa_application::types::{decode_transaction, Query, QueryResponse};
 use ursa_consensus::AbciQueryQuery;
 
-use crate::api::{EthSendTransactionParams, NetworkInterface};
+use crate::api::{
+    EthCall, EthGetTransactionReceipt, EthSendTransactionParams, EthSendTransactionResult,
+    NetworkInterface,
+};
 use crate::routes::network::Result;
 
-pub async fn eth_send_raw_transaction<I>(
-    data: Data<Arc<I>>,
-    Params(params): Params<EthSendTransactionParams>,
-) -> Result<()>
+/// Error code of reverted calls, as used by geth.
+const EXECUTION_REVERTED: i64 = 3;
+/// Error code of calls halted by the evm.
+const EXECUTION_ERROR: i64 = -32000;
+
+/// The positional param at `index`, missing params are `null` so that trailing
+/// optional params can be left out.
+fn param<T: DeserializeOwned>(params: &[Value], index: usize) -> Result<T> {
+    serde_json::from_value(params.get(index).cloned().unwrap_or(Value::Null))
+        .map_err(|_| Error::INVALID_PARAMS)
+}
+
+/// The transaction of `[transaction, block]` params, or of a bare transaction object.
+fn transaction(params: Value) -> Result<EthSendTransactionParams> {
+    match params {
+        Value::Array(params) => param(&params, 0),
+        params => serde_json::from_value(params).map_err(|_| Error::INVALID_PARAMS),
+    }
+}
+
+async fn query<I>(interface: &I, query: Query) -> Result<QueryResponse>
+where
+    I: NetworkInterface,
+{
+    let query = serde_json::to_string(&query).map_err(Error::internal)?;
+    let abci_query = AbciQueryQuery {
+        data: query,
+        path: "".to_string(),
+        height: None,
+        prove: None,
+    };
+
+    let res = interface
+        .query_abci(abci_query)
+        .await
+        .map_err(Error::internal)?;
+    // failed queries are answered with an error message
+    serde_json::from_slice(&res.value)
+        .map_err(|_| Error::internal(String::from_utf8_lossy(&res.value)))
+}
+
+fn unexpected(res: QueryResponse) -> Error {
+    Error::internal(format!("unexpected query response {res:?}"))
+}
+
+fn rejected(message: impl ToString) -> Error {
+    Error::Full {
+        code: EXECUTION_ERROR,
+        message: message.to_string(),
+        data: None,
+    }
+}
+
+/// Submit the transaction bytes to consensus, rejecting the transactions the application
+/// can't decode, and those reusing a committed nonce of their sender, before they reach
+/// consensus. Returns the hash the application derives.
+///
+/// Transactions of a sender still pending in consensus aren't known here, so two submits
+/// with the same nonce both reach consensus, and the application executes only the first.
+async fn submit<I>(interface: &I, transaction: Vec<u8>) -> Result<EthSendTransactionResult>
 where
     I: NetworkInterface,
 {
-    //Todo(dalton) to support actually ethereum RPC we should return the transaction hash here. Keccack256(txn)
+    let (hash, decoded) = decode_transaction(&transaction).map_err(rejected)?;
+    if let Some(from) = decoded.from {
+        let nonce = decoded.nonce.ok_or_else(|| rejected("missing nonce"))?;
+        match query(interface, Query::Nonce(from)).await? {
+            QueryResponse::Nonce(next) if nonce < U256::from(next) => {
+                return Err(rejected(format!("nonce too low: next nonce is {next}")))
+            }
+            QueryResponse::Nonce(_) => {}
+            res => return Err(unexpected(res)),
+        }
+    }
     let request = TransactionProto {
-        transaction: Bytes::from(serde_json::to_vec(&params).map_err(Error::internal)?),
+        transaction: transaction.into(),
     };
-    data.0
+    interface
         .submit_narwhal_txn(request)
         .await
-        .map_err(Error::internal)
+        .map_err(Error::internal)?;
+    Ok(hash)
+}
+
+/// Execute `params` without committing, failing if the execution doesn't succeed.
+async fn execute<I>(interface: &I, params: Value) -> Result<ExecutionResult>
+where
+    I: NetworkInterface,
+{
+    match query(interface, Query::Execute(transaction(params)?)).await? {
+        QueryResponse::Tx(result @ ExecutionResult::Success { .. }) => Ok(result),
+        QueryResponse::Tx(ExecutionResult::Revert { output, .. }) => Err(Error::Full {
+            code: EXECUTION_REVERTED,
+            message: format!("execution reverted: {}", Bytes::from(output)),
+            data: None,
+        }),
+        QueryResponse::Tx(ExecutionResult::Halt { reason, .. }) => Err(Error::Full {
+            code: EXECUTION_ERROR,
+            message: format!("execution halted: {reason:?}"),
+            data: None,
+        }),
+        res => Err(unexpected(res)),
+    }
 }
 
-pub async fn eth_call<I>(
+pub async fn eth_send_transaction<I>(
     data: Data<Arc<I>>,
-    Params(params): Params<EthSendTransactionParams>,
-) -> Result<Vec<u8>>
+    Params(params): Params<Value>,
+) -> Result<EthSendTransactionResult>
 where
     I: NetworkInterface,
 {
-    let query = Query::EthCall(params);
-    let query = serde_json::to_string(&query).map_err(Error::internal)?;
+    // unsigned transactions have no sender, the application rejects them otherwise
+    let transaction = transaction(params)?;
+    if transaction.from.is_some() {
+        return Err(rejected(
+            "unsigned transactions can't have a sender, use eth_sendRawTransaction",
+        ));
+    }
+    let transaction = serde_json::to_vec(&transaction).map_err(Error::internal)?;
+    submit(data.0.as_ref(), transaction).await
+}
 
-    let abci_query = AbciQueryQuery {
-        data: query,
-        path: "".to_string(),
-        height: None,
-        prove: None,
-    };
+pub async fn eth_send_raw_transaction<I>(
+    data: Data<Arc<I>>,
+    Params(params): Params<Vec<Value>>,
+) -> Result<EthSendTransactionResult>
+where
+    I: NetworkInterface,
+{
+    let transaction: Bytes = param(&params, 0)?;
+    submit(data.0.as_ref(), transaction.to_vec()).await
+}
+
+pub async fn eth_call<I>(data: Data<Arc<I>>, Params(params): Params<Value>) -> Result<EthCall>
+where
+    I: NetworkInterface,
+{
+    match execute(data.0.as_ref(), params).await? {
+        ExecutionResult::Success {
+            output: Output::Call(bytes) | Output::Create(bytes, _),
+            ..
+        } => Ok(bytes.into()),
+        res => Err(Error::internal(format!(
+            "unexpected execution result {res:?}"
+        ))),
+    }
+}
+
+pub async fn eth_estimate_gas<I>(data: Data<Arc<I>>, Params(params): Params<Value>) -> Result<U256>
+where
+    I: NetworkInterface,
+{
+    let result = execute(data.0.as_ref(), params).await?;
+    Ok(result.gas_used().into())
+}
 
-    match data.0.query_abci(abci_query).await {
-        Err(e) => Err(Error::internal(e)),
-        Ok(res) => Ok(res.value),
+pub async fn eth_get_transaction_receipt<I>(
+    data: Data<Arc<I>>,
+    Params(params): Params<Vec<Value>>,
+) -> Result<EthGetTransactionReceipt>
+where
+    I: NetworkInterface,
+{
+    let hash: H256 = param(&params, 0)?;
+    match query(data.0.as_ref(), Query::Receipt(hash)).await? {
+        QueryResponse::Receipt(receipt) => Ok(receipt),
+        res => Err(unexpected(res)),
     }
 }
+
+pub async fn eth_get_balance<I>(
+    data: Data<Arc<I>>,
+    Params(params): Params<Vec<Value>>,
+) -> Result<U256>
+where
+    I: NetworkInterface,
+{
+    let address: Address = param(&params, 0)?;
+    match query(data.0.as_ref(), Query::Balance(address)).await? {
+        QueryResponse::Balance(balance) => Ok(U256::from_big_endian(&balance.to_be_bytes::<32>())),
+        res => Err(unexpected(res)),
+    }
+}
+
+pub async fn eth_get_code<I>(
+    data: Data<Arc<I>>,
+    Params(params): Params<Vec<Value>>,
+) -> Result<Bytes>
+where
+    I: NetworkInterface,
+{
+    let address: Address = param(&params, 0)?;
+    match query(data.0.as_ref(), Query::Code(address)).await? {
+        QueryResponse::Code(code) => Ok(code),
+        res => Err(unexpected(res)),
+    }
+}
+
+pub async fn eth_get_transaction_count<I>(
+    data: Data<Arc<I>>,
+    Params(params): Params<Vec<Value>>,
+) -> Result<U256>
+where
+    I: NetworkInterface,
+{
+    let address: Address = param(&params, 0)?;
+    match query(data.0.as_ref(), Query::Nonce(address)).await? {
+        QueryResponse::Nonce(nonce) => Ok(nonce.into()),
+        res => Err(unexpected(res)),
+    }
+}
+
+pub async fn eth_get_logs<I>(
+    data: Data<Arc<I>>,
+    Params(params): Params<Vec<Value>>,
+) -> Result<Vec<Log>>
+where
+    I: NetworkInterface,
+{
+    let filter: Filter = param(&params, 0)?;
+    match query(data.0.as_ref(), Query::Logs(filter)).await? {
+        QueryResponse::Logs(logs) => Ok(logs),
+        res => Err(unexpected(res)),
+    }
+}
+
+pub async fn eth_block_number<I>(data: Data<Arc<I>>) -> Result<U64>
+where
+    I: NetworkInterface,
+{
+    match query(data.0.as_ref(), Query::BlockNumber).await? {
+        QueryResponse::BlockNumber(height) => Ok(U64::from(height.max(0) as u64)),
+        res => Err(unexpected(res)),
+    }
+}
+
+pub async fn eth_chain_id<I>(data: Data<Arc<I>>) -> Result<U64>
+where
+    I: NetworkInterface,
+{
+    match query(data.0.as_ref(), Query::ChainId).await? {
+        QueryResponse::ChainId(chain_id) => Ok(chain_id.into()),
+        res => Err(unexpected(res)),
+    }
+}
+
+/// Transactions aren't charged for gas yet.
+pub async fn eth_gas_price() -> Result<U256> {
+    Ok(U256::zero())
+}
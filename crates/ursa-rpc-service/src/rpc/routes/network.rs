@@ -1,9 +1,11 @@
 use axum::{
     middleware,
-    routing::{post, put},
-    Router,
+    routing::{get, post, put},
+    Json, Router,
 };
 use libipld::Cid;
+use libp2p::PeerId;
+use serde_json::Value;
 use std::{str::FromStr, sync::Arc};
 use ursa_metrics::middleware::track_metrics;
 
@@ -11,22 +13,35 @@ use jsonrpc_v2::{Data, Error, Params};
 
 use crate::{
     api::{
-        NetworkGetFileParams, NetworkGetListenerAddresses, NetworkGetParams, NetworkGetPeers,
-        NetworkGetResult, NetworkInterface, NetworkPutFileParams, NetworkPutFileResult,
+        NetworkGetFileParams, NetworkGetListenerAddresses, NetworkGetParams,
+        NetworkGetPeerInfoParams, NetworkGetPeerInfoResult, NetworkGetPeers, NetworkGetRelayState,
+        NetworkGetResult, NetworkInterface, NetworkOriginHealth, NetworkPrefetchParams,
+        NetworkPrefetchResult, NetworkPrefetchStatusParams, NetworkPrefetchStatusResult,
+        NetworkPutFileParams, NetworkPutFileResult, NetworkStoreStats, PrefetchError,
     },
-    rpc::rpc_handler,
+    rpc::{rpc_handler, ws::ws_handler, LIMIT_EXCEEDED},
+    schema,
 };
 use tracing::error;
 
 pub type Result<T> = anyhow::Result<T, Error>;
 
-pub fn init() -> Router {
+pub fn init<I>() -> Router
+where
+    I: NetworkInterface,
+{
     Router::new()
         .route("/rpc/v0", put(rpc_handler))
         .route("/rpc/v0", post(rpc_handler))
+        .route("/rpc/v0/ws", get(ws_handler::<I>))
+        .route("/rpc/v0/schema", get(|| async { Json(schema::openrpc()) }))
         .route_layer(middleware::from_fn(track_metrics))
 }
 
+pub async fn discover() -> Result<Value> {
+    Ok(schema::openrpc())
+}
+
 pub async fn get_cid_handler<I>(
     data: Data<Arc<I>>,
     Params(params): Params<NetworkGetParams>,
@@ -53,7 +68,7 @@ where
 {
     let path = params.path;
     if let Ok(cid) = Cid::from_str(&params.cid) {
-        match data.0.get_file(path, cid).await {
+        match data.0.get_file(path, cid, params.selection).await {
             Err(err) => {
                 error!("{:?}", err);
                 Err(Error::internal(err))
@@ -84,6 +99,57 @@ where
     }
 }
 
+pub async fn prefetch_handler<I>(
+    data: Data<Arc<I>>,
+    Params(params): Params<NetworkPrefetchParams>,
+) -> Result<NetworkPrefetchResult>
+where
+    I: NetworkInterface,
+{
+    let mut roots = Vec::with_capacity(params.cids.len());
+    for cid in &params.cids {
+        match Cid::from_str(cid) {
+            Ok(cid) => roots.push(cid),
+            Err(_) => {
+                error!("Invalid Cid String, Cannot Parse {} to CID", cid);
+                return Err(Error::INVALID_PARAMS);
+            }
+        }
+    }
+
+    match data.0.prefetch(roots, params.replicate).await {
+        Err(err) => {
+            error!("{:?}", err);
+            Err(match err.downcast_ref::<PrefetchError>() {
+                Some(PrefetchError::InvalidRoots) => Error::INVALID_PARAMS,
+                Some(PrefetchError::TooManyJobs) => Error::Full {
+                    code: LIMIT_EXCEEDED,
+                    message: err.to_string(),
+                    data: None,
+                },
+                None => Error::internal(err),
+            })
+        }
+        Ok(res) => Ok(res),
+    }
+}
+
+pub async fn prefetch_status_handler<I>(
+    data: Data<Arc<I>>,
+    Params(params): Params<NetworkPrefetchStatusParams>,
+) -> Result<NetworkPrefetchStatusResult>
+where
+    I: NetworkInterface,
+{
+    match data.0.get_prefetch(params.id).await {
+        Err(err) => {
+            error!("{:?}", err);
+            Err(Error::internal(err))
+        }
+        Ok(res) => Ok(res),
+    }
+}
+
 pub async fn get_peers<I>(data: Data<Arc<I>>) -> Result<NetworkGetPeers>
 where
     I: NetworkInterface,
@@ -97,6 +163,69 @@ where
     }
 }
 
+pub async fn get_peer_info<I>(
+    data: Data<Arc<I>>,
+    Params(params): Params<NetworkGetPeerInfoParams>,
+) -> Result<NetworkGetPeerInfoResult>
+where
+    I: NetworkInterface,
+{
+    if let Ok(peer_id) = PeerId::from_str(&params.peer_id) {
+        match data.0.get_peer_info(peer_id).await {
+            Err(err) => {
+                error!("{:?}", err);
+                Err(Error::internal(err))
+            }
+            Ok(res) => Ok(res),
+        }
+    } else {
+        error!(
+            "Invalid PeerId String, Cannot Parse {} to PeerId",
+            &params.peer_id
+        );
+        Err(Error::INVALID_PARAMS)
+    }
+}
+
+pub async fn get_relay_state<I>(data: Data<Arc<I>>) -> Result<NetworkGetRelayState>
+where
+    I: NetworkInterface,
+{
+    match data.0.get_relay_state().await {
+        Err(err) => {
+            error!("{:?}", err);
+            Err(Error::internal(err))
+        }
+        Ok(res) => Ok(res),
+    }
+}
+
+pub async fn store_stats<I>(data: Data<Arc<I>>) -> Result<NetworkStoreStats>
+where
+    I: NetworkInterface,
+{
+    match data.0.store_stats().await {
+        Err(err) => {
+            error!("{:?}", err);
+            Err(Error::internal(err))
+        }
+        Ok(res) => Ok(res),
+    }
+}
+
+pub async fn origin_health<I>(data: Data<Arc<I>>) -> Result<NetworkOriginHealth>
+where
+    I: NetworkInterface,
+{
+    match data.0.origin_health().await {
+        Err(err) => {
+            error!("{:?}", err);
+            Err(Error::internal(err))
+        }
+        Ok(res) => Ok(res),
+    }
+}
+
 pub async fn get_listener_addresses<I>(data: Data<Arc<I>>) -> Result<NetworkGetListenerAddresses>
 where
     I: NetworkInterface,
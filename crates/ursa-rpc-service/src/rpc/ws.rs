@@ -0,0 +1,297 @@
+//! # JSON-RPC over WebSocket.
+//!
+//! Every method of the http endpoint is available on `/rpc/v0/ws`, plus subscriptions that
+//! push notifications to the client until it unsubscribes or disconnects:
+//!
+//! - `ursa_subscribeNetworkEvents`: peer connections, completed pulls and gossipsub messages.
+//! - `ursa_subscribeNewBlocks`: the height of every block committed by consensus.
+//!
+//! Subscribing returns the subscription id, notifications are sent as
+//! `{"method": "ursa_subscription", "params": {"subscription": <id>, "result": <event>}}`.
+//! Subscription methods can't be part of a batch.
+//!
+//! Calls run concurrently, a few at a time. Messages to the client are queued up to
+//! [`OUTBOUND_QUEUE`], a client that falls further behind is disconnected.
+
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::Bytes,
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    response::Response,
+    Extension,
+};
+use futures::{SinkExt, StreamExt};
+use jsonrpc_v2::ResponseObjects;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    select,
+    sync::{
+        broadcast::{self, error::RecvError},
+        mpsc::{channel, error::TrySendError, Receiver, Sender},
+        Notify, Semaphore,
+    },
+    task::{self, JoinHandle},
+};
+use tracing::{error, warn};
+
+use super::{check_access, RpcServer, INVALID_PARAMS};
+use crate::{
+    api::{
+        NetworkEventNotification, NetworkInterface, NewBlockNotification, SUBSCRIBE_NETWORK_EVENTS,
+        SUBSCRIBE_NEW_BLOCKS, SUBSCRIPTION, UNSUBSCRIBE,
+    },
+    auth::Access,
+};
+
+/// Number of messages queued for a client before it is considered too slow.
+pub const OUTBOUND_QUEUE: usize = 256;
+/// Number of calls of a connection that run at the same time.
+const MAX_CONCURRENT_CALLS: usize = 16;
+
+pub async fn ws_handler<I>(
+    ws: WebSocketUpgrade,
+    Extension(server): Extension<RpcServer>,
+    Extension(interface): Extension<Arc<I>>,
+    Extension(access): Extension<Access>,
+) -> Response
+where
+    I: NetworkInterface,
+{
+    ws.on_upgrade(move |socket| serve(socket, server, interface, access))
+}
+
+async fn serve<I>(socket: WebSocket, server: RpcServer, interface: Arc<I>, access: Access)
+where
+    I: NetworkInterface,
+{
+    let (mut sink, mut stream) = socket.split();
+    let (out, mut out_receiver) = Outbox::new(OUTBOUND_QUEUE);
+    let overflow = out.clone();
+    let mut connection = Connection::new(server, interface, access, out);
+
+    loop {
+        select! {
+            message = stream.next() => match message {
+                Some(Ok(Message::Text(text))) => connection.handle(Bytes::from(text)).await,
+                Some(Ok(Message::Binary(data))) => connection.handle(Bytes::from(data)).await,
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // pings are answered by axum
+                Some(Ok(_)) => {}
+            },
+            Some(text) = out_receiver.recv() => {
+                if sink.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            _ = overflow.overflowed() => {
+                warn!("Closing websocket connection, the client doesn't keep up with its messages");
+                break;
+            }
+        }
+    }
+}
+
+/// Bounded queue of the messages to a client.
+#[derive(Clone)]
+pub(crate) struct Outbox {
+    sender: Sender<String>,
+    overflow: Arc<Notify>,
+}
+
+impl Outbox {
+    pub(crate) fn new(capacity: usize) -> (Self, Receiver<String>) {
+        let (sender, receiver) = channel(capacity);
+        let outbox = Self {
+            sender,
+            overflow: Arc::new(Notify::new()),
+        };
+        (outbox, receiver)
+    }
+
+    /// Queue a message, returns false if the queue is full or the connection is gone.
+    fn send(&self, message: String) -> bool {
+        match self.sender.try_send(message) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.overflow.notify_one();
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Resolves once a message was dropped because the queue was full.
+    async fn overflowed(&self) {
+        self.overflow.notified().await
+    }
+}
+
+#[derive(Deserialize)]
+struct Call {
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+/// State of a single websocket connection. Responses and notifications are sent to `out`.
+pub(crate) struct Connection<I> {
+    server: RpcServer,
+    interface: Arc<I>,
+    /// Scopes granted when the connection was opened.
+    access: Access,
+    out: Outbox,
+    /// Permits of the calls in flight.
+    calls: Arc<Semaphore>,
+    subscriptions: HashMap<u64, JoinHandle<()>>,
+    next_id: u64,
+}
+
+impl<I> Connection<I>
+where
+    I: NetworkInterface,
+{
+    pub(crate) fn new(server: RpcServer, interface: Arc<I>, access: Access, out: Outbox) -> Self {
+        Self {
+            server,
+            interface,
+            access,
+            out,
+            calls: Arc::new(Semaphore::new(MAX_CONCURRENT_CALLS)),
+            subscriptions: HashMap::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Handle a message from the client. Subscription methods are handled here, everything
+    /// else is spawned on the rpc server so a slow call doesn't hold up the connection.
+    pub(crate) async fn handle(&mut self, body: Bytes) {
+        if let Some(error) = check_access(&self.access, &body) {
+            self.out.send(error.to_string());
+            return;
+        }
+
+        let call = serde_json::from_slice::<Call>(&body).ok().filter(|call| {
+            matches!(
+                call.method.as_str(),
+                SUBSCRIBE_NETWORK_EVENTS | SUBSCRIBE_NEW_BLOCKS | UNSUBSCRIBE
+            )
+        });
+        if let Some(call) = call {
+            if let Some(response) = self.handle_subscription(call) {
+                self.out.send(response.to_string());
+            }
+            return;
+        }
+
+        // waiting for a permit stops reading from a client that has too many calls in flight
+        let permit = match Arc::clone(&self.calls).acquire_owned().await {
+            Ok(permit) => permit,
+            Err(_) => return,
+        };
+        let (server, out) = (self.server.clone(), self.out.clone());
+        task::spawn(async move {
+            let _permit = permit;
+            let response = match server.0.handle(body).await {
+                ResponseObjects::Empty => return,
+                responses => match serde_json::to_string(&responses) {
+                    Ok(response) => response,
+                    Err(e) => {
+                        error!("Failed to serialize rpc response: {e}");
+                        return;
+                    }
+                },
+            };
+            out.send(response);
+        });
+    }
+
+    /// Returns `None` for notifications, which get no response.
+    fn handle_subscription(&mut self, call: Call) -> Option<Value> {
+        let result = match call.method.as_str() {
+            SUBSCRIBE_NETWORK_EVENTS => {
+                let receiver = self.interface.subscribe_network_events();
+                json!(self.subscribe(receiver, |event| {
+                    NetworkEventNotification::from_event(&event)
+                }))
+            }
+            SUBSCRIBE_NEW_BLOCKS => {
+                let receiver = self.interface.subscribe_new_blocks();
+                json!(self.subscribe(receiver, |height| Some(NewBlockNotification { height })))
+            }
+            _ => match serde_json::from_value::<(u64,)>(call.params) {
+                Ok((id,)) => json!(self.unsubscribe(id)),
+                Err(_) => {
+                    return call.id.map(|id| {
+                        json!({
+                            "jsonrpc": "2.0",
+                            "error": { "code": INVALID_PARAMS, "message": "Invalid params" },
+                            "id": id,
+                        })
+                    })
+                }
+            },
+        };
+        call.id
+            .map(|id| json!({ "jsonrpc": "2.0", "result": result, "id": id }))
+    }
+
+    /// Forward the events of `receiver` mapped by `notification` to the client.
+    fn subscribe<T, N, F>(&mut self, mut receiver: broadcast::Receiver<T>, notification: F) -> u64
+    where
+        T: Clone + Send + 'static,
+        N: Serialize,
+        F: Fn(T) -> Option<N> + Send + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let out = self.out.clone();
+        let task = task::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        if let Some(result) = notification(event) {
+                            let message = json!({
+                                "jsonrpc": "2.0",
+                                "method": SUBSCRIPTION,
+                                "params": { "subscription": id, "result": result },
+                            });
+                            if !out.send(message.to_string()) {
+                                break;
+                            }
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("Subscription {id} is too slow, skipped {skipped} events")
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+        self.subscriptions.insert(id, task);
+        id
+    }
+
+    /// Returns whether the subscription existed.
+    fn unsubscribe(&mut self, id: u64) -> bool {
+        match self.subscriptions.remove(&id) {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<I> Drop for Connection<I> {
+    fn drop(&mut self) {
+        for task in self.subscriptions.values() {
+            task.abort();
+        }
+    }
+}
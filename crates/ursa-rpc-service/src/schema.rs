@@ -0,0 +1,529 @@
+//! # API schemas.
+//!
+//! The OpenRPC document of the JSON-RPC methods, served at `/rpc/v0/schema` and by
+//! `rpc.discover`, and the OpenAPI document of the http routes, served at `/openapi.json`.
+//! The methods are documented where their handlers are registered, and the param and result
+//! schemas are derived from the types used by the handlers, the ethers types from how they
+//! serialize, so the documents follow the api as it changes.
+
+use std::collections::HashSet;
+
+use ethers::core::types::{
+    Address, BlockNumber, Bytes, Filter, Log, TransactionReceipt, TransactionRequest, H256, U256,
+    U64,
+};
+use libp2p::{Multiaddr, PeerId};
+use schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::{InstanceType, ObjectValidation, Schema, SchemaObject},
+    JsonSchema,
+};
+use serde::Serialize;
+use serde_json::{json, Value};
+use ursa_network::BitswapProgress;
+use ursa_store::DagSelection;
+
+use crate::{
+    api::{NetworkPrefetchParams, PrefetchJob},
+    auth::{method_scope, Scope},
+    rpc,
+};
+
+pub const OPENRPC_VERSION: &str = "1.2.6";
+pub const OPENAPI_VERSION: &str = "3.0.3";
+
+/// Documentation of a method, declared next to its handler in `rpc_methods!`.
+pub(crate) struct Method {
+    pub(crate) name: &'static str,
+    pub(crate) summary: &'static str,
+    /// Whether params are an array, as in the Ethereum api, rather than an object.
+    pub(crate) by_position: bool,
+    pub(crate) params: fn(&mut SchemaGenerator) -> Vec<Field>,
+    pub(crate) result: fn(&mut SchemaGenerator) -> Schema,
+}
+
+/// A field of a params or query struct.
+pub(crate) struct Field {
+    name: String,
+    required: bool,
+    schema: Schema,
+}
+
+/// The fields of `P`, empty unless `P` is a struct.
+pub(crate) fn fields<P: JsonSchema>(gen: &mut SchemaGenerator) -> Vec<Field> {
+    let object = match P::json_schema(gen) {
+        Schema::Object(SchemaObject {
+            object: Some(object),
+            ..
+        }) => object,
+        _ => return Vec::new(),
+    };
+    let ObjectValidation {
+        properties,
+        required,
+        ..
+    } = *object;
+    properties
+        .into_iter()
+        .map(|(name, schema)| Field {
+            required: required.contains(&name),
+            name,
+            schema,
+        })
+        .collect()
+}
+
+pub(crate) fn schema<R: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<R>()
+}
+
+/// Schema of a type without a `JsonSchema` impl, derived from how `example` serializes.
+/// The example is part of the schema, so it must not change between documents.
+fn example<T: Serialize>(gen: &mut SchemaGenerator, example: &T) -> Schema {
+    gen.root_schema_for_value(example)
+        .map(|root| Schema::Object(root.schema))
+        .unwrap_or(Schema::Bool(true))
+}
+
+/// Schema of the ethers quantities, hashes and bytes, all hex strings.
+pub(crate) fn hex<T: Default + Serialize>(gen: &mut SchemaGenerator) -> Schema {
+    example(gen, &T::default())
+}
+
+fn param<T: JsonSchema>(gen: &mut SchemaGenerator, name: &str, required: bool) -> Field {
+    Field {
+        name: name.to_string(),
+        required,
+        schema: gen.subschema_for::<T>(),
+    }
+}
+
+fn example_param<T: Serialize>(
+    gen: &mut SchemaGenerator,
+    name: &str,
+    required: bool,
+    value: &T,
+) -> Field {
+    Field {
+        name: name.to_string(),
+        required,
+        schema: example(gen, value),
+    }
+}
+
+/// A transaction with every optional field set, so each one is documented.
+fn transaction_request() -> TransactionRequest {
+    TransactionRequest::new()
+        .from(Address::zero())
+        .to(Address::zero())
+        .gas(U256::zero())
+        .gas_price(U256::zero())
+        .value(U256::zero())
+        .data(Bytes::default())
+        .nonce(U256::zero())
+}
+
+fn log() -> Log {
+    Log {
+        block_hash: Some(H256::zero()),
+        block_number: Some(U64::zero()),
+        transaction_hash: Some(H256::zero()),
+        transaction_index: Some(U64::zero()),
+        log_index: Some(U256::zero()),
+        transaction_log_index: Some(U256::zero()),
+        log_type: Some(String::new()),
+        removed: Some(false),
+        ..Default::default()
+    }
+}
+
+/// Params of the Ethereum methods taking a transaction.
+pub(crate) fn transaction_param(gen: &mut SchemaGenerator) -> Vec<Field> {
+    vec![example_param(
+        gen,
+        "transaction",
+        true,
+        &transaction_request(),
+    )]
+}
+
+/// Params of the Ethereum methods taking a transaction and a block.
+pub(crate) fn transaction_params(gen: &mut SchemaGenerator) -> Vec<Field> {
+    let mut params = transaction_param(gen);
+    params.push(param::<String>(gen, "block", false));
+    params
+}
+
+/// Params of the Ethereum methods taking an address and a block.
+pub(crate) fn address_params(gen: &mut SchemaGenerator) -> Vec<Field> {
+    vec![
+        example_param(gen, "address", true, &Address::zero()),
+        param::<String>(gen, "block", false),
+    ]
+}
+
+pub(crate) fn raw_transaction_params(gen: &mut SchemaGenerator) -> Vec<Field> {
+    vec![example_param(gen, "transaction", true, &Bytes::default())]
+}
+
+pub(crate) fn hash_params(gen: &mut SchemaGenerator) -> Vec<Field> {
+    vec![example_param(gen, "hash", true, &H256::zero())]
+}
+
+pub(crate) fn filter_params(gen: &mut SchemaGenerator) -> Vec<Field> {
+    let filter = Filter::new()
+        .from_block(BlockNumber::Earliest)
+        .to_block(BlockNumber::Latest)
+        .address(Address::zero())
+        .topic0(H256::zero());
+    vec![example_param(gen, "filter", true, &filter)]
+}
+
+/// `eth_getTransactionReceipt`, null for unknown transactions.
+pub(crate) fn receipt(gen: &mut SchemaGenerator) -> Schema {
+    let receipt = TransactionReceipt {
+        root: Some(H256::zero()),
+        transaction_type: Some(U64::zero()),
+        effective_gas_price: Some(U256::zero()),
+        block_hash: Some(H256::zero()),
+        block_number: Some(U64::zero()),
+        to: Some(Address::zero()),
+        gas_used: Some(U256::zero()),
+        contract_address: Some(Address::zero()),
+        status: Some(U64::one()),
+        logs: vec![log()],
+        ..Default::default()
+    };
+    let mut schema = example(gen, &receipt).into_object();
+    schema.instance_type = Some(vec![InstanceType::Object, InstanceType::Null].into());
+    Schema::Object(schema)
+}
+
+pub(crate) fn logs(gen: &mut SchemaGenerator) -> Schema {
+    example(gen, &vec![log()])
+}
+
+pub(crate) fn peer_ids(gen: &mut SchemaGenerator) -> Schema {
+    let peer: PeerId = "12D3KooWDji7xMLia6GAsyr4oiEFD2dd3zSryqNhfxU3Grzs1r9p"
+        .parse()
+        .expect("a valid peer id");
+    example(gen, &HashSet::from([peer]))
+}
+
+pub(crate) fn multiaddrs(gen: &mut SchemaGenerator) -> Schema {
+    let address: Multiaddr = "/ip4/127.0.0.1/tcp/6009"
+        .parse()
+        .expect("a valid multiaddr");
+    example(gen, &vec![address])
+}
+
+fn generator() -> SchemaGenerator {
+    SchemaSettings::draft07()
+        .with(|settings| settings.definitions_path = "#/components/schemas/".to_string())
+        .into_generator()
+}
+
+/// The OpenRPC document of the JSON-RPC api.
+pub fn openrpc() -> Value {
+    let mut gen = generator();
+    let methods: Vec<Value> = rpc::methods()
+        .into_iter()
+        .map(|method| {
+            let params: Vec<Value> = (method.params)(&mut gen)
+                .into_iter()
+                .map(|field| {
+                    json!({
+                        "name": field.name,
+                        "required": field.required,
+                        "schema": field.schema,
+                    })
+                })
+                .collect();
+            json!({
+                "name": method.name,
+                "summary": method.summary,
+                "paramStructure": if method.by_position { "by-position" } else { "by-name" },
+                "params": params,
+                "result": { "name": "result", "schema": (method.result)(&mut gen) },
+                "x-scope": method_scope(method.name),
+            })
+        })
+        .collect();
+
+    json!({
+        "openrpc": OPENRPC_VERSION,
+        "info": {
+            "title": "Ursa JSON-RPC API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "name": "ursa", "url": "/rpc/v0" }],
+        "methods": methods,
+        "components": { "schemas": gen.take_definitions() },
+    })
+}
+
+/// The OpenAPI document of the http routes.
+pub fn openapi() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let cid = json!({
+        "name": "cid",
+        "in": "path",
+        "required": true,
+        "description": "Root cid of the dag",
+        "schema": { "type": "string" },
+    });
+    let selection: Vec<Value> = fields::<DagSelection>(&mut gen)
+        .into_iter()
+        .map(|field| {
+            json!({
+                "name": field.name,
+                "in": "query",
+                "required": field.required,
+                "schema": field.schema,
+            })
+        })
+        .collect();
+    let car = json!({
+        "description": "The car file of the selected blocks",
+        "content": {
+            "application/vnd.curl.car": { "schema": { "type": "string", "format": "binary" } },
+        },
+    });
+    let error = |description: &str| {
+        json!({
+            "description": description,
+            "content": { "text/plain": { "schema": { "type": "string" } } },
+        })
+    };
+    let security = |scope: Scope| json!({ "security": [{ "bearer": [] }], "x-scope": scope });
+
+    let mut get_car = json!({
+        "summary": "Stream the car file of a dag",
+        "parameters": [cid.clone()],
+        "responses": {
+            "200": car,
+            "400": error("Invalid cid"),
+            "404": error("The dag wasn't found on the network or the origins"),
+            "413": error("The dag exceeds the maximum size fetched from the origins"),
+            "500": error("The dag couldn't be fetched"),
+            "502": error("The origins failed"),
+            "504": error("The origins timed out"),
+        },
+    });
+    merge(&mut get_car, security(Scope::Read));
+    get_car["parameters"]
+        .as_array_mut()
+        .unwrap()
+        .extend(selection.clone());
+
+    let mut get_car_path = get_car.clone();
+    get_car_path["summary"] = json!("Stream the car file of the dag at a path under a directory");
+    get_car_path["parameters"].as_array_mut().unwrap().insert(
+        1,
+        json!({
+            "name": "path",
+            "in": "path",
+            "required": true,
+            "description": "Path under the root, e.g. `assets/logo.png`",
+            "schema": { "type": "string" },
+        }),
+    );
+    get_car_path["responses"]["404"] =
+        error("The path doesn't exist under the root, or the dag wasn't found");
+
+    let mut progress = json!({
+        "summary": "Progress of the fetch of a dag",
+        "parameters": [cid],
+        "responses": {
+            "200": {
+                "description": "Progress of the fetch",
+                "content": {
+                    "application/json": { "schema": gen.subschema_for::<BitswapProgress>() },
+                },
+            },
+            "400": error("Invalid cid"),
+            "404": error("No fetch in progress"),
+        },
+    });
+    merge(&mut progress, security(Scope::Read));
+
+    let mut progress_events = json!({
+        "summary": "Stream the progress of the fetch of a dag",
+        "description": "Server-sent events with the progress of the fetch, ending with the fetch",
+        "parameters": [cid],
+        "responses": {
+            "200": {
+                "description": "An event per progress update",
+                "content": {
+                    "text/event-stream": { "schema": gen.subschema_for::<BitswapProgress>() },
+                },
+            },
+            "400": error("Invalid cid"),
+            "404": error("No fetch in progress"),
+        },
+    });
+    merge(&mut progress_events, security(Scope::Read));
+
+    let mut upload = json!({
+        "summary": "Import a car file",
+        "requestBody": {
+            "required": true,
+            "content": {
+                "multipart/form-data": {
+                    "schema": {
+                        "type": "object",
+                        "properties": { "file": { "type": "string", "format": "binary" } },
+                    },
+                    "encoding": { "file": { "contentType": "application/vnd.curl.car" } },
+                },
+            },
+        },
+        "responses": {
+            "200": {
+                "description": "Roots of the car file",
+                "content": {
+                    "application/json": {
+                        "schema": { "type": "array", "items": { "type": "string" } },
+                    },
+                },
+            },
+            "400": error("Missing file, wrong content type or invalid blocks"),
+            "413": error("The file is larger than 250MB"),
+        },
+    });
+    merge(&mut upload, security(Scope::Write));
+
+    let job = json!({
+        "application/json": { "schema": gen.subschema_for::<PrefetchJob>() },
+    });
+    let mut prefetch = json!({
+        "summary": "Start pulling dags into the node in the background",
+        "requestBody": {
+            "required": true,
+            "content": {
+                "application/json": { "schema": gen.subschema_for::<NetworkPrefetchParams>() },
+            },
+        },
+        "responses": {
+            "202": { "description": "The prefetch job", "content": job.clone() },
+            "400": error("No roots, too many roots or an invalid cid"),
+            "503": error("Too many prefetch jobs are running"),
+        },
+    });
+    merge(&mut prefetch, security(Scope::Write));
+
+    let mut prefetch_status = json!({
+        "summary": "Status and progress of a prefetch job",
+        "parameters": [{
+            "name": "id",
+            "in": "path",
+            "required": true,
+            "description": "Id of the prefetch job",
+            "schema": { "type": "string" },
+        }],
+        "responses": {
+            "200": { "description": "The prefetch job", "content": job },
+            "404": error("No prefetch job with the id"),
+        },
+    });
+    merge(&mut prefetch_status, security(Scope::Write));
+
+    let mut provider_head = json!({
+        "summary": "Signed head of the index provider advertisement chain",
+        "responses": {
+            "200": {
+                "description": "The signed head",
+                "content": { "application/json": { "schema": { "type": "object" } } },
+            },
+            "404": error("Nothing was advertised yet"),
+        },
+    });
+    merge(&mut provider_head, security(Scope::Read));
+
+    let mut provider_block = json!({
+        "summary": "A block of the advertisement chain, fetched by indexers",
+        "parameters": [{
+            "name": "cid",
+            "in": "path",
+            "required": true,
+            "description": "Cid of the block",
+            "schema": { "type": "string" },
+        }],
+        "responses": {
+            "200": {
+                "description": "The block",
+                "content": {
+                    "application/octet-stream": {
+                        "schema": { "type": "string", "format": "binary" },
+                    },
+                },
+            },
+            "404": error("No such block"),
+            "500": error("Invalid cid"),
+        },
+    });
+    merge(&mut provider_block, security(Scope::Read));
+
+    let mut metrics = json!({
+        "summary": "Prometheus metrics of the node, served when metrics are enabled",
+        "responses": {
+            "200": {
+                "description": "The metrics in the Prometheus text format",
+                "content": { "text/plain": { "schema": { "type": "string" } } },
+            },
+        },
+    });
+    merge(&mut metrics, security(Scope::Read));
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "Ursa HTTP API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": {
+            "/ursa/v0/": { "post": upload },
+            "/ursa/v0/{cid}": { "get": get_car },
+            "/ursa/v0/{cid}/{path}": { "get": get_car_path },
+            "/ursa/v0/progress/{cid}": { "get": progress },
+            "/ursa/v0/progress/{cid}/events": { "get": progress_events },
+            "/ursa/v0/prefetch": { "post": prefetch },
+            "/ursa/v0/prefetch/{id}": { "get": prefetch_status },
+            "/head": { "get": provider_head },
+            "/{cid}": { "get": provider_block },
+            "/metrics": { "get": metrics },
+            "/ping": {
+                "get": {
+                    "summary": "Liveness check, used for TLS verification",
+                    "responses": {
+                        "200": {
+                            "description": "pong",
+                            "content": { "text/plain": { "schema": { "type": "string" } } },
+                        },
+                    },
+                },
+            },
+            "/openapi.json": {
+                "get": {
+                    "summary": "This document",
+                    "responses": {
+                        "200": {
+                            "description": "The OpenAPI document of the http routes",
+                            "content": { "application/json": { "schema": { "type": "object" } } },
+                        },
+                    },
+                },
+            },
+        },
+        "components": {
+            "schemas": gen.take_definitions(),
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } },
+        },
+    })
+}
+
+fn merge(target: &mut Value, fields: Value) {
+    if let (Value::Object(target), Value::Object(fields)) = (target, fields) {
+        target.extend(fields);
+    }
+}
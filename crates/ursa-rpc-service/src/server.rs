@@ -1,11 +1,12 @@
 use anyhow::Result;
-use axum::{Extension, Router};
+use axum::{middleware, Extension, Router};
 use db::Store;
 use fvm_ipld_blockstore::Blockstore;
 use std::{net::SocketAddr, sync::Arc};
 
 use crate::{
     api::NodeNetworkInterface,
+    auth::{authenticate, require_scope, Auth, Listener, Scope},
     config::ServerConfig,
     http,
     rpc::{self, RpcServer},
@@ -19,16 +20,18 @@ where
 {
     rpc_server: RpcServer,
     interface: Arc<NodeNetworkInterface<S>>,
+    auth: Arc<Auth>,
 }
 
 impl<S> Server<S>
 where
     S: Blockstore + Store + Send + Sync + 'static,
 {
-    pub fn new(interface: Arc<NodeNetworkInterface<S>>) -> Self {
+    pub fn new(interface: Arc<NodeNetworkInterface<S>>, auth: Auth) -> Self {
         Self {
             rpc_server: RpcServer::new(Arc::clone(&interface)),
             interface: interface.clone(),
+            auth: Arc::new(auth),
         }
     }
 
@@ -43,28 +46,57 @@ where
             if metrics.is_some() { " + metrics" } else { "" }
         );
 
-        let service = MultiplexService::new(self.http_app(index_provider, metrics), self.rpc_app());
+        let public = MultiplexService::new(
+            self.http_app(Listener::Public, index_provider.clone(), metrics.clone()),
+            self.rpc_app(Listener::Public),
+        );
+        let admin = MultiplexService::new(
+            self.http_app(Listener::Admin, index_provider, metrics),
+            self.rpc_app(Listener::Admin),
+        );
 
         let http_address = SocketAddr::from(([0, 0, 0, 0], config.port));
-        info!("listening on {}", http_address);
-        axum::Server::bind(&http_address)
-            .serve(tower::make::Shared::new(service))
-            .await?;
+        let admin_address: SocketAddr =
+            format!("{}:{}", config.admin_addr, config.admin_port).parse()?;
+        info!("listening on {http_address}, admin listening on {admin_address}");
+        tokio::try_join!(
+            axum::Server::bind(&http_address).serve(tower::make::Shared::new(public)),
+            axum::Server::bind(&admin_address).serve(tower::make::Shared::new(admin)),
+        )?;
 
         Ok(())
     }
 
-    pub fn rpc_app(&self) -> Router {
+    pub fn rpc_app(&self, listener: Listener) -> Router {
         Router::new()
-            .merge(rpc::routes::network::init())
+            .merge(rpc::routes::network::init::<NodeNetworkInterface<S>>())
             .layer(Extension(self.rpc_server.clone()))
+            .layer(Extension(self.interface.clone()))
+            .layer(middleware::from_fn_with_state(
+                (Arc::clone(&self.auth), listener),
+                authenticate,
+            ))
     }
 
-    pub fn http_app(&self, index_provider: Router, metrics: Option<Router>) -> Router {
+    pub fn http_app(
+        &self,
+        listener: Listener,
+        index_provider: Router,
+        metrics: Option<Router>,
+    ) -> Router {
+        let read_scope = || middleware::from_fn_with_state(Scope::Read, require_scope);
         Router::new()
-            .merge(http::routes::network::init::<S>())
-            .merge(index_provider)
-            .merge(metrics.unwrap_or_else(Router::new))
+            .merge(http::routes::network::init::<NodeNetworkInterface<S>>())
+            .merge(index_provider.route_layer(read_scope()))
+            .merge(
+                metrics
+                    .map(|metrics| metrics.route_layer(read_scope()))
+                    .unwrap_or_else(Router::new),
+            )
             .layer(Extension(self.interface.clone()))
+            .layer(middleware::from_fn_with_state(
+                (Arc::clone(&self.auth), listener),
+                authenticate,
+            ))
     }
 }
@@ -1,17 +1,29 @@
 #[cfg(test)]
 mod tests {
-    use crate::api::{NetworkInterface, NodeNetworkInterface};
-    use crate::config::OriginConfig;
-    use crate::tests::{dummy_ipfs, init, setup_logger};
+    use crate::api::{NetworkInterface, NodeNetworkInterface, PrefetchError, PrefetchStatus};
+    use crate::http::routes::network::get_handler;
+    use crate::origin::OriginError;
+    use crate::tests::{dummy_ipfs, init, interface_with_gateway, setup_logger};
     use anyhow::Result;
     use async_fs::{remove_file, File};
+    use axum::{
+        extract::{Path as UrlPath, Query},
+        http::StatusCode,
+        response::IntoResponse,
+        routing::get,
+        Extension, Router,
+    };
+    use futures::future;
     use futures::io::BufReader;
     use fvm_ipld_car::load_car;
+    use libipld::{cbor::DagCborCodec, ipld, multihash::Code, Block, Cid, DefaultParams};
     use std::path::Path;
-    use std::sync::Arc;
-    use tokio::task;
-
-    use tracing::error;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+    use tokio::{sync::broadcast, time::sleep};
 
     #[tokio::test]
     async fn test_put_and_get() -> Result<()> {
@@ -24,6 +36,8 @@ mod tests {
             Default::default(),
             mempool_address,
             abci_send,
+            ursa_service.event_broadcast(),
+            broadcast::channel(16).0,
         ));
 
         // the test case does not start the provider engine, so the best way
@@ -37,7 +51,7 @@ mod tests {
         let root_cid = put_file[0];
 
         interface
-            .get_file("../../test_files".to_string(), root_cid)
+            .get_file("../../test_files".to_string(), root_cid, Default::default())
             .await?;
 
         let path = format!("../../test_files/{root_cid}.car");
@@ -55,33 +69,10 @@ mod tests {
     #[tokio::test]
     async fn test_origin_fallback() -> Result<()> {
         setup_logger();
-        task::spawn(async {
-            if let Err(e) = dummy_ipfs().await {
-                error!("dummy ipfs server failed: {}", e);
-            }
-        });
-
         const IPFS_CID: &str = "bafkreihwcrnsi2tqozwq22k4vl7flutu43jlxgb3tenewysm2xvfuej5i4";
         const IPFS_LEN: usize = 26849;
 
-        let (node, mut provider, store, mempool_address, abci_send) = init()?;
-        let command_sender = node.command_sender();
-        provider.command_receiver().close();
-        tokio::task::spawn(async move {
-            node.start().await.unwrap();
-        });
-
-        let interface = Arc::new(NodeNetworkInterface::new(
-            Arc::clone(&store),
-            command_sender,
-            provider.command_sender(),
-            OriginConfig {
-                ipfs_gateway: "127.0.0.1:9682".to_string(),
-                use_https: Some(false),
-            },
-            mempool_address,
-            abci_send,
-        ));
+        let interface = interface_with_gateway(dummy_ipfs()?)?;
 
         // since we have no peers, get will fallback to origin
         let (cid, data) = &interface.get_data(IPFS_CID.parse()?).await?[0];
@@ -90,4 +81,100 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_origin_negative_cache() -> Result<()> {
+        setup_logger();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let router = Router::new().route(
+            "/ipfs/:cid",
+            get({
+                let requests = Arc::clone(&requests);
+                move || {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    future::ready(StatusCode::NOT_FOUND)
+                }
+            }),
+        );
+        let interface = interface_with_gateway(router)?;
+
+        const MISSING_CID: &str = "bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy";
+        let cid = MISSING_CID.parse()?;
+        for _ in 0..2 {
+            let err = interface.get_data(cid).await.unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<OriginError>(),
+                Some(OriginError::NotFound(_))
+            ));
+        }
+        // the second request is answered from the negative cache
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        let res = get_handler(
+            UrlPath(MISSING_CID.to_string()),
+            Query(Default::default()),
+            Extension(Arc::clone(&interface)),
+        )
+        .await
+        .into_response();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let res = get_handler(
+            UrlPath("not-a-cid".to_string()),
+            Query(Default::default()),
+            Extension(interface),
+        )
+        .await
+        .into_response();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prefetch() -> Result<()> {
+        setup_logger();
+        let router =
+            Router::new().route("/ipfs/:cid", get(|| future::ready(StatusCode::NOT_FOUND)));
+        let interface = interface_with_gateway(router)?;
+        let store = &interface.store;
+
+        let file = File::open("../../test_files/test.car").await?;
+        let root = store.load_car(BufReader::new(file)).await?[0];
+        let missing: Cid = "bafkreigh2akiscaildcqabsyg3dfr6chu3fgpregiymsck7e7aqa4s52zy".parse()?;
+        let err = interface.prefetch(vec![], false).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PrefetchError>(),
+            Some(PrefetchError::InvalidRoots)
+        ));
+
+        // a root held without the rest of its dag isn't complete
+        let partial = Block::<DefaultParams>::encode(
+            DagCborCodec,
+            Code::Sha2_256,
+            &ipld!({ "link": missing }),
+        )?;
+        store.put_block(partial.cid(), partial.data())?;
+
+        let job = interface
+            .prefetch(vec![root, missing, *partial.cid()], false)
+            .await?;
+        assert_eq!(job.roots.len(), 3);
+        assert_eq!(job.id.len(), 32);
+        let mut job = loop {
+            let job = interface.get_prefetch(job.id.clone()).await?.unwrap();
+            if job.status.is_finished() {
+                break job;
+            }
+            sleep(Duration::from_millis(100)).await;
+        };
+        assert_eq!(job.status, PrefetchStatus::Failed);
+        for failed in job.roots.drain(1..) {
+            assert_eq!(failed.status, PrefetchStatus::Failed);
+            assert!(failed.error.is_some());
+        }
+        assert_eq!(job.roots[0].status, PrefetchStatus::Completed);
+
+        assert_eq!(interface.get_prefetch("unknown".to_string()).await?, None);
+        Ok(())
+    }
 }
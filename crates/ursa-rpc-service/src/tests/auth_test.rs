@@ -0,0 +1,211 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::NodeNetworkInterface,
+        auth::{issue_token, key_id, Auth, AuthConfig, AuthError, Claims, Listener, Scope},
+        server::Server,
+        tests::{init, setup_logger},
+    };
+    use anyhow::Result;
+    use axum::{
+        body::Body,
+        http::{self, Request, StatusCode},
+    };
+    use libp2p::identity::Keypair;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+    use tokio::sync::broadcast;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_tokens() -> Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let auth = Auth::new(keypair.public(), AuthConfig::default());
+
+        let claims = Claims {
+            scopes: vec![Scope::Write],
+            exp: None,
+            name: Some("dashboard".to_string()),
+        };
+        let token = issue_token(&keypair, &claims)?;
+        assert_eq!(auth.verify(&token)?, claims);
+
+        let access = auth.access(Some(&token))?;
+        assert!(access.authenticated);
+        assert!(access.allows(Scope::Read));
+        assert!(access.allows(Scope::Write));
+        assert!(!access.allows(Scope::Admin));
+
+        let anonymous = auth.access(None)?;
+        assert!(!anonymous.authenticated);
+        assert!(anonymous.allows(Scope::Read));
+        assert!(!anonymous.allows(Scope::Write));
+
+        // signed by another identity
+        let other = issue_token(&Keypair::generate_ed25519(), &claims)?;
+        assert!(matches!(
+            auth.verify(&other),
+            Err(AuthError::InvalidSignature)
+        ));
+
+        // claims swapped under a valid signature
+        let admin = issue_token(
+            &Keypair::generate_ed25519(),
+            &Claims {
+                scopes: vec![Scope::Admin],
+                exp: None,
+                name: None,
+            },
+        )?;
+        let forged = format!(
+            "{}.{}",
+            admin.split_once('.').unwrap().0,
+            token.split_once('.').unwrap().1
+        );
+        assert!(matches!(
+            auth.verify(&forged),
+            Err(AuthError::InvalidSignature)
+        ));
+
+        // a signature of the node identity over the bare claims isn't a token
+        let claims_part = token.split_once('.').unwrap().0;
+        let bare = format!(
+            "{claims_part}.{}",
+            base64::encode_config(
+                keypair.sign(claims_part.as_bytes())?,
+                base64::URL_SAFE_NO_PAD
+            )
+        );
+        assert!(matches!(
+            auth.verify(&bare),
+            Err(AuthError::InvalidSignature)
+        ));
+
+        let expired = issue_token(
+            &keypair,
+            &Claims {
+                scopes: vec![Scope::Read],
+                exp: Some(1),
+                name: None,
+            },
+        )?;
+        assert!(matches!(auth.verify(&expired), Err(AuthError::Expired)));
+        assert!(matches!(auth.verify("garbage"), Err(AuthError::Malformed)));
+
+        let revoked = Auth::new(
+            keypair.public(),
+            AuthConfig {
+                revoked_keys: vec![key_id(&token)],
+                ..Default::default()
+            },
+        );
+        assert!(matches!(revoked.verify(&token), Err(AuthError::Revoked)));
+        let read = issue_token(
+            &keypair,
+            &Claims {
+                scopes: vec![Scope::Read],
+                exp: None,
+                name: None,
+            },
+        )?;
+        assert!(revoked.verify(&read).is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rpc_scopes() -> Result<()> {
+        setup_logger();
+        let (ursa_service, provider_engine, store, mempool_address, abci_send) = init()?;
+        let keypair = Keypair::generate_ed25519();
+
+        let interface = Arc::new(NodeNetworkInterface::new(
+            Arc::clone(&store),
+            ursa_service.command_sender(),
+            provider_engine.command_sender(),
+            Default::default(),
+            mempool_address,
+            abci_send,
+            ursa_service.event_broadcast(),
+            broadcast::channel(16).0,
+        ));
+        let server = Server::new(
+            interface,
+            Auth::new(keypair.public(), AuthConfig::default()),
+        );
+        let read_token = issue_token(
+            &keypair,
+            &Claims {
+                scopes: vec![Scope::Read],
+                exp: None,
+                name: None,
+            },
+        )?;
+
+        let rpc = |listener: Listener, token: Option<&str>, method: &str| {
+            let mut req = Request::builder()
+                .method(http::Method::POST)
+                .uri("/rpc/v0")
+                .header(http::header::CONTENT_TYPE, "application/json");
+            if let Some(token) = token {
+                req = req.header(http::header::AUTHORIZATION, format!("Bearer {token}"));
+            }
+            let body = json!({
+                "jsonrpc": "2.0",
+                "method": method,
+                "params": { "path": "/nonexistent/file" },
+                "id": 1,
+            });
+            let req = req
+                .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                .unwrap();
+            let rpc_app = server.rpc_app(listener);
+            async move {
+                let response = rpc_app.oneshot(req).await.unwrap();
+                let status = response.status();
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                let value = serde_json::from_slice::<Value>(&body).unwrap_or(Value::Null);
+                (status, value)
+            }
+        };
+
+        // read methods are open to anonymous requests
+        let (status, _) = rpc(Listener::Public, None, "ursa_listener_addresses").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, value) = rpc(Listener::Public, None, "ursa_put_file").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(value["error"]["code"], json!(-32001));
+
+        let (status, _) = rpc(Listener::Public, Some(&read_token), "ursa_put_file").await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _) = rpc(Listener::Public, Some("garbage"), "ursa_listener_addresses").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // a denied batch gets an error for each of its calls
+        let batch = json!([
+            { "jsonrpc": "2.0", "method": "ursa_listener_addresses", "params": [], "id": 1 },
+            { "jsonrpc": "2.0", "method": "ursa_put_file", "params": { "path": "/file" }, "id": 2 },
+        ]);
+        let req = Request::builder()
+            .method(http::Method::POST)
+            .uri("/rpc/v0")
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(Body::from(serde_json::to_vec(&batch)?))?;
+        let response = server.rpc_app(Listener::Public).oneshot(req).await?;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        let errors: Vec<Value> = serde_json::from_slice(&body)?;
+        assert_eq!(errors.len(), 2);
+        for (error, id) in errors.iter().zip([1, 2]) {
+            assert_eq!(error["id"], json!(id));
+            assert_eq!(error["error"]["code"], json!(-32001));
+        }
+
+        // the admin listener allows everything, the call itself fails on the missing file
+        let (status, value) = rpc(Listener::Admin, None, "ursa_put_file").await;
+        assert_ne!(status, StatusCode::UNAUTHORIZED);
+        assert_ne!(value["error"]["code"], json!(-32001));
+        Ok(())
+    }
+}
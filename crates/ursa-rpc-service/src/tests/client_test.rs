@@ -0,0 +1,207 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        api::{
+            Car, NetworkPrefetchParams, NetworkPrefetchStatusParams, NodeNetworkInterface,
+            PrefetchStatus,
+        },
+        auth::{Auth, Listener},
+        client::{ClientError, UrsaClient},
+        mock::{MockNetworkInterface, MockServer},
+        origin::OriginHealth,
+        server::Server,
+        tests::{init, setup_logger},
+    };
+    use anyhow::Result;
+    use axum::{http::StatusCode, routing::post, Router};
+    use futures::io::Cursor;
+    use fvm_ipld_car::CarReader;
+    use libp2p::{identity::Keypair, Multiaddr, PeerId};
+    use std::{
+        collections::HashSet,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+    use tokio::{sync::broadcast, task};
+    use ursa_store::{DagScope, DagSelection};
+
+    #[test]
+    fn test_client_endpoint() {
+        assert!(UrsaClient::new("https://node.example.com/rpc/v0").is_ok());
+        assert!(matches!(
+            UrsaClient::new("ftp://node.example.com/rpc/v0"),
+            Err(ClientError::InvalidEndpoint(_))
+        ));
+        assert!(matches!(
+            UrsaClient::new("not a url"),
+            Err(ClientError::InvalidEndpoint(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client() -> Result<()> {
+        setup_logger();
+        let (ursa_service, provider_engine, store, mempool_address, abci_send) = init()?;
+
+        let interface = Arc::new(NodeNetworkInterface::new(
+            Arc::clone(&store),
+            ursa_service.command_sender(),
+            provider_engine.command_sender(),
+            Default::default(),
+            mempool_address,
+            abci_send,
+            ursa_service.event_broadcast(),
+            broadcast::channel(16).0,
+        ));
+        let server = Server::new(
+            interface,
+            Auth::new(Keypair::generate_ed25519().public(), Default::default()),
+        );
+        let http_server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(server.rpc_app(Listener::Public).into_make_service());
+        let address = http_server.local_addr();
+        task::spawn(http_server);
+
+        let client = UrsaClient::new(&format!("http://{address}/rpc/v0"))?;
+        let addresses: Vec<Multiaddr> = client.call("ursa_listener_addresses", ()).await?;
+        assert_eq!(addresses, vec!["/ip4/127.0.0.1/tcp/6009".parse()?]);
+
+        match client.call::<_, ()>("ursa_unknown", ()).await {
+            Err(ClientError::Rpc { code, .. }) => assert_eq!(code, -32601),
+            res => panic!("expected an rpc error, got {res:?}"),
+        }
+        // filesystem methods need a token on the public listener
+        match client.call::<_, String>("ursa_put_file", ()).await {
+            Err(ClientError::Rpc { code, .. }) => assert_eq!(code, -32001),
+            res => panic!("expected an rpc error, got {res:?}"),
+        }
+
+        let unreachable = UrsaClient::new("http://127.0.0.1:1/rpc/v0")?;
+        assert!(matches!(
+            unreachable
+                .call::<_, ()>("ursa_listener_addresses", ())
+                .await,
+            Err(ClientError::Transport(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_url_keeps_prefix() -> Result<()> {
+        let client = UrsaClient::new("https://node.example.com/prefix/rpc/v0")?;
+        assert_eq!(
+            client.http_url("ursa/v0/progress/cid")?.as_str(),
+            "https://node.example.com/prefix/ursa/v0/progress/cid"
+        );
+        let client = UrsaClient::new("http://127.0.0.1:4070/rpc/v0/")?;
+        assert_eq!(
+            client.http_url("ursa/v0/")?.as_str(),
+            "http://127.0.0.1:4070/ursa/v0/"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_retries_reads_only() -> Result<()> {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&attempts);
+        let router = Router::new().route(
+            "/rpc/v0",
+            post(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { StatusCode::SERVICE_UNAVAILABLE }
+            }),
+        );
+        let server = axum::Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))?
+            .serve(router.into_make_service());
+        let address = server.local_addr();
+        task::spawn(server);
+        let client = UrsaClient::new(&format!("http://{address}/rpc/v0"))?.with_retries(2);
+
+        let res: Result<Vec<Multiaddr>, _> = client.read("ursa_listener_addresses", ()).await;
+        assert!(matches!(res, Err(ClientError::Http { status: 503, .. })));
+        assert_eq!(attempts.swap(0, Ordering::SeqCst), 3);
+
+        let res = client.eth_send_raw_transaction(Default::default()).await;
+        assert!(matches!(res, Err(ClientError::Http { status: 503, .. })));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_client_mock_server() -> Result<()> {
+        setup_logger();
+        let server = MockServer::start(Arc::new(MockNetworkInterface::new()))?;
+        let client = server.client().with_retries(2);
+
+        let car = Car::from_file("../../test_files/test.car").await?;
+        let root = CarReader::new(car).await?.header.roots[0];
+        let roots = client
+            .upload_car(Car::from_file("../../test_files/test.car").await?)
+            .await?;
+        assert_eq!(roots, vec![root]);
+        assert!(server.interface.store.has_block(&root)?);
+
+        let mut car = CarReader::new(client.get_car(&root, &Default::default()).await?).await?;
+        assert_eq!(car.header.roots, vec![root]);
+        let mut blocks = 0;
+        while car.next_block().await?.is_some() {
+            blocks += 1;
+        }
+        assert_eq!(blocks, server.interface.store.dag_traversal(&root)?.len());
+
+        let selection = DagSelection {
+            dag_scope: Some(DagScope::Block),
+            ..Default::default()
+        };
+        let bytes = client
+            .get_car(&root, &selection)
+            .await?
+            .into_bytes()
+            .await?;
+        let mut car = CarReader::new(Cursor::new(bytes)).await?;
+        assert_eq!(car.next_block().await?.map(|block| block.cid), Some(root));
+        assert!(car.next_block().await?.is_none());
+
+        assert_eq!(client.get_progress(&root).await?, None);
+
+        let job = client
+            .prefetch(NetworkPrefetchParams {
+                cids: vec![root.to_string()],
+                replicate: true,
+            })
+            .await?;
+        assert_eq!(job.status, PrefetchStatus::Completed);
+        let status = client
+            .prefetch_status(NetworkPrefetchStatusParams { id: job.id.clone() })
+            .await?;
+        assert_eq!(status, Some(job));
+        assert!(client
+            .prefetch(NetworkPrefetchParams {
+                cids: vec!["invalid".to_string()],
+                replicate: false,
+            })
+            .await
+            .is_err());
+
+        let peer = PeerId::random();
+        server
+            .interface
+            .set_peers(HashSet::from_iter(vec![peer]))
+            .await;
+        assert_eq!(client.get_peers().await?, HashSet::from_iter(vec![peer]));
+        assert!(client.get_relay_state().await.is_err());
+
+        let health = vec![OriginHealth {
+            name: "https://ipfs.io".to_string(),
+            failures: 3,
+            healthy: false,
+        }];
+        server.interface.set_origin_health(health.clone()).await;
+        assert_eq!(client.origin_health().await?, health);
+        Ok(())
+    }
+}
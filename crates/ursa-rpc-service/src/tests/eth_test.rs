@@ -0,0 +1,133 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        client::ClientError,
+        mock::{MockNetworkInterface, MockServer},
+        tests::setup_logger,
+    };
+    use anyhow::Result;
+    use ethers::{
+        signers::{LocalWallet, Signer},
+        types::{transaction::eip2718::TypedTransaction, TransactionRequest, H256, U64},
+        utils::keccak256,
+    };
+    use serde_json::Value;
+    use std::sync::Arc;
+    use tendermint_proto::abci::ResponseQuery;
+    use ursa_application::types::{decode_transaction, QueryResponse};
+
+    #[tokio::test]
+    async fn test_eth_transactions() -> Result<()> {
+        setup_logger();
+        let server = MockServer::start(Arc::new(MockNetworkInterface::new()))?;
+        let client = server.client();
+
+        let wallet: LocalWallet =
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318".parse()?;
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(wallet.address())
+            .value(1)
+            .nonce(0)
+            .chain_id(1)
+            .into();
+        let signature = wallet.sign_transaction(&tx).await?;
+        let raw = tx.rlp_signed(&signature);
+
+        let next_nonce = |nonce: u64| ResponseQuery {
+            value: serde_json::to_vec(&QueryResponse::Nonce(nonce)).unwrap(),
+            ..Default::default()
+        };
+        server.interface.set_abci_response(next_nonce(0)).await;
+        let hash = client.eth_send_raw_transaction(raw.clone()).await?;
+        assert_eq!(hash, H256::from(keccak256(&raw)));
+        let submitted = server.interface.transactions().await;
+        assert_eq!(submitted[0].transaction.as_ref(), raw.as_ref());
+
+        // the application recovers the sender from the signature
+        let (decoded_hash, decoded) = decode_transaction(&raw)?;
+        assert_eq!(decoded_hash, hash);
+        assert_eq!(decoded.from, Some(wallet.address()));
+
+        // unsigned transactions are hashed from their fields, not their json
+        let hash = client
+            .eth_send_transaction(TransactionRequest::new().to(wallet.address()))
+            .await?;
+        let submitted = server.interface.transactions().await;
+        assert_eq!(decode_transaction(&submitted[1].transaction)?.0, hash);
+        let json: Value = serde_json::from_slice(&submitted[1].transaction)?;
+        assert_eq!(
+            decode_transaction(&serde_json::to_vec_pretty(&json)?)?.0,
+            hash
+        );
+
+        // only signed transactions have a sender
+        match client
+            .eth_send_transaction(TransactionRequest::new().from(wallet.address()))
+            .await
+        {
+            Err(ClientError::Rpc { code, .. }) => assert_eq!(code, -32000),
+            res => panic!("expected an rpc error, got {res:?}"),
+        }
+        let forged = serde_json::to_vec(&TransactionRequest::new().from(wallet.address()))?;
+        assert!(decode_transaction(&forged).is_err());
+
+        // a committed nonce is rejected before reaching consensus
+        server.interface.set_abci_response(next_nonce(1)).await;
+        match client.eth_send_raw_transaction(raw).await {
+            Err(ClientError::Rpc { code, message, .. }) => {
+                assert_eq!(code, -32000);
+                assert!(message.contains("nonce too low"));
+            }
+            res => panic!("expected an rpc error, got {res:?}"),
+        }
+
+        match client.eth_send_raw_transaction(vec![1, 2, 3].into()).await {
+            Err(ClientError::Rpc { code, .. }) => assert_eq!(code, -32000),
+            res => panic!("expected an rpc error, got {res:?}"),
+        }
+        assert_eq!(server.interface.transactions().await.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_eth_queries() -> Result<()> {
+        setup_logger();
+        let server = MockServer::start(Arc::new(MockNetworkInterface::new()))?;
+        let client = server.client();
+
+        let respond = |res: QueryResponse| ResponseQuery {
+            value: serde_json::to_vec(&res).unwrap(),
+            ..Default::default()
+        };
+        server
+            .interface
+            .set_abci_response(respond(QueryResponse::BlockNumber(7)))
+            .await;
+        assert_eq!(client.eth_block_number().await?, U64::from(7));
+
+        server
+            .interface
+            .set_abci_response(respond(QueryResponse::Receipt(None)))
+            .await;
+        assert_eq!(
+            client.eth_get_transaction_receipt(H256::zero()).await?,
+            None
+        );
+
+        // a failed query is answered with an error message
+        server
+            .interface
+            .set_abci_response(ResponseQuery {
+                value: "error retrieving code".into(),
+                ..Default::default()
+            })
+            .await;
+        match client.eth_get_code(Default::default()).await {
+            Err(ClientError::Rpc { message, .. }) => {
+                assert!(message.contains("error retrieving code"))
+            }
+            res => panic!("expected an rpc error, got {res:?}"),
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,9 @@
 mod api_test;
+mod auth_test;
+mod client_test;
+mod eth_test;
+mod origin_test;
+mod schema_test;
 mod server_test;
 
 use anyhow::Result;
@@ -6,11 +11,15 @@ use axum::{headers::HeaderMap, routing::get, Router};
 use db::MemoryDB;
 use libp2p::identity::Keypair;
 use simple_logger::SimpleLogger;
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 use tendermint_proto::abci::ResponseQuery;
-use tokio::sync::{
-    mpsc::{channel, Sender as BoundedSender},
-    oneshot,
+use tokio::{
+    sync::{
+        broadcast,
+        mpsc::{channel, Sender as BoundedSender},
+        oneshot,
+    },
+    task,
 };
 use tracing::{log::LevelFilter, warn};
 use ursa_consensus::AbciQueryQuery;
@@ -18,6 +27,8 @@ use ursa_index_provider::{config::ProviderConfig, engine::ProviderEngine};
 use ursa_network::{NetworkConfig, UrsaService};
 use ursa_store::UrsaStore;
 
+use crate::{api::NodeNetworkInterface, config::OriginConfig, origin::Origins};
+
 pub fn setup_logger() {
     let level = LevelFilter::Debug;
     if let Err(err) = SimpleLogger::new()
@@ -67,20 +78,48 @@ pub fn init() -> InitResult {
     Ok((service, provider_engine, store, mempool_address, abci_send))
 }
 
-pub async fn dummy_ipfs() -> Result<()> {
+/// An interface of a running node, fetching from `gateway` served as the ipfs gateway on a
+/// local port.
+pub fn interface_with_gateway(gateway: Router) -> Result<Arc<NodeNetworkInterface<MemoryDB>>> {
+    let server = axum::Server::try_bind(&SocketAddr::from(([127, 0, 0, 1], 0)))?
+        .serve(gateway.into_make_service());
+    let address = server.local_addr();
+    task::spawn(server);
+
+    let (node, mut provider, store, mempool_address, abci_send) = init()?;
+    let command_sender = node.command_sender();
+    let network_events = node.event_broadcast();
+    provider.command_receiver().close();
+    task::spawn(async move {
+        node.start().await.unwrap();
+    });
+
+    Ok(Arc::new(NodeNetworkInterface::new(
+        store,
+        command_sender,
+        provider.command_sender(),
+        Origins::from_config(&OriginConfig {
+            ipfs_gateway: address.to_string(),
+            use_https: Some(false),
+            ..Default::default()
+        })?,
+        mempool_address,
+        abci_send,
+        network_events,
+        broadcast::channel(16).0,
+    )))
+}
+
+/// An ipfs gateway serving the test car file for any cid.
+pub fn dummy_ipfs() -> Result<Router> {
     let file: Vec<u8> = std::fs::read("../../test_files/test.car")?;
 
-    let router = Router::new().route(
+    Ok(Router::new().route(
         "/ipfs/:cid",
         get(|| async move {
             let mut headers = HeaderMap::new();
             headers.insert("Content-Type", "application/vnd.ipfs.car".parse().unwrap());
             (headers, file.clone())
         }),
-    );
-
-    axum::Server::bind(&"0.0.0.0:9682".parse().unwrap())
-        .serve(router.into_make_service())
-        .await
-        .map_err(|e| e.into())
+    ))
 }
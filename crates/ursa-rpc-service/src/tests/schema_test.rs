@@ -0,0 +1,181 @@
+#[cfg(test)]
+mod tests {
+    use crate::{
+        client::ClientError,
+        http::routes::network::ROUTES,
+        mock::{self, MockNetworkInterface, MockServer},
+        rpc::methods,
+        schema::{openapi, openrpc},
+        tests::{init, setup_logger},
+    };
+    use anyhow::Result;
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+    };
+    use serde_json::{json, Value};
+    use std::{collections::BTreeSet, sync::Arc};
+    use tower::ServiceExt;
+
+    /// Every `$ref` in `value`.
+    fn refs<'a>(value: &'a Value, out: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    match (key.as_str(), value) {
+                        ("$ref", Value::String(path)) => out.push(path),
+                        _ => refs(value, out),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|value| refs(value, out)),
+            _ => {}
+        }
+    }
+
+    fn assert_refs_resolve(doc: &Value) {
+        let mut paths = Vec::new();
+        refs(doc, &mut paths);
+        assert!(!paths.is_empty());
+        for path in paths {
+            let name = path
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected ref {path}"));
+            assert!(
+                doc["components"]["schemas"].get(name).is_some(),
+                "unresolved ref {path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_schema_documents() {
+        let rpc = openrpc();
+        assert_refs_resolve(&rpc);
+        let get_file = rpc["methods"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|method| method["name"] == "ursa_get_file")
+            .unwrap();
+        let params: Vec<&Value> = get_file["params"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|param| &param["name"])
+            .collect();
+        // the flattened selection is part of the params
+        assert!(params.contains(&&json!("cid")));
+        assert!(params.contains(&&json!("dag-scope")));
+        assert_eq!(get_file["x-scope"], "admin");
+
+        let api = openapi();
+        assert_refs_resolve(&api);
+        assert!(api["paths"]["/ursa/v0/{cid}"]["get"].is_object());
+        assert_eq!(
+            api["paths"]["/ursa/v0/prefetch"]["post"]["x-scope"],
+            "write"
+        );
+        assert_eq!(
+            api["paths"]["/ursa/v0/prefetch/{id}"]["get"]["x-scope"],
+            "write"
+        );
+        assert!(api["components"]["schemas"]["BitswapProgress"].is_object());
+    }
+
+    /// The OpenAPI form of an axum route, `/ursa/v0/:cid/*path` is `/ursa/v0/{cid}/{path}`.
+    fn openapi_path(route: &str) -> String {
+        route
+            .split('/')
+            .map(|segment| match segment.strip_prefix([':', '*']) {
+                Some(name) => format!("{{{name}}}"),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[tokio::test]
+    async fn test_openapi_routes() -> Result<()> {
+        // the routes of the index provider and the metrics are merged in by the server
+        let provider = [("get", "/head"), ("get", "/:cid")];
+        let served: BTreeSet<(String, String)> = ROUTES
+            .iter()
+            .chain(&provider)
+            .chain(&[("get", "/metrics")])
+            .map(|(method, path)| (method.to_string(), openapi_path(path)))
+            .collect();
+        let api = openapi();
+        let documented: BTreeSet<(String, String)> = api["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, operations)| {
+                operations
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .map(move |method| (method.clone(), path.clone()))
+            })
+            .collect();
+        assert_eq!(documented, served);
+
+        let (_, provider_engine, ..) = init()?;
+        let app = provider_engine
+            .router()
+            .fallback(|| async { StatusCode::IM_A_TEAPOT });
+        for (_, path) in provider {
+            let uri = path.replace(":cid", "bafkqaaa");
+            let response = app
+                .clone()
+                .oneshot(Request::get(uri).body(Body::empty())?)
+                .await?;
+            assert_ne!(response.status(), StatusCode::IM_A_TEAPOT, "{path}");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_schema_routes() -> Result<()> {
+        setup_logger();
+        let server = MockServer::start(Arc::new(MockNetworkInterface::new()))?;
+        let client = server.client();
+
+        // every registered method is documented, and every documented one is registered
+        let registered: BTreeSet<&str> = methods().iter().map(|method| method.name).collect();
+        let rpc = openrpc();
+        let documented: BTreeSet<&str> = rpc["methods"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|method| method["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(documented, registered);
+        for name in documented {
+            if let Err(ClientError::Rpc { code, .. }) =
+                client.call::<_, Value>(name, json!({})).await
+            {
+                assert_ne!(code, -32601, "{name} is not registered");
+            }
+        }
+        let unknown = client.call::<_, Value>("ursa_unknown", json!({})).await;
+        assert!(matches!(
+            unknown,
+            Err(ClientError::Rpc { code: -32601, .. })
+        ));
+        let discovered: Value = client.call("rpc.discover", ()).await?;
+        assert_eq!(discovered, openrpc());
+
+        let app = mock::router(Arc::clone(&server.interface));
+        for (uri, doc) in [("/rpc/v0/schema", openrpc()), ("/openapi.json", openapi())] {
+            let response = app
+                .clone()
+                .oneshot(Request::get(uri).body(Body::empty())?)
+                .await?;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = hyper::body::to_bytes(response.into_body()).await?;
+            assert_eq!(serde_json::from_slice::<Value>(&body)?, doc);
+        }
+        Ok(())
+    }
+}
@@ -2,6 +2,12 @@
 mod tests {
     use crate::{
         api::NodeNetworkInterface,
+        auth::{Access, Auth, Listener},
+        mock::{self, MockNetworkInterface, MockServer},
+        rpc::{
+            ws::{Connection, Outbox, OUTBOUND_QUEUE},
+            RpcServer,
+        },
         server::Server,
         tests::{init, setup_logger},
     };
@@ -11,9 +17,18 @@ mod tests {
         http::{self, Request, StatusCode},
     };
 
+    use futures::{SinkExt, Stream, StreamExt};
+    use libipld::Cid;
+    use libp2p::identity::Keypair;
     use serde_json::{json, Value};
-    use std::sync::Arc;
+    use std::{str::FromStr, sync::Arc, time::Duration};
+    use tokio::{
+        sync::{broadcast, mpsc::Receiver},
+        time::timeout,
+    };
+    use tokio_tungstenite::{connect_async, tungstenite::Message};
     use tower::ServiceExt;
+    use ursa_network::{BitswapProgress, NetworkEvent};
 
     #[tokio::test]
     async fn test_http_server() -> Result<()> {
@@ -27,10 +42,12 @@ mod tests {
             Default::default(),
             mempool_address,
             abci_send,
+            ursa_service.event_broadcast(),
+            broadcast::channel(16).0,
         ));
-        let server = Server::new(interface);
+        let server = Server::new(interface, auth());
         let metrics = ursa_metrics::routes::init();
-        let http_app = server.http_app(provider_engine.router(), Some(metrics));
+        let http_app = server.http_app(Listener::Public, provider_engine.router(), Some(metrics));
 
         let response = http_app
             .oneshot(Request::builder().uri("/ping").body(Body::empty()).unwrap())
@@ -56,9 +73,11 @@ mod tests {
             Default::default(),
             mempool_address,
             abci_send,
+            ursa_service.event_broadcast(),
+            broadcast::channel(16).0,
         ));
-        let server = Server::new(interface);
-        let rpc_app = server.rpc_app();
+        let server = Server::new(interface, auth());
+        let rpc_app = server.rpc_app(Listener::Public);
 
         let req = serde_json::to_vec(&json!({
             "jsonrpc": "2.0",
@@ -88,4 +107,285 @@ mod tests {
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_rpc_batch_and_notifications() -> Result<()> {
+        setup_logger();
+        let (ursa_service, provider_engine, store, mempool_address, abci_send) = init()?;
+
+        let interface = Arc::new(NodeNetworkInterface::new(
+            Arc::clone(&store),
+            ursa_service.command_sender(),
+            provider_engine.command_sender(),
+            Default::default(),
+            mempool_address,
+            abci_send,
+            ursa_service.event_broadcast(),
+            broadcast::channel(16).0,
+        ));
+        let server = Server::new(interface, auth());
+
+        let rpc = |body: Value| {
+            let rpc_app = server.rpc_app(Listener::Public);
+            async move {
+                let response = rpc_app
+                    .oneshot(
+                        Request::builder()
+                            .method(http::Method::POST)
+                            .uri("/rpc/v0")
+                            .header(http::header::CONTENT_TYPE, "application/json")
+                            .body(Body::from(serde_json::to_vec(&body).unwrap()))
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                let value = serde_json::from_slice::<Value>(&body).unwrap_or(Value::Null);
+                (status, value)
+            }
+        };
+
+        // a batch answers every request, in order, and skips notifications
+        let (status, value) = rpc(json!([
+            { "jsonrpc": "2.0", "method": "ursa_listener_addresses", "params": [], "id": 1 },
+            { "jsonrpc": "2.0", "method": "ursa_listener_addresses", "params": [] },
+            { "jsonrpc": "2.0", "method": "ursa_unknown", "params": [], "id": 2 },
+        ]))
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let responses = value.as_array().unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], json!(1));
+        assert_eq!(
+            responses[0]["result"],
+            json!(["/ip4/127.0.0.1/tcp/6009".to_string()])
+        );
+        assert_eq!(responses[1]["id"], json!(2));
+        assert_eq!(responses[1]["error"]["code"], json!(-32601));
+
+        // notifications get no response
+        let (status, _) = rpc(json!(
+            { "jsonrpc": "2.0", "method": "ursa_listener_addresses", "params": [] }
+        ))
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        // errors are full response objects with a matching status
+        let (status, value) = rpc(json!(
+            { "jsonrpc": "2.0", "method": "ursa_unknown", "params": [], "id": 3 }
+        ))
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(value["id"], json!(3));
+        assert_eq!(value["error"]["code"], json!(-32601));
+
+        let (status, value) = rpc(json!(
+            { "jsonrpc": "2.0", "method": "ursa_get_cid", "params": { "cid": "not a cid" }, "id": 4 }
+        ))
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(value["error"]["code"], json!(-32602));
+        Ok(())
+    }
+
+    fn auth() -> Auth {
+        Auth::new(Keypair::generate_ed25519().public(), Default::default())
+    }
+
+    async fn next(messages: &mut Receiver<String>) -> Value {
+        let message = timeout(Duration::from_secs(5), messages.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        serde_json::from_str(&message).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_rpc_websocket_subscriptions() -> Result<()> {
+        setup_logger();
+        let (ursa_service, provider_engine, store, mempool_address, abci_send) = init()?;
+        let network_events = ursa_service.event_broadcast();
+        let (new_blocks, _) = broadcast::channel(16);
+
+        let interface = Arc::new(NodeNetworkInterface::new(
+            Arc::clone(&store),
+            ursa_service.command_sender(),
+            provider_engine.command_sender(),
+            Default::default(),
+            mempool_address,
+            abci_send,
+            network_events.clone(),
+            new_blocks.clone(),
+        ));
+        let (out, mut messages) = Outbox::new(OUTBOUND_QUEUE);
+        let mut connection = Connection::new(
+            RpcServer::new(Arc::clone(&interface)),
+            interface,
+            Access::admin(),
+            out,
+        );
+
+        // regular methods are answered like over http
+        connection
+            .handle(
+                json!({ "jsonrpc": "2.0", "method": "ursa_listener_addresses", "params": [], "id": 1 })
+                    .to_string()
+                    .into(),
+            )
+            .await;
+        assert_eq!(
+            next(&mut messages).await["result"],
+            json!(["/ip4/127.0.0.1/tcp/6009".to_string()])
+        );
+
+        connection
+            .handle(
+                json!({ "jsonrpc": "2.0", "method": "ursa_subscribeNetworkEvents", "id": 2 })
+                    .to_string()
+                    .into(),
+            )
+            .await;
+        let events_id = next(&mut messages).await["result"].clone();
+        connection
+            .handle(
+                json!({ "jsonrpc": "2.0", "method": "ursa_subscribeNewBlocks", "id": 3 })
+                    .to_string()
+                    .into(),
+            )
+            .await;
+        let blocks_id = next(&mut messages).await["result"].clone();
+        assert_ne!(events_id, blocks_id);
+
+        let cid = Cid::from_str("bafkreihwcrnsi2tqozwq22k4vl7flutu43jlxgb3tenewysm2xvfuej5i4")?;
+        network_events
+            .send(NetworkEvent::PullComplete { cid, size: 5 })
+            .unwrap();
+        let message = next(&mut messages).await;
+        assert_eq!(message["method"], json!("ursa_subscription"));
+        assert_eq!(message["params"]["subscription"], events_id);
+        assert_eq!(
+            message["params"]["result"],
+            json!({ "type": "PullComplete", "cid": cid.to_string(), "size": 5 })
+        );
+
+        new_blocks.send(7).unwrap();
+        let message = next(&mut messages).await;
+        assert_eq!(message["params"]["subscription"], blocks_id);
+        assert_eq!(message["params"]["result"], json!({ "height": 7 }));
+
+        // unsubscribing stops the notifications
+        let unsubscribe = json!({
+            "jsonrpc": "2.0", "method": "ursa_unsubscribe", "params": [blocks_id], "id": 4
+        });
+        connection.handle(unsubscribe.to_string().into()).await;
+        assert_eq!(next(&mut messages).await["result"], json!(true));
+        connection.handle(unsubscribe.to_string().into()).await;
+        assert_eq!(next(&mut messages).await["result"], json!(false));
+
+        new_blocks.send(8).unwrap();
+        network_events
+            .send(NetworkEvent::PullComplete { cid, size: 5 })
+            .unwrap();
+        assert_eq!(
+            next(&mut messages).await["params"]["subscription"],
+            events_id
+        );
+        Ok(())
+    }
+
+    /// The next text message of a websocket client, parsed as json.
+    async fn receive<S>(socket: &mut S) -> Result<Value>
+    where
+        S: Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+    {
+        loop {
+            let message = timeout(Duration::from_secs(5), socket.next())
+                .await?
+                .expect("the connection is open")?;
+            if let Message::Text(text) = message {
+                return Ok(serde_json::from_str(&text)?);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rpc_websocket_upgrade() -> Result<()> {
+        setup_logger();
+        let server = MockServer::start(Arc::new(MockNetworkInterface::new()))?;
+        let (mut socket, _) = connect_async(format!("ws://{}/rpc/v0/ws", server.address())).await?;
+
+        let call = |method: &str, id: u64| {
+            Message::Text(json!({ "jsonrpc": "2.0", "method": method, "id": id }).to_string())
+        };
+        socket.send(call("ursa_subscribeNewBlocks", 1)).await?;
+        let response = receive(&mut socket).await?;
+        assert_eq!(response["id"], json!(1));
+        let subscription = response["result"].clone();
+
+        server.interface.emit_new_block(3);
+        let message = receive(&mut socket).await?;
+        assert_eq!(message["method"], json!("ursa_subscription"));
+        assert_eq!(message["params"]["subscription"], subscription);
+        assert_eq!(message["params"]["result"], json!({ "height": 3 }));
+
+        // regular methods are answered over the same socket
+        socket.send(call("ursa_listener_addresses", 2)).await?;
+        let response = receive(&mut socket).await?;
+        assert_eq!(response["id"], json!(2));
+        assert!(response["result"].is_array());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_progress_events() -> Result<()> {
+        setup_logger();
+        let interface = Arc::new(MockNetworkInterface::new());
+        let cid = Cid::from_str("bafkreihwcrnsi2tqozwq22k4vl7flutu43jlxgb3tenewysm2xvfuej5i4")?;
+        let app = mock::router(Arc::clone(&interface));
+        let uri = format!("/ursa/v0/progress/{cid}/events");
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let started = BitswapProgress {
+            blocks_missing: 1,
+            ..Default::default()
+        };
+        let fetched = BitswapProgress {
+            blocks_fetched: 1,
+            bytes_fetched: 11,
+            blocks_missing: 0,
+        };
+        interface.set_progress(cid, started).await;
+        let response = app
+            .oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[http::header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+
+        // the stream ends once the fetch finishes
+        interface.set_progress(cid, fetched).await;
+        interface.clear_progress(cid).await;
+        let body = timeout(
+            Duration::from_secs(5),
+            hyper::body::to_bytes(response.into_body()),
+        )
+        .await??;
+        let events: Vec<BitswapProgress> = String::from_utf8(body.to_vec())?
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| serde_json::from_str(data.trim()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(events, vec![started, fetched]);
+        Ok(())
+    }
 }
@@ -0,0 +1,163 @@
+use db::{rocks::RocksDb, rocks_config::RocksDbConfig, Error, Store};
+use fvm_ipld_blockstore::Blockstore;
+use libipld::Cid;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// Blocks larger than this are written to their own file.
+const INLINE_LIMIT: usize = 64 * 1024;
+
+/// Suffix of the temporary files of the writes in flight.
+static TMP_SUFFIX: AtomicU64 = AtomicU64::new(0);
+
+/// Stores large blocks as flat files and keeps small blocks and
+/// everything that is not a block in RocksDB.
+#[derive(Clone)]
+pub struct FlatFileDb {
+    index: RocksDb,
+    blobs: Arc<PathBuf>,
+}
+
+impl FlatFileDb {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let index = RocksDb::open(path.join("index"), &RocksDbConfig::default())?;
+        let blobs = path.join("blobs");
+        fs::create_dir_all(&blobs)?;
+
+        Ok(Self {
+            index,
+            blobs: Arc::new(blobs),
+        })
+    }
+
+    /// Path of the file holding a block, sharded by the last characters of the cid.
+    fn blob_path(&self, key: &[u8]) -> Option<PathBuf> {
+        let name = Cid::try_from(key).ok()?.to_string();
+        let shard = &name[name.len().saturating_sub(2)..];
+        Some(self.blobs.join(shard).join(name))
+    }
+
+    fn write_blob(path: &Path, value: &[u8]) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // write to a temporary file first so readers never see a partial block, named
+        // uniquely so concurrent writers of the same block never share it
+        let suffix = TMP_SUFFIX.fetch_add(1, Ordering::Relaxed);
+        let tmp = path.with_extension(format!("{}.{suffix}.tmp", process::id()));
+        fs::write(&tmp, value)?;
+        fs::rename(tmp, path)
+    }
+}
+
+impl FlatFileDb {
+    /// Call `f` with every key and value in the index, then with every block stored as a file.
+    pub fn for_each<F>(&self, mut f: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&[u8], Vec<u8>) -> anyhow::Result<()>,
+    {
+        let mut iter = self.index.db.raw_iterator();
+        iter.seek_to_first();
+        while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+            f(key, value.to_vec())?;
+            iter.next();
+        }
+        iter.status()?;
+
+        for shard in fs::read_dir(self.blobs.as_ref())? {
+            for entry in fs::read_dir(shard?.path())? {
+                let path = entry?.path();
+                let cid = match path.file_name().and_then(|name| name.to_str()) {
+                    Some(name) => match Cid::from_str(name) {
+                        Ok(cid) => cid,
+                        // leftover temporary file of an interrupted write
+                        Err(_) => continue,
+                    },
+                    None => continue,
+                };
+                f(&cid.to_bytes(), fs::read(&path)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn io_error(e: io::Error) -> Error {
+    Error::Other(e.to_string())
+}
+
+impl Store for FlatFileDb {
+    fn read<K>(&self, key: K) -> Result<Option<Vec<u8>>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        if let Some(value) = self.index.read(key.as_ref())? {
+            return Ok(Some(value));
+        }
+        match self.blob_path(key.as_ref()).map(fs::read) {
+            Some(Ok(value)) => Ok(Some(value)),
+            Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => Err(io_error(e)),
+            _ => Ok(None),
+        }
+    }
+
+    fn write<K, V>(&self, key: K, value: V) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        match self.blob_path(key.as_ref()) {
+            Some(path) if value.as_ref().len() > INLINE_LIMIT => {
+                Self::write_blob(&path, value.as_ref()).map_err(io_error)
+            }
+            _ => self.index.write(key, value),
+        }
+    }
+
+    fn delete<K>(&self, key: K) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        if let Some(path) = self.blob_path(key.as_ref()) {
+            match fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(io_error(e)),
+                _ => {}
+            }
+        }
+        self.index.delete(key)
+    }
+
+    fn exists<K>(&self, key: K) -> Result<bool, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        Ok(self.index.exists(key.as_ref())?
+            || self
+                .blob_path(key.as_ref())
+                .map(|path| path.exists())
+                .unwrap_or(false))
+    }
+}
+
+impl Blockstore for FlatFileDb {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read(k.to_bytes()).map_err(|e| e.into())
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.write(k.to_bytes(), block).map_err(|e| e.into())
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        self.exists(k.to_bytes()).map_err(|e| e.into())
+    }
+}
@@ -0,0 +1,185 @@
+//! # Storage backends.
+//!
+//! [`Backend`] wraps the databases ursa can store blocks in behind a single type
+//! implementing [`Blockstore`] and [`Store`], so the node can pick one at runtime
+//! from its configuration. Every backend but the in-memory one sits behind a cargo feature.
+
+#[cfg(feature = "flatfile")]
+mod flatfile;
+#[cfg(feature = "paritydb")]
+mod paritydb;
+
+use anyhow::{anyhow, Result};
+use db::{Error, MemoryDB, Store};
+use fvm_ipld_blockstore::Blockstore;
+use libipld::Cid;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[cfg(feature = "flatfile")]
+pub use self::flatfile::FlatFileDb;
+#[cfg(feature = "paritydb")]
+pub use self::paritydb::ParityDb;
+#[cfg(feature = "rocksdb")]
+use db::{rocks::RocksDb, rocks_config::RocksDbConfig};
+
+/// Storage backend of the blockstore.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    /// Keep everything in memory. Content is lost on restart.
+    Memory,
+    /// RocksDB.
+    #[default]
+    RocksDb,
+    /// Large blocks as flat files, with small blocks and the index in RocksDB.
+    FlatFile,
+    /// ParityDB.
+    ParityDb,
+}
+
+#[derive(Clone)]
+pub enum Backend {
+    Memory(MemoryDB),
+    #[cfg(feature = "rocksdb")]
+    RocksDb(RocksDb),
+    #[cfg(feature = "flatfile")]
+    FlatFile(FlatFileDb),
+    #[cfg(feature = "paritydb")]
+    ParityDb(ParityDb),
+}
+
+impl Backend {
+    /// Open the backend at `path`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(backend: StorageBackend, path: P) -> Result<Self> {
+        let path = path.as_ref();
+        match backend {
+            StorageBackend::Memory => Ok(Backend::Memory(MemoryDB::default())),
+            #[cfg(feature = "rocksdb")]
+            StorageBackend::RocksDb => Ok(Backend::RocksDb(RocksDb::open(
+                path,
+                &RocksDbConfig::default(),
+            )?)),
+            #[cfg(feature = "flatfile")]
+            StorageBackend::FlatFile => Ok(Backend::FlatFile(FlatFileDb::open(path)?)),
+            #[cfg(feature = "paritydb")]
+            StorageBackend::ParityDb => Ok(Backend::ParityDb(ParityDb::open(path)?)),
+            #[allow(unreachable_patterns)]
+            backend => Err(anyhow!(
+                "ursa was built without support for the {backend:?} storage backend"
+            )),
+        }
+    }
+}
+
+impl Backend {
+    /// Call `f` with every block in the store. Entries that are not blocks are skipped.
+    pub fn for_each_block<F>(&self, mut f: F) -> Result<()>
+    where
+        F: FnMut(Cid, Vec<u8>) -> Result<()>,
+    {
+        let mut f = |key: &[u8], value: Vec<u8>| match Cid::try_from(key) {
+            Ok(cid) => f(cid, value),
+            Err(_) => Ok(()),
+        };
+        match self {
+            Backend::Memory(_) => Err(anyhow!(
+                "walking the memory storage backend is not supported"
+            )),
+            #[cfg(feature = "rocksdb")]
+            Backend::RocksDb(db) => {
+                let mut iter = db.db.raw_iterator();
+                iter.seek_to_first();
+                while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+                    f(key, value.to_vec())?;
+                    iter.next();
+                }
+                Ok(iter.status()?)
+            }
+            #[cfg(feature = "flatfile")]
+            Backend::FlatFile(db) => db.for_each(f),
+            #[cfg(feature = "paritydb")]
+            Backend::ParityDb(db) => db.for_each(f),
+        }
+    }
+}
+
+macro_rules! dispatch {
+    ($self:ident, $db:ident => $e:expr) => {
+        match $self {
+            Backend::Memory($db) => $e,
+            #[cfg(feature = "rocksdb")]
+            Backend::RocksDb($db) => $e,
+            #[cfg(feature = "flatfile")]
+            Backend::FlatFile($db) => $e,
+            #[cfg(feature = "paritydb")]
+            Backend::ParityDb($db) => $e,
+        }
+    };
+}
+
+impl Store for Backend {
+    fn read<K>(&self, key: K) -> Result<Option<Vec<u8>>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        dispatch!(self, db => db.read(key))
+    }
+
+    fn write<K, V>(&self, key: K, value: V) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        dispatch!(self, db => db.write(key, value))
+    }
+
+    fn delete<K>(&self, key: K) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        dispatch!(self, db => db.delete(key))
+    }
+
+    fn exists<K>(&self, key: K) -> Result<bool, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        dispatch!(self, db => db.exists(key))
+    }
+
+    fn bulk_write<K, V>(&self, values: &[(K, V)]) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        dispatch!(self, db => db.bulk_write(values))
+    }
+}
+
+impl Blockstore for Backend {
+    fn get(&self, k: &Cid) -> Result<Option<Vec<u8>>> {
+        dispatch!(self, db => db.get(k))
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
+        dispatch!(self, db => db.put_keyed(k, block))
+    }
+
+    fn has(&self, k: &Cid) -> Result<bool> {
+        dispatch!(self, db => db.has(k))
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        dispatch!(self, db => db.put_many_keyed(blocks))
+    }
+}
+
+#[cfg(test)]
+#[path = "../tests/backend_tests.rs"]
+mod backend_tests;
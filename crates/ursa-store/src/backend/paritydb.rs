@@ -0,0 +1,121 @@
+use db::{Error, Store};
+use fvm_ipld_blockstore::Blockstore;
+use libipld::Cid;
+use parity_db::{Db, Options};
+use std::{path::Path, sync::Arc};
+
+/// The single column everything is stored in.
+const COLUMN: u8 = 0;
+
+#[derive(Clone)]
+pub struct ParityDb {
+    db: Arc<Db>,
+}
+
+impl ParityDb {
+    pub fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let options = Self::options(path.as_ref());
+        let db = Db::open_or_create(&options).map_err(|e| anyhow::anyhow!("{e}"))?;
+
+        Ok(Self { db: Arc::new(db) })
+    }
+
+    /// Layout of the database: a single column with a btree index, which keeps the keys so
+    /// the store can be walked.
+    fn options(path: &Path) -> Options {
+        let mut options = Options::with_columns(path, 1);
+        options.columns[COLUMN as usize].btree_index = true;
+        options
+    }
+}
+
+impl ParityDb {
+    /// Call `f` with every key and value in the store.
+    pub fn for_each<F>(&self, mut f: F) -> anyhow::Result<()>
+    where
+        F: FnMut(&[u8], Vec<u8>) -> anyhow::Result<()>,
+    {
+        let mut iter = self.db.iter(COLUMN).map_err(db_error)?;
+        iter.seek_to_first().map_err(db_error)?;
+        while let Some((key, value)) = iter.next().map_err(db_error)? {
+            f(&key, value)?;
+        }
+        Ok(())
+    }
+}
+
+fn db_error(e: parity_db::Error) -> Error {
+    Error::Other(e.to_string())
+}
+
+impl Store for ParityDb {
+    fn read<K>(&self, key: K) -> Result<Option<Vec<u8>>, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.db.get(COLUMN, key.as_ref()).map_err(db_error)
+    }
+
+    fn write<K, V>(&self, key: K, value: V) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.db
+            .commit([(COLUMN, key.as_ref(), Some(value.as_ref().to_vec()))])
+            .map_err(db_error)
+    }
+
+    fn delete<K>(&self, key: K) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.db
+            .commit([(COLUMN, key.as_ref(), None)])
+            .map_err(db_error)
+    }
+
+    fn exists<K>(&self, key: K) -> Result<bool, Error>
+    where
+        K: AsRef<[u8]>,
+    {
+        self.read(key).map(|value| value.is_some())
+    }
+
+    fn bulk_write<K, V>(&self, values: &[(K, V)]) -> Result<(), Error>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        self.db
+            .commit(
+                values
+                    .iter()
+                    .map(|(key, value)| (COLUMN, key.as_ref(), Some(value.as_ref().to_vec()))),
+            )
+            .map_err(db_error)
+    }
+}
+
+impl Blockstore for ParityDb {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read(k.to_bytes()).map_err(|e| e.into())
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.write(k.to_bytes(), block).map_err(|e| e.into())
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        let values: Vec<_> = blocks
+            .into_iter()
+            .map(|(cid, block)| (cid.to_bytes(), block))
+            .collect();
+        self.bulk_write(&values).map_err(|e| e.into())
+    }
+}
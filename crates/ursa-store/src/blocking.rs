@@ -0,0 +1,116 @@
+//! # Async store access.
+//!
+//! Every database call of [`UrsaStore`] is blocking. [`AsyncStore`] runs them on tokio's
+//! blocking pool instead of the async worker threads, and bounds the number of calls in
+//! flight so a burst of requests queues up instead of exhausting the pool.
+
+use anyhow::{anyhow, Result};
+use db::Store;
+use futures::AsyncRead;
+use fvm_ipld_blockstore::Blockstore;
+use libipld::Cid;
+use std::sync::Arc;
+use tokio::{sync::Semaphore, task};
+
+use crate::{load_car_batched, UrsaStore};
+
+/// Default number of blocking store calls that can run at the same time.
+pub const DEFAULT_MAX_BLOCKING_TASKS: usize = 64;
+
+/// Non-blocking handle to an [`UrsaStore`].
+pub struct AsyncStore<S> {
+    store: Arc<UrsaStore<S>>,
+    permits: Arc<Semaphore>,
+}
+
+impl<S> Clone for AsyncStore<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: Arc::clone(&self.store),
+            permits: Arc::clone(&self.permits),
+        }
+    }
+}
+
+impl<S> AsyncStore<S>
+where
+    S: Blockstore + Store + Send + Sync + 'static,
+{
+    pub fn new(store: Arc<UrsaStore<S>>) -> Self {
+        Self::with_max_blocking_tasks(store, DEFAULT_MAX_BLOCKING_TASKS)
+    }
+
+    pub fn with_max_blocking_tasks(store: Arc<UrsaStore<S>>, max_blocking_tasks: usize) -> Self {
+        Self {
+            store,
+            permits: Arc::new(Semaphore::new(max_blocking_tasks)),
+        }
+    }
+
+    /// The wrapped synchronous store.
+    pub fn inner(&self) -> &Arc<UrsaStore<S>> {
+        &self.store
+    }
+
+    /// Run `f` on the blocking pool once a permit is available.
+    pub async fn run<F, T>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&UrsaStore<S>) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let permit = Arc::clone(&self.permits).acquire_owned().await?;
+        let store = Arc::clone(&self.store);
+        task::spawn_blocking(move || {
+            let _permit = permit;
+            f(&store)
+        })
+        .await
+        .map_err(|e| anyhow!("Blocking store task failed: {e}"))?
+    }
+
+    pub async fn get(&self, cid: Cid) -> Result<Option<Vec<u8>>> {
+        self.run(move |store| store.get_block(&cid)).await
+    }
+
+    pub async fn has(&self, cid: Cid) -> Result<bool> {
+        self.run(move |store| store.has_block(&cid)).await
+    }
+
+    pub async fn put_block(&self, cid: Cid, data: Vec<u8>) -> Result<()> {
+        self.run(move |store| store.put_block(&cid, &data)).await
+    }
+
+    pub async fn put_blocks(&self, blocks: Vec<(Cid, Vec<u8>)>) -> Result<()> {
+        self.run(move |store| store.put_blocks(blocks)).await
+    }
+
+    pub async fn delete_block(&self, cid: Cid) -> Result<()> {
+        self.run(move |store| store.delete_block(&cid)).await
+    }
+
+    pub async fn dag_traversal(&self, root_cid: Cid) -> Result<Vec<(Cid, Vec<u8>)>> {
+        self.run(move |store| store.dag_traversal(&root_cid)).await
+    }
+
+    pub async fn car_size(&self, root_cid: Cid) -> Result<u64> {
+        self.run(move |store| store.car_size(&root_cid)).await
+    }
+
+    pub async fn insert_car_size(&self, root_cid: Cid, dag: Vec<(Cid, usize)>) -> Result<u64> {
+        self.run(move |store| store.insert_car_size(&root_cid, &dag))
+            .await
+    }
+
+    /// Load a car file into the store, verifying every block and writing them in batches.
+    /// Returns the roots of the car file.
+    pub async fn load_car<R>(&self, reader: R) -> Result<Vec<Cid>>
+    where
+        R: AsyncRead + Send + Unpin,
+    {
+        load_car_batched(reader, |blocks| self.put_blocks(blocks)).await
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/blocking_tests.rs"]
+mod blocking_tests;
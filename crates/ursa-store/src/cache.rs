@@ -0,0 +1,85 @@
+//! # Hot block cache.
+//!
+//! A bounded in-memory cache of recently read blocks, weighted by block size, so popular
+//! content does not hit the database on every request. Reads don't lock: a miss only fills
+//! the cache if no block was deleted since the read started, tracked by a generation that
+//! every delete bumps.
+
+use libipld::Cid;
+use metrics::increment_counter;
+use moka::sync::Cache;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+pub(crate) struct BlockCache {
+    inner: Cache<Cid, Arc<[u8]>>,
+    /// Bumped by every delete.
+    generation: AtomicU64,
+    /// Makes the generation check and the insert of a fill atomic with respect to deletes.
+    fills: Mutex<()>,
+}
+
+impl BlockCache {
+    pub fn new(max_bytes: u64) -> Self {
+        Self {
+            inner: Cache::builder()
+                .max_capacity(max_bytes)
+                .weigher(|_cid, data: &Arc<[u8]>| data.len().try_into().unwrap_or(u32::MAX))
+                .build(),
+            generation: AtomicU64::new(0),
+            fills: Mutex::new(()),
+        }
+    }
+
+    /// Generation to pass to [`BlockCache::fill`] after reading a block from the database.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    pub fn get(&self, cid: &Cid) -> Option<Vec<u8>> {
+        match self.inner.get(cid) {
+            Some(data) => {
+                increment_counter!("store_block_cache_hits");
+                Some(data.to_vec())
+            }
+            None => {
+                increment_counter!("store_block_cache_misses");
+                None
+            }
+        }
+    }
+
+    pub fn contains(&self, cid: &Cid) -> bool {
+        self.inner.contains_key(cid)
+    }
+
+    /// Cache a block read from the database, unless a block was deleted since `generation`
+    /// was taken, in which case the read may predate the delete.
+    pub fn fill(&self, cid: Cid, data: &[u8], generation: u64) {
+        let _lock = self.fills.lock().unwrap();
+        if self.generation.load(Ordering::Acquire) == generation {
+            self.inner.insert(cid, Arc::from(data));
+        }
+    }
+
+    /// Drop a block once its delete is committed to the database.
+    pub fn invalidate(&self, cid: &Cid) {
+        let _lock = self.fills.lock().unwrap();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.inner.invalidate(cid);
+    }
+}
+
+impl fmt::Debug for BlockCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockCache")
+            .field("entries", &self.inner.entry_count())
+            .field("bytes", &self.inner.weighted_size())
+            .finish()
+    }
+}
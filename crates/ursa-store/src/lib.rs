@@ -1,5 +1,16 @@
+mod backend;
+mod blocking;
+mod cache;
+mod path;
+mod selection;
+mod stats;
 mod store;
 
+pub use self::backend::*;
+pub use self::blocking::*;
+pub use self::path::PathError;
+pub use self::selection::*;
+pub use self::stats::StoreStats;
 pub use self::store::*;
 #[cfg(test)]
 mod tests;
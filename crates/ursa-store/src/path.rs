@@ -0,0 +1,120 @@
+//! # IPLD path resolution.
+//!
+//! Resolves paths like `<cid>/assets/logo.png` to the cid of the sub-dag they point to.
+//! UnixFS directories (dag-pb) are walked by link name, dag-cbor and dag-json nodes by
+//! map key or list index. Sharded (HAMT) UnixFS directories are not supported.
+
+use db::Store;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::DAG_CBOR;
+use libipld::{
+    cbor::DagCborCodec, codec::Codec, json::DagJsonCodec, pb::DagPbCodec, Cid, Ipld, Result,
+};
+use thiserror::Error;
+
+use crate::{UrsaStore, DAG_JSON, DAG_PB};
+
+#[derive(Debug, Error)]
+pub enum PathError {
+    #[error("block {0} is not in the store")]
+    MissingBlock(Cid),
+    #[error("no link named {segment:?} in {cid}")]
+    NotFound { cid: Cid, segment: String },
+    #[error("can't traverse {cid} with codec {codec:#x}")]
+    UnsupportedCodec { cid: Cid, codec: u64 },
+    #[error("path {path:?} points inside block {cid} instead of to a block")]
+    NotALink { cid: Cid, path: String },
+}
+
+impl<S> UrsaStore<S>
+where
+    S: Blockstore + Store + Send + Sync + 'static,
+{
+    /// Resolve `path` relative to `root` and return the cid it points to.
+    /// An empty path resolves to `root` itself.
+    pub fn resolve_path(&self, root: &Cid, path: &str) -> Result<Cid> {
+        let mut segments = path.split('/').filter(|s| !s.is_empty()).peekable();
+        let mut cid = *root;
+
+        while segments.peek().is_some() {
+            let data = self.get_block(&cid)?.ok_or(PathError::MissingBlock(cid))?;
+
+            let mut node: Ipld = match cid.codec() {
+                DAG_PB => DagPbCodec.decode(&data)?,
+                DAG_CBOR => DagCborCodec.decode(&data)?,
+                DAG_JSON => DagJsonCodec.decode(&data)?,
+                codec => return Err(PathError::UnsupportedCodec { cid, codec }.into()),
+            };
+
+            // walk inside the block until the path reaches a link
+            let mut walked = Vec::new();
+            loop {
+                let segment = match segments.next() {
+                    Some(segment) => segment,
+                    None => {
+                        return Err(PathError::NotALink {
+                            cid,
+                            path: walked.join("/"),
+                        }
+                        .into())
+                    }
+                };
+                walked.push(segment);
+
+                let next = if cid.codec() == DAG_PB {
+                    pb_link(&node, segment)
+                } else {
+                    child(&node, segment)
+                };
+                match next {
+                    Some(Ipld::Link(link)) => {
+                        cid = link;
+                        break;
+                    }
+                    Some(child) if cid.codec() != DAG_PB => node = child,
+                    _ => {
+                        return Err(PathError::NotFound {
+                            cid,
+                            segment: segment.to_string(),
+                        }
+                        .into())
+                    }
+                }
+            }
+        }
+        Ok(cid)
+    }
+}
+
+/// Find the link named `name` in a decoded dag-pb node.
+fn pb_link(node: &Ipld, name: &str) -> Option<Ipld> {
+    let links = match node {
+        Ipld::Map(node) => match node.get("Links") {
+            Some(Ipld::List(links)) => links,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    links.iter().find_map(|link| match link {
+        Ipld::Map(link) if matches!(link.get("Name"), Some(Ipld::String(n)) if n == name) => {
+            link.get("Hash").cloned()
+        }
+        _ => None,
+    })
+}
+
+/// Get a map entry or list element of a decoded dag-cbor or dag-json node.
+fn child(node: &Ipld, segment: &str) -> Option<Ipld> {
+    match node {
+        Ipld::Map(map) => map.get(segment).cloned(),
+        Ipld::List(list) => segment
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| list.get(index).cloned()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/path_tests.rs"]
+mod path_tests;
@@ -0,0 +1,342 @@
+//! # Partial dag selection.
+//!
+//! Selects part of a dag so it can be exported without the rest, either with an IPLD
+//! selector or with the `dag-scope` values of the trustless gateway spec:
+//!
+//! - `all`: every block of the dag.
+//! - `entity`: the blocks needed to read the root entity, the whole file for a UnixFS file
+//!   and only the root block for anything else.
+//! - `block`: only the root block.
+//!
+//! A `depth` limits the selection to the blocks at most that many links below the root.
+
+use anyhow::anyhow;
+use db::Store;
+use fnv::FnvHashSet;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::DAG_CBOR;
+use ipld_traversal::{selector::RecursionLimit, Selector};
+use libipld::{
+    cbor::DagCborCodec, codec::Codec, json::DagJsonCodec, pb::DagPbCodec, serde::from_ipld,
+    store::DefaultParams, Block, Cid, Ipld, Result,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::{UrsaStore, DAG_JSON, DAG_PB};
+
+/// UnixFS node type of files.
+const UNIXFS_FILE: u8 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DagScope {
+    All,
+    Entity,
+    Block,
+}
+
+impl FromStr for DagScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "all" => Ok(DagScope::All),
+            "entity" => Ok(DagScope::Entity),
+            "block" => Ok(DagScope::Block),
+            _ => Err(anyhow!(
+                "Invalid dag-scope {s:?}, expected all, entity or block"
+            )),
+        }
+    }
+}
+
+/// The part of a dag to return. Selects the whole dag by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DagSelection {
+    #[serde(default, rename = "dag-scope", skip_serializing_if = "Option::is_none")]
+    pub dag_scope: Option<DagScope>,
+    /// Only include blocks at most this many links below the root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<u64>,
+    /// An IPLD selector encoded as dag-json, takes precedence over the other fields.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+}
+
+/// State of the innermost `ExploreRecursive` selector.
+#[derive(Clone, Copy)]
+struct Recursion<'a> {
+    sequence: &'a Selector,
+    remaining: Option<u64>,
+}
+
+impl<S> UrsaStore<S>
+where
+    S: Blockstore + Store + Send + Sync + 'static,
+{
+    /// Get the blocks of the dag under `root` picked by `selection`, root first.
+    pub fn select(&self, root: &Cid, selection: &DagSelection) -> Result<Vec<(Cid, Vec<u8>)>> {
+        if let Some(selector) = &selection.selector {
+            let ipld: Ipld = DagJsonCodec.decode(selector.as_bytes())?;
+            let selector: Selector = from_ipld(ipld)?;
+            let mut walk = Walk {
+                store: self,
+                seen: FnvHashSet::default(),
+                visited: FnvHashSet::default(),
+                blocks: Vec::new(),
+            };
+            walk.run(*root, &selector)?;
+            return Ok(walk.blocks);
+        }
+
+        let depth = match selection.dag_scope.unwrap_or(DagScope::All) {
+            DagScope::All => selection.depth,
+            DagScope::Entity if self.is_unixfs_file(root)? => selection.depth,
+            DagScope::Entity | DagScope::Block => Some(0),
+        };
+        match depth {
+            None => self.dag_traversal(root),
+            Some(depth) => self.dag_traversal_depth(root, depth),
+        }
+    }
+
+    /// Like `dag_traversal`, but only the blocks at most `depth` links below the root.
+    fn dag_traversal_depth(&self, root: &Cid, depth: u64) -> Result<Vec<(Cid, Vec<u8>)>> {
+        let mut res = Vec::new();
+        let mut seen = FnvHashSet::default();
+        let mut level = vec![*root];
+
+        for current_depth in 0..=depth {
+            let mut next = Vec::new();
+            for cid in level {
+                if !seen.insert(cid) {
+                    continue;
+                }
+                let data = self
+                    .get_block(&cid)?
+                    .ok_or_else(|| anyhow!("The block with cid {cid} is missing"))?;
+                if current_depth < depth {
+                    Block::<DefaultParams>::new(cid, data.clone())?.references(&mut next)?;
+                }
+                res.push((cid, data));
+            }
+            if next.is_empty() {
+                break;
+            }
+            level = next;
+        }
+        Ok(res)
+    }
+
+    fn is_unixfs_file(&self, cid: &Cid) -> Result<bool> {
+        if cid.codec() != DAG_PB {
+            return Ok(false);
+        }
+        let data = self
+            .get_block(cid)?
+            .ok_or_else(|| anyhow!("The block with cid {cid} is missing"))?;
+        let node: Ipld = DagPbCodec.decode(&data)?;
+        // the unixfs type is the first field of the `Data` protobuf message
+        Ok(match node {
+            Ipld::Map(node) => matches!(
+                node.get("Data"),
+                Some(Ipld::Bytes(data)) if data.len() > 1 && data[0] == 0x08 && data[1] == UNIXFS_FILE
+            ),
+            _ => false,
+        })
+    }
+}
+
+/// A node still to be walked and the selector to apply to it.
+struct Step<'s> {
+    node: Ipld,
+    selector: &'s Selector,
+    recursion: Option<Recursion<'s>>,
+}
+
+/// Identifies a link visited with a selector, selectors by their address in the parsed tree.
+type Visit = (Cid, usize, Option<(usize, Option<u64>)>);
+
+struct Walk<'a, S> {
+    store: &'a UrsaStore<S>,
+    seen: FnvHashSet<Cid>,
+    visited: FnvHashSet<Visit>,
+    blocks: Vec<(Cid, Vec<u8>)>,
+}
+
+impl<'a, S> Walk<'a, S>
+where
+    S: Blockstore + Store + Send + Sync + 'static,
+{
+    /// Apply `selector` to the dag under `root`, depth first with an explicit stack so deep
+    /// dags can't overflow the call stack.
+    fn run<'s>(&mut self, root: Cid, selector: &'s Selector) -> Result<()> {
+        let mut stack = vec![Step {
+            node: Ipld::Link(root),
+            selector,
+            recursion: None,
+        }];
+        while let Some(Step {
+            node,
+            selector,
+            recursion,
+        }) = stack.pop()
+        {
+            // an edge past the recursion limit isn't followed
+            if matches!(selector, Selector::ExploreRecursiveEdge)
+                && matches!(recursion, Some(r) if r.remaining == Some(0))
+            {
+                continue;
+            }
+            let node = match node {
+                Ipld::Link(cid) => {
+                    let visit = (
+                        cid,
+                        selector as *const Selector as usize,
+                        recursion.map(|r| (r.sequence as *const Selector as usize, r.remaining)),
+                    );
+                    if !self.visited.insert(visit) {
+                        continue;
+                    }
+                    self.load(cid)?
+                }
+                node => node,
+            };
+            let mut steps = Vec::new();
+            explore(&node, selector, recursion, &mut steps)?;
+            stack.extend(steps.into_iter().rev());
+        }
+        Ok(())
+    }
+
+    /// Load and decode the block of `cid`, adding it to the selection.
+    fn load(&mut self, cid: Cid) -> Result<Ipld> {
+        let data = self
+            .store
+            .get_block(&cid)?
+            .ok_or_else(|| anyhow!("The block with cid {cid} is missing"))?;
+        let block: Ipld = match cid.codec() {
+            DAG_PB => DagPbCodec.decode(&data)?,
+            DAG_CBOR => DagCborCodec.decode(&data)?,
+            DAG_JSON => DagJsonCodec.decode(&data)?,
+            _ => Ipld::Bytes(Vec::new()),
+        };
+        if self.seen.insert(cid) {
+            self.blocks.push((cid, data));
+        }
+        Ok(block)
+    }
+}
+
+/// Apply `selector` to `node`, collecting the children to walk next in order.
+fn explore<'s>(
+    node: &Ipld,
+    selector: &'s Selector,
+    recursion: Option<Recursion<'s>>,
+    steps: &mut Vec<Step<'s>>,
+) -> Result<()> {
+    let mut step = |node: &Ipld, selector: &'s Selector| {
+        steps.push(Step {
+            node: node.clone(),
+            selector,
+            recursion,
+        })
+    };
+    match selector {
+        Selector::Matcher => {}
+        Selector::ExploreAll { next } => {
+            for child in children(node) {
+                step(child, next);
+            }
+        }
+        Selector::ExploreFields { fields } => {
+            if let Ipld::Map(map) = node {
+                for (field, next) in fields.iter() {
+                    if let Some(child) = map.get(field) {
+                        step(child, next);
+                    }
+                }
+            }
+        }
+        Selector::ExploreIndex { index, next } => {
+            if let Ipld::List(list) = node {
+                if let Some(child) = list.get(*index as usize) {
+                    step(child, next);
+                }
+            }
+        }
+        Selector::ExploreRange { start, end, next } => {
+            if let Ipld::List(list) = node {
+                for child in list.iter().take(*end as usize).skip(*start as usize) {
+                    step(child, next);
+                }
+            }
+        }
+        Selector::ExploreUnion(selectors) => {
+            for selector in selectors {
+                explore(node, selector, recursion, steps)?;
+            }
+        }
+        Selector::ExploreRecursive {
+            limit, sequence, ..
+        } => {
+            if reaches_edge(sequence) {
+                return Err(anyhow!(
+                    "ExploreRecursive must explore a child before reaching its edge"
+                ));
+            }
+            // like go-ipld-prime, a depth limit counts the nodes on a path, links or not,
+            // including the one the recursion starts at
+            let remaining = match limit {
+                RecursionLimit::None => None,
+                RecursionLimit::Depth(depth) => Some((*depth as u64).saturating_sub(1)),
+            };
+            let recursion = Recursion {
+                sequence,
+                remaining,
+            };
+            explore(node, sequence, Some(recursion), steps)?;
+        }
+        Selector::ExploreRecursiveEdge => match recursion {
+            Some(Recursion {
+                sequence,
+                remaining,
+            }) => {
+                let recursion = Recursion {
+                    sequence,
+                    remaining: remaining.map(|r| r.saturating_sub(1)),
+                };
+                explore(node, sequence, Some(recursion), steps)?;
+            }
+            None => return Err(anyhow!("ExploreRecursiveEdge outside of ExploreRecursive")),
+        },
+        #[allow(unreachable_patterns)]
+        _ => return Err(anyhow!("Unsupported selector {selector:?}")),
+    }
+    Ok(())
+}
+
+/// Whether `selector` reaches an `ExploreRecursiveEdge` without exploring a child first,
+/// which would apply the recursion to the same node forever.
+fn reaches_edge(selector: &Selector) -> bool {
+    match selector {
+        Selector::ExploreRecursiveEdge => true,
+        Selector::ExploreUnion(selectors) => selectors.iter().any(reaches_edge),
+        _ => false,
+    }
+}
+
+/// Direct children of a node, map values in key order and list elements in order.
+fn children(node: &Ipld) -> Vec<&Ipld> {
+    match node {
+        Ipld::Map(map) => map.values().collect(),
+        Ipld::List(list) => list.iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+#[path = "tests/selection_tests.rs"]
+mod selection_tests;
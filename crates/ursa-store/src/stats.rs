@@ -0,0 +1,173 @@
+//! # Store statistics.
+//!
+//! Block and byte counters are updated on every write and delete that goes through
+//! [`UrsaStore`](crate::UrsaStore), exported as prometheus gauges labelled with the name of
+//! the store and persisted in the store itself every [`PERSIST_INTERVAL`] updates. A marker
+//! is written on the first update after the statistics were persisted and removed when they
+//! are persisted on shutdown, so counters left stale by a crash are recounted on the next
+//! start. Blocks written to the underlying database directly are not accounted for.
+//!
+//! The car file sizes of roots are cached alongside, and dropped on any delete since the
+//! deleted block may belong to their dag. Blocks are only deleted by repairs and explicit
+//! removals, the sizes are recomputed on demand.
+
+use libipld::Cid;
+use metrics::{gauge, Label};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex, MutexGuard, RwLock,
+    },
+};
+
+/// Key the statistics are persisted under.
+pub(crate) const STATS_KEY: &[u8] = b"/ursa/store/stats";
+/// Key present while the counters changed since they were last persisted on shutdown.
+pub(crate) const STATS_DIRTY_KEY: &[u8] = b"/ursa/store/stats_dirty";
+/// Number of updates after which the statistics are persisted.
+pub(crate) const PERSIST_INTERVAL: u64 = 1000;
+
+/// Snapshot of the content held by a node.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct StoreStats {
+    /// Number of blocks in the store.
+    pub blocks: u64,
+    /// Total size of the blocks in the store.
+    pub bytes: u64,
+    /// Car file size of the roots whose size has been computed.
+    pub roots: BTreeMap<String, u64>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Accounting {
+    /// Name of the store in the gauges.
+    name: RwLock<&'static str>,
+    blocks: AtomicU64,
+    bytes: AtomicU64,
+    roots: RwLock<HashMap<Cid, u64>>,
+    updates: AtomicU64,
+    /// Whether the counters changed since they were persisted on shutdown.
+    dirty: AtomicBool,
+    writes: Mutex<()>,
+}
+
+impl Accounting {
+    pub fn from_stats(stats: StoreStats) -> Self {
+        let roots = stats
+            .roots
+            .iter()
+            .filter_map(|(cid, size)| Cid::from_str(cid).ok().map(|cid| (cid, *size)))
+            .collect();
+        Self {
+            name: RwLock::new("content"),
+            blocks: AtomicU64::new(stats.blocks),
+            bytes: AtomicU64::new(stats.bytes),
+            roots: RwLock::new(roots),
+            updates: AtomicU64::new(0),
+            dirty: AtomicBool::new(false),
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// Name the store in the gauges.
+    pub fn set_name(&self, name: &'static str) {
+        *self.name.write().unwrap() = name;
+        self.update_gauges();
+    }
+
+    /// Replace the counters with the ones of a full walk of the store.
+    pub fn reset(&self, blocks: u64, bytes: u64) {
+        self.blocks.store(blocks, Ordering::Relaxed);
+        self.bytes.store(bytes, Ordering::Relaxed);
+        self.update_gauges();
+    }
+
+    /// Serialize writes and deletes, so that checking whether a block is new and writing it
+    /// can't race with another write or delete of the same block.
+    pub fn lock_writes(&self) -> MutexGuard<'_, ()> {
+        self.writes.lock().unwrap()
+    }
+
+    /// Mark the counters as changed, returns true the first time since they were persisted
+    /// on shutdown, when the marker has to be written.
+    pub fn mark_dirty(&self) -> bool {
+        !self.dirty.swap(true, Ordering::Relaxed)
+    }
+
+    /// The counters were persisted on shutdown.
+    pub fn mark_clean(&self) {
+        self.dirty.store(false, Ordering::Relaxed);
+    }
+
+    /// Record inserted blocks, returns true if the statistics should be persisted.
+    pub fn record_put(&self, blocks: u64, bytes: u64) -> bool {
+        self.blocks.fetch_add(blocks, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.record_update(blocks)
+    }
+
+    /// Record a deleted block, returns true if the statistics should be persisted.
+    pub fn record_delete(&self, bytes: u64) -> bool {
+        // saturate so that blocks written before accounting started can't underflow
+        let _ = self
+            .blocks
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                Some(v.saturating_sub(1))
+            });
+        let _ = self
+            .bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                Some(v.saturating_sub(bytes))
+            });
+        // any dag may have lost the block
+        self.roots.write().unwrap().clear();
+        self.record_update(1)
+    }
+
+    pub fn root_size(&self, root: &Cid) -> Option<u64> {
+        self.roots.read().unwrap().get(root).copied()
+    }
+
+    /// Cache the car file size of a root.
+    pub fn insert_root(&self, root: Cid, size: u64) {
+        self.roots.write().unwrap().insert(root, size);
+    }
+
+    pub fn stats(&self) -> StoreStats {
+        StoreStats {
+            blocks: self.blocks.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            roots: self
+                .roots
+                .read()
+                .unwrap()
+                .iter()
+                .map(|(cid, size)| (cid.to_string(), *size))
+                .collect(),
+        }
+    }
+
+    fn record_update(&self, count: u64) -> bool {
+        self.update_gauges();
+        let before = self.updates.fetch_add(count, Ordering::Relaxed);
+        before / PERSIST_INTERVAL != (before + count) / PERSIST_INTERVAL
+    }
+
+    pub fn update_gauges(&self) {
+        let labels = vec![Label::new("store", *self.name.read().unwrap())];
+        gauge!(
+            "store_blocks",
+            self.blocks.load(Ordering::Relaxed) as f64,
+            labels.clone()
+        );
+        gauge!(
+            "store_bytes",
+            self.bytes.load(Ordering::Relaxed) as f64,
+            labels
+        );
+    }
+}
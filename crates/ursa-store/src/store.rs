@@ -1,23 +1,135 @@
 use anyhow::anyhow;
 use db::Store;
 use fnv::FnvHashSet;
+use futures::{future, stream, AsyncRead, AsyncWrite, Stream, TryStreamExt};
 use fvm_ipld_blockstore::Blockstore;
-use fvm_ipld_car::CarHeader;
+use fvm_ipld_car::{CarHeader, CarReader};
 use fvm_ipld_encoding::{de::DeserializeOwned, from_slice, ser::Serialize, to_vec, DAG_CBOR};
 use integer_encoding::VarInt;
 use ipld_traversal::blockstore::Blockstore as GSBlockstore;
 use libipld::{
     cid,
+    codec::Codec,
+    json::DagJsonCodec,
     multihash::{Code, MultihashDigest},
+    serde::{from_ipld, to_ipld},
     store::DefaultParams,
-    Block, Cid, Result,
+    Block, Cid, Ipld, Result,
 };
 use libp2p_bitswap::BitswapStore;
-use std::sync::Arc;
+use std::{future::Future, sync::Arc};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::backend::Backend;
+use crate::cache::BlockCache;
+use crate::stats::{Accounting, StoreStats, STATS_DIRTY_KEY, STATS_KEY};
+
+/// Key of the roots that still have to be announced to the indexer.
+const PENDING_ANNOUNCEMENTS_KEY: &[u8] = b"/ursa/store/pending_announcements";
+
+/// Number of blocks written to the store at once when loading a car file or a fetched dag.
+pub const BATCH_SIZE: usize = 1000;
+
+/// Multihash code of the identity hash.
+const IDENTITY: u64 = 0x00;
+/// Multihash code of sha2-256.
+const SHA2_256: u64 = 0x12;
+/// Multihash code of blake3.
+const BLAKE3_256: u64 = 0x1e;
+
+#[derive(Debug, Error)]
+pub enum BlockError {
+    #[error("the data of block {0} does not match its hash")]
+    HashMismatch(Cid),
+    #[error("block {cid} uses the unsupported hash function 0x{code:x}")]
+    UnsupportedHash { cid: Cid, code: u64 },
+}
+
+impl BlockError {
+    pub fn cid(&self) -> Cid {
+        match self {
+            BlockError::HashMismatch(cid) => *cid,
+            BlockError::UnsupportedHash { cid, .. } => *cid,
+        }
+    }
+}
+
+/// Check that `data` hashes to the multihash of `cid`.
+/// Supports sha2-256, blake3 and identity hashes.
+pub fn verify_block(cid: &Cid, data: &[u8]) -> std::result::Result<(), BlockError> {
+    let hash = cid.hash();
+    let valid = match hash.code() {
+        IDENTITY => hash.digest() == data,
+        SHA2_256 => Code::Sha2_256.digest(data).digest() == hash.digest(),
+        BLAKE3_256 => Code::Blake3_256.digest(data).digest() == hash.digest(),
+        code => return Err(BlockError::UnsupportedHash { cid: *cid, code }),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(BlockError::HashMismatch(*cid))
+    }
+}
+
+/// Pass the blocks of `blocks` to `put` in batches of [`BATCH_SIZE`], stopping at the
+/// first error of either.
+pub async fn put_batched<St, F, Fut>(mut blocks: St, mut put: F) -> Result<()>
+where
+    St: Stream<Item = Result<(Cid, Vec<u8>)>> + Unpin,
+    F: FnMut(Vec<(Cid, Vec<u8>)>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut buf = Vec::with_capacity(BATCH_SIZE);
+    while let Some(block) = blocks.try_next().await? {
+        buf.push(block);
+        if buf.len() >= BATCH_SIZE {
+            put(std::mem::replace(&mut buf, Vec::with_capacity(BATCH_SIZE))).await?;
+        }
+    }
+    put(buf).await
+}
+
+/// Read a car file and pass its blocks to `put` in batches of [`BATCH_SIZE`].
+/// Returns the roots of the car file.
+pub async fn load_car_batched<R, F, Fut>(reader: R, put: F) -> Result<Vec<Cid>>
+where
+    R: AsyncRead + Send + Unpin,
+    F: FnMut(Vec<(Cid, Vec<u8>)>) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let car = CarReader::new(reader).await?;
+    let roots = car.header.roots.clone();
+    let blocks = stream::try_unfold(car, |mut car| async move {
+        let block = car.next_block().await?;
+        Ok(block.map(|block| ((block.cid, block.data), car)))
+    });
+    put_batched(Box::pin(blocks), put).await?;
+    Ok(roots)
+}
+
+/// Size of the car file of the dag of `root_cid`, made of `blocks` given as their cid with
+/// the size of their data.
+fn car_len<'a>(root_cid: &Cid, blocks: impl Iterator<Item = (&'a Cid, usize)>) -> Result<u64> {
+    let header_bytes = to_vec(&CarHeader {
+        roots: vec![*root_cid],
+        version: 1,
+    })?;
+    let mut len = header_bytes.len();
+
+    for (cid, size) in blocks {
+        let block_len = size + cid.to_bytes().len();
+        len += block_len.encode_var_vec().len(); // varint size
+        len += block_len;
+    }
+    Ok(len as u64)
+}
 
 #[derive(Debug, Clone)]
 pub struct UrsaStore<S> {
     pub db: Arc<S>,
+    stats: Arc<Accounting>,
+    cache: Option<Arc<BlockCache>>,
 }
 
 impl<S> UrsaStore<S>
@@ -25,7 +137,75 @@ where
     S: Blockstore + Store + Send + Sync + 'static,
 {
     pub fn new(db: Arc<S>) -> Self {
-        Self { db }
+        Self::with_cache(db, None)
+    }
+
+    /// Create a store that keeps up to `max_bytes` of recently read blocks in memory.
+    /// A size of zero disables the cache.
+    pub fn with_block_cache(db: Arc<S>, max_bytes: u64) -> Self {
+        let cache = (max_bytes > 0).then(|| Arc::new(BlockCache::new(max_bytes)));
+        Self::with_cache(db, cache)
+    }
+
+    fn with_cache(db: Arc<S>, cache: Option<Arc<BlockCache>>) -> Self {
+        let stats = match db.read(STATS_KEY) {
+            Ok(Some(bytes)) => from_slice::<StoreStats>(&bytes).unwrap_or_else(|e| {
+                warn!("Failed to decode store stats, starting from zero: {e:?}");
+                StoreStats::default()
+            }),
+            Ok(None) => StoreStats::default(),
+            Err(e) => {
+                warn!("Failed to read store stats, starting from zero: {e:?}");
+                StoreStats::default()
+            }
+        };
+        Self {
+            db,
+            stats: Arc::new(Accounting::from_stats(stats)),
+            cache,
+        }
+    }
+
+    /// Name the store in the `store_blocks` and `store_bytes` gauges, `content` by default.
+    pub fn with_name(self, name: &'static str) -> Self {
+        self.stats.set_name(name);
+        self
+    }
+
+    /// Current statistics of the store.
+    pub fn stats(&self) -> StoreStats {
+        self.stats.stats()
+    }
+
+    /// Write the statistics to the store so they survive a restart. Called once writes
+    /// stopped, i.e. on shutdown, the counters are trusted on the next start.
+    pub fn persist_stats(&self) -> Result<()> {
+        self.write_stats()?;
+        self.db.delete(STATS_DIRTY_KEY)?;
+        self.stats.mark_clean();
+        Ok(())
+    }
+
+    fn write_stats(&self) -> Result<()> {
+        self.db.write(STATS_KEY, to_vec(&self.stats.stats())?)?;
+        Ok(())
+    }
+
+    /// Write the marker of counters changed since they were persisted, before the first
+    /// write that changes them.
+    fn mark_stats_dirty(&self) -> Result<()> {
+        if self.stats.mark_dirty() {
+            self.db.write(STATS_DIRTY_KEY, b"1")?;
+        }
+        Ok(())
+    }
+
+    fn maybe_persist_stats(&self, persist: bool) {
+        if persist {
+            if let Err(e) = self.write_stats() {
+                warn!("Failed to persist store stats: {e:?}");
+            }
+        }
     }
 
     /// return the inner blockstore
@@ -33,6 +213,95 @@ where
         &self.db
     }
 
+    /// Read a block, going through the block cache if there is one.
+    pub fn get_block(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.db.get(cid),
+        };
+        if let Some(data) = cache.get(cid) {
+            return Ok(Some(data));
+        }
+        let generation = cache.generation();
+        let data = self.db.get(cid)?;
+        if let Some(data) = &data {
+            cache.fill(*cid, data, generation);
+        }
+        Ok(data)
+    }
+
+    /// Check whether a block is in the cache or the database.
+    pub fn has_block(&self, cid: &Cid) -> Result<bool> {
+        match &self.cache {
+            Some(cache) if cache.contains(cid) => Ok(true),
+            _ => self.db.has(cid),
+        }
+    }
+
+    /// Verify a block and insert it into the store.
+    pub fn put_block(&self, cid: &Cid, data: &[u8]) -> Result<()> {
+        verify_block(cid, data)?;
+        let _lock = self.stats.lock_writes();
+        let new = !self.db.has(cid)?;
+        if new {
+            self.mark_stats_dirty()?;
+        }
+        self.db.put_keyed(cid, data)?;
+        if new {
+            self.maybe_persist_stats(self.stats.record_put(1, data.len() as u64));
+        }
+        Ok(())
+    }
+
+    /// Verify a batch of blocks and insert them into the store.
+    /// Nothing is inserted if any block of the batch is invalid.
+    pub fn put_blocks<D: AsRef<[u8]>>(&self, blocks: Vec<(Cid, D)>) -> Result<()> {
+        for (cid, data) in &blocks {
+            verify_block(cid, data.as_ref())?;
+        }
+        let _lock = self.stats.lock_writes();
+        let mut new = FnvHashSet::default();
+        let mut new_bytes = 0;
+        for (cid, data) in &blocks {
+            if !self.db.has(cid)? && new.insert(*cid) {
+                new_bytes += data.as_ref().len() as u64;
+            }
+        }
+        if !new.is_empty() {
+            self.mark_stats_dirty()?;
+        }
+        self.db.put_many_keyed(blocks)?;
+        if !new.is_empty() {
+            self.maybe_persist_stats(self.stats.record_put(new.len() as u64, new_bytes));
+        }
+        Ok(())
+    }
+
+    /// Delete a block from the store.
+    pub fn delete_block(&self, cid: &Cid) -> Result<()> {
+        let _lock = self.stats.lock_writes();
+        if let Some(data) = self.db.get(cid)? {
+            self.mark_stats_dirty()?;
+            self.db.delete(cid.to_bytes())?;
+            self.maybe_persist_stats(self.stats.record_delete(data.len() as u64));
+        }
+        // after the delete commits, a read that started earlier sees the generation change
+        // and doesn't cache the block again
+        if let Some(cache) = &self.cache {
+            cache.invalidate(cid);
+        }
+        Ok(())
+    }
+
+    /// Load a car file into the store, verifying every block.
+    /// Returns the roots of the car file.
+    pub async fn load_car<R>(&self, reader: R) -> Result<Vec<Cid>>
+    where
+        R: AsyncRead + Send + Unpin,
+    {
+        load_car_batched(reader, |blocks| future::ready(self.put_blocks(blocks))).await
+    }
+
     /// traverse a dag and get full dag given a root cid
     pub fn dag_traversal(&self, root_cid: &Cid) -> Result<Vec<(Cid, Vec<u8>)>> {
         let mut res = Vec::new();
@@ -46,7 +315,7 @@ where
             if refs.contains(&cid) {
                 continue;
             }
-            match self.db.get(&cid)? {
+            match self.get_block(&cid)? {
                 Some(data) => {
                     res.push((cid, data.clone()));
                     let next_block = Block::<DefaultParams>::new(cid, data)?;
@@ -66,53 +335,254 @@ where
         Ok(res)
     }
 
-    /// Calculate a car file size from a root cid
+    /// Walk the part of a dag that is already in the store.
+    ///
+    /// Returns the number of blocks and bytes present and the number of blocks still missing.
+    pub fn dag_progress(&self, root_cid: &Cid) -> Result<(usize, u64, usize)> {
+        let (mut blocks, mut bytes, mut missing) = (0, 0, 0);
+        let mut stack = vec![*root_cid];
+        let mut visited = FnvHashSet::default();
+
+        while let Some(cid) = stack.pop() {
+            if !visited.insert(cid) {
+                continue;
+            }
+            match self.db.get(&cid)? {
+                Some(data) => {
+                    blocks += 1;
+                    bytes += data.len() as u64;
+                    Block::<DefaultParams>::new_unchecked(cid, data).references(&mut stack)?;
+                }
+                None => missing += 1,
+            }
+        }
+        Ok((blocks, bytes, missing))
+    }
+
+    /// Blocks of a dag missing from the store, whose subdags couldn't be walked.
+    pub fn missing_blocks(&self, root_cid: &Cid) -> Result<Vec<Cid>> {
+        let mut stack = vec![*root_cid];
+        let mut missing = vec![];
+
+        while let Some(cid) = stack.pop() {
+            if let Some(data) = self.get_block(&cid)? {
+                let block = Block::<DefaultParams>::new_unchecked(cid, data);
+                block.references(&mut stack)?;
+            } else {
+                missing.push(cid);
+            }
+        }
+
+        Ok(missing)
+    }
+
+    /// Calculate a car file size from a root cid.
+    /// The size is computed once per root and cached.
     pub fn car_size(&self, root_cid: &Cid) -> Result<u64> {
+        if let Some(size) = self.stats.root_size(root_cid) {
+            return Ok(size);
+        }
+
         let dag = self.dag_traversal(root_cid)?;
+        let len = car_len(root_cid, dag.iter().map(|(cid, bytes)| (cid, bytes.len())))?;
+        self.stats.insert_root(*root_cid, len);
+        Ok(len)
+    }
 
-        let header_bytes = to_vec(&CarHeader {
+    /// Cache the car file size of a dag whose blocks were just written, sparing `car_size`
+    /// a walk of the dag. `dag` holds the cids of its blocks with the size of their data.
+    pub fn insert_car_size(&self, root_cid: &Cid, dag: &[(Cid, usize)]) -> Result<u64> {
+        let len = car_len(root_cid, dag.iter().map(|(cid, size)| (cid, *size)))?;
+        self.stats.insert_root(*root_cid, len);
+        Ok(len)
+    }
+
+    /// Write the dag of `root_cid` to `writer` as a car file.
+    pub async fn export_car<W>(&self, root_cid: &Cid, writer: &mut W) -> Result<()>
+    where
+        W: AsyncWrite + Send + Unpin,
+    {
+        let dag = self.dag_traversal(root_cid)?;
+        CarHeader {
             roots: vec![*root_cid],
             version: 1,
+        }
+        .write_stream_async(writer, &mut stream::iter(dag))
+        .await?;
+        Ok(())
+    }
+
+    /// Roots that were added to the store without being announced to the indexer.
+    pub fn pending_announcements(&self) -> Result<Vec<Cid>> {
+        match self.db.read(PENDING_ANNOUNCEMENTS_KEY)? {
+            Some(bytes) => Ok(from_slice(&bytes)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Record roots that have to be announced the next time the node starts.
+    pub fn add_pending_announcements(&self, roots: &[Cid]) -> Result<()> {
+        let mut pending = self.pending_announcements()?;
+        for root in roots {
+            if !pending.contains(root) {
+                pending.push(*root);
+            }
+        }
+        self.db
+            .write(PENDING_ANNOUNCEMENTS_KEY, to_vec(&pending)?)?;
+        Ok(())
+    }
+
+    /// Forget about a root once it has been announced.
+    pub fn remove_pending_announcement(&self, root: &Cid) -> Result<()> {
+        let mut pending = self.pending_announcements()?;
+        pending.retain(|cid| cid != root);
+        if pending.is_empty() {
+            self.db.delete(PENDING_ANNOUNCEMENTS_KEY)?;
+        } else {
+            self.db
+                .write(PENDING_ANNOUNCEMENTS_KEY, to_vec(&pending)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of checking the integrity of a store.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// Number of blocks checked.
+    pub blocks: u64,
+    /// Blocks whose data does not match their hash.
+    pub corrupt: Vec<Cid>,
+    /// Blocks using a hash function that can't be verified.
+    pub unsupported: Vec<Cid>,
+    /// Roots whose dag is missing blocks or contains blocks whose data does not match their
+    /// hash.
+    pub incomplete_roots: Vec<Cid>,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.corrupt.is_empty() && self.incomplete_roots.is_empty()
+    }
+}
+
+impl UrsaStore<Backend> {
+    /// Recount the blocks and bytes of the store when the persisted counters can't be
+    /// trusted: the store predates them, or the node stopped without persisting them.
+    pub fn reconcile_stats(&self) -> Result<()> {
+        let stale = self.db.read(STATS_KEY)?.is_none() || self.db.exists(STATS_DIRTY_KEY)?;
+        // a memory store starts empty
+        if stale && !matches!(*self.db, Backend::Memory(_)) {
+            info!("Recounting the blocks of the store");
+            let (mut blocks, mut bytes) = (0, 0);
+            self.db.for_each_block(|_, data| {
+                blocks += 1;
+                bytes += data.len() as u64;
+                Ok(())
+            })?;
+            self.stats.reset(blocks, bytes);
+            self.persist_stats()?;
+        }
+        self.stats.update_gauges();
+        Ok(())
+    }
+
+    /// Recompute the digest of every block and check that the dags of `roots` are complete.
+    pub fn verify(&self, roots: &[Cid]) -> Result<VerifyReport> {
+        let mut report = VerifyReport::default();
+        self.db.for_each_block(|cid, data| {
+            report.blocks += 1;
+            match verify_block(&cid, &data) {
+                Ok(()) => {}
+                Err(BlockError::HashMismatch(cid)) => report.corrupt.push(cid),
+                Err(BlockError::UnsupportedHash { cid, .. }) => report.unsupported.push(cid),
+            }
+            Ok(())
         })?;
-        let mut len = header_bytes.len();
 
-        for (cid, bytes) in dag {
-            let block_len = bytes.len() + cid.to_bytes().len();
-            len += block_len.encode_var_vec().len(); // varint size
-            len += block_len;
+        // the walk checks the hash of every block of the dag
+        for root in roots {
+            if let Err(e) = self.dag_traversal(root) {
+                warn!("Dag of {root} is incomplete or corrupt: {e:?}");
+                report.incomplete_roots.push(*root);
+            }
         }
+        Ok(report)
+    }
+}
+
+/// Codec of raw binary blocks.
+pub const RAW: u64 = 0x55;
+/// Codec of dag-pb (unixfs) blocks.
+pub const DAG_PB: u64 = 0x70;
+/// Codec of dag-json blocks.
+pub const DAG_JSON: u64 = 0x0129;
 
-        Ok(len as u64)
+/// Convert a cid to version 1, keeping its codec and hash.
+pub fn cid_to_v1(cid: &Cid) -> Cid {
+    Cid::new_v1(cid.codec(), *cid.hash())
+}
+
+/// Convert a cid to version 0. Only dag-pb blocks hashed with sha2-256 have a v0 cid.
+pub fn cid_to_v0(cid: &Cid) -> Result<Cid> {
+    if cid.codec() != DAG_PB {
+        return Err(anyhow!(
+            "Only dag-pb cids can be converted to v0, {cid} has codec {:#x}",
+            cid.codec()
+        ));
     }
+    Ok(Cid::new_v0(*cid.hash())?)
 }
 
 /// Extension methods for inserting and retrieving IPLD data with CIDs
 pub trait BlockstoreExt: Blockstore {
-    /// Get typed object from block store by CID
+    /// Get typed object from block store by CID.
+    /// Dag-json blocks are decoded as json, blocks of any other codec, raw included, as cbor.
     fn get_obj<T>(&self, cid: &Cid) -> Result<Option<T>>
     where
         T: DeserializeOwned,
     {
-        match self.get(cid)? {
-            Some(bz) => Ok(Some(from_slice(&bz)?)),
-            None => Ok(None),
+        let bytes = match self.get(cid)? {
+            Some(bytes) => bytes,
+            None => return Ok(None),
+        };
+        match cid.codec() {
+            DAG_JSON => {
+                let ipld: Ipld = DagJsonCodec.decode(&bytes)?;
+                Ok(Some(from_ipld(ipld)?))
+            }
+            _ => Ok(Some(from_slice(&bytes)?)),
         }
     }
 
-    /// Put an object in the block store and return the Cid identifier.
+    /// Put a block encoded with `codec` in the block store and return its Cid identifier.
+    fn put_block_with_codec(&self, codec: u64, code: Code, bytes: &[u8]) -> Result<Cid> {
+        let cid = Cid::new_v1(codec, code.digest(bytes));
+        self.put_keyed(&cid, bytes)?;
+        Ok(cid)
+    }
+
+    /// Put an object in the block store as dag-cbor and return the Cid identifier.
     fn put_obj<S>(&self, obj: &S, code: Code) -> Result<Cid>
     where
         S: Serialize,
     {
-        let bytes = to_vec(obj)?;
-        self.put_raw(bytes, code)
+        self.put_block_with_codec(DAG_CBOR, code, &to_vec(obj)?)
+    }
+
+    /// Put an object in the block store as dag-json and return the Cid identifier.
+    fn put_json<S>(&self, obj: &S, code: Code) -> Result<Cid>
+    where
+        S: Serialize,
+    {
+        let bytes = DagJsonCodec.encode(&to_ipld(obj)?)?;
+        self.put_block_with_codec(DAG_JSON, code, &bytes)
     }
 
     /// Put raw bytes in the block store and return the Cid identifier.
     fn put_raw(&self, bytes: Vec<u8>, code: Code) -> Result<Cid> {
-        let cid = Cid::new_v1(DAG_CBOR, code.digest(&bytes));
-        self.put_keyed(&cid, &bytes)?;
-        Ok(cid)
+        self.put_block_with_codec(RAW, code, &bytes)
     }
 
     /// Batch put CBOR objects into block store and returns vector of CIDs
@@ -147,15 +617,15 @@ where
     S: Blockstore + Store + Send + Sync + 'static,
 {
     fn get(&self, k: &cid::Cid) -> Result<Option<Vec<u8>>> {
-        self.db.get(k)
+        self.get_block(k)
     }
 
     fn put_keyed(&self, k: &Cid, block: &[u8]) -> Result<()> {
-        self.db.put_keyed(k, block)
+        self.put_block(k, block)
     }
 
     fn delete_block(&self, k: &Cid) -> Result<()> {
-        self.db.delete(k.to_bytes()).map_err(|e| e.into())
+        UrsaStore::delete_block(self, k)
     }
 }
 
@@ -172,33 +642,19 @@ where
     type Params = DefaultParams;
 
     fn contains(&mut self, cid: &Cid) -> Result<bool> {
-        self.0.db.has(cid)
+        self.0.has_block(cid)
     }
 
     fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>> {
-        Ok(self.0.db.get(cid).unwrap())
+        self.0.get_block(cid)
     }
 
     fn insert(&mut self, block: &Block<Self::Params>) -> Result<()> {
-        self.0.db.put_keyed(block.cid(), block.data()).unwrap();
-
-        Ok(())
+        self.0.put_block(block.cid(), block.data())
     }
 
     fn missing_blocks(&mut self, cid: &Cid) -> Result<Vec<Cid>> {
-        let mut stack = vec![*cid];
-        let mut missing = vec![];
-
-        while let Some(cid) = stack.pop() {
-            if let Some(data) = self.get(&cid)? {
-                let block = Block::<Self::Params>::new_unchecked(cid, data);
-                block.references(&mut stack)?;
-            } else {
-                missing.push(cid);
-            }
-        }
-
-        Ok(missing)
+        self.0.missing_blocks(cid)
     }
 }
 
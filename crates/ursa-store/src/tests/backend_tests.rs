@@ -0,0 +1,152 @@
+#[cfg(test)]
+mod tests {
+    use db::Store;
+    use fvm_ipld_blockstore::Blockstore;
+    use libipld::{
+        cbor::DagCborCodec,
+        ipld,
+        multihash::{Code, MultihashDigest},
+        Block, Cid, DefaultParams, Ipld,
+    };
+    use std::sync::Arc;
+
+    use crate::{Backend, StorageBackend, UrsaStore};
+
+    const RAW: u64 = 0x55;
+
+    fn create_block(ipld: Ipld) -> Block<DefaultParams> {
+        Block::encode(DagCborCodec, Code::Blake3_256, &ipld).unwrap()
+    }
+
+    fn roundtrip(backend: StorageBackend) -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let db = Backend::open(backend, dir.path())?;
+
+        let small = vec![1u8; 32];
+        let large = vec![2u8; 1024 * 1024];
+        for data in [small, large] {
+            let cid = Cid::new_v1(RAW, Code::Sha2_256.digest(&data));
+            assert!(!db.has(&cid)?);
+            db.put_keyed(&cid, &data)?;
+            assert!(db.has(&cid)?);
+            assert_eq!(db.get(&cid)?, Some(data));
+            db.delete(cid.to_bytes())?;
+            assert!(!db.has(&cid)?);
+        }
+
+        db.write(b"/ursa/key", b"value")?;
+        assert_eq!(db.read(b"/ursa/key")?, Some(b"value".to_vec()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_backend() -> anyhow::Result<()> {
+        roundtrip(StorageBackend::Memory)
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn test_rocksdb_backend() -> anyhow::Result<()> {
+        roundtrip(StorageBackend::RocksDb)
+    }
+
+    #[cfg(feature = "flatfile")]
+    #[test]
+    fn test_flatfile_backend() -> anyhow::Result<()> {
+        roundtrip(StorageBackend::FlatFile)
+    }
+
+    #[cfg(feature = "paritydb")]
+    #[test]
+    fn test_paritydb_backend() -> anyhow::Result<()> {
+        roundtrip(StorageBackend::ParityDb)?;
+
+        // the btree index keeps the keys, so the blocks can be walked
+        let dir = tempfile::tempdir()?;
+        let db = Backend::open(StorageBackend::ParityDb, dir.path())?;
+        let data = vec![3u8; 32];
+        let cid = Cid::new_v1(RAW, Code::Sha2_256.digest(&data));
+        db.put_keyed(&cid, &data)?;
+        let mut blocks = vec![];
+        db.for_each_block(|cid, data| {
+            blocks.push((cid, data));
+            Ok(())
+        })?;
+        assert_eq!(blocks, vec![(cid, data)]);
+        Ok(())
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn test_verify_store() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let store = UrsaStore::new(Arc::new(Backend::open(
+            StorageBackend::RocksDb,
+            dir.path(),
+        )?));
+
+        let leaf = create_block(ipld!("leaf"));
+        let root = create_block(ipld!({ "link": *leaf.cid() }));
+        store.put_block(root.cid(), root.data())?;
+        store.put_block(leaf.cid(), leaf.data())?;
+
+        let report = store.verify(&[*root.cid()])?;
+        assert_eq!(report.blocks, 2);
+        assert!(report.is_ok());
+
+        // simulate a corrupted write that bypassed verification
+        store.db.put_keyed(leaf.cid(), b"corrupt")?;
+        let report = store.verify(&[*root.cid()])?;
+        assert_eq!(report.corrupt, vec![*leaf.cid()]);
+        assert_eq!(report.incomplete_roots, vec![*root.cid()]);
+
+        // a corrupt raw leaf still decodes, it is caught by its hash
+        let data = b"raw leaf".to_vec();
+        let raw = Cid::new_v1(RAW, Code::Sha2_256.digest(&data));
+        let raw_root = create_block(ipld!({ "link": raw }));
+        store.put_block(raw_root.cid(), raw_root.data())?;
+        store.put_block(&raw, &data)?;
+        store.db.put_keyed(&raw, b"corrupt raw leaf")?;
+        let report = store.verify(&[*raw_root.cid()])?;
+        assert!(report.corrupt.contains(&raw));
+        assert_eq!(report.incomplete_roots, vec![*raw_root.cid()]);
+        Ok(())
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn test_reconcile_stats() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let open = || -> anyhow::Result<UrsaStore<Backend>> {
+            let db = Backend::open(StorageBackend::RocksDb, dir.path())?;
+            Ok(UrsaStore::new(Arc::new(db)))
+        };
+        let leaf = create_block(ipld!("leaf"));
+        let root = create_block(ipld!({ "link": *leaf.cid() }));
+
+        // a store written before the counters existed is counted on open
+        {
+            let store = open()?;
+            store.db.put_keyed(leaf.cid(), leaf.data())?;
+            store.reconcile_stats()?;
+            assert_eq!(store.stats().blocks, 1);
+        }
+
+        // counters left behind by a crash are recounted
+        {
+            let store = open()?;
+            store.reconcile_stats()?;
+            store.put_block(root.cid(), root.data())?;
+            assert_eq!(store.stats().blocks, 2);
+        }
+        let store = open()?;
+        assert_eq!(store.stats().blocks, 1);
+        store.reconcile_stats()?;
+        assert_eq!(store.stats().blocks, 2);
+        assert_eq!(
+            store.stats().bytes,
+            (leaf.data().len() + root.data().len()) as u64
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+#[cfg(test)]
+mod tests {
+    use async_fs::File;
+    use futures::io::BufReader;
+    use libipld::{
+        multihash::{Code, MultihashDigest},
+        Cid,
+    };
+    use std::path::Path;
+
+    use crate::tests::get_store;
+    use crate::AsyncStore;
+
+    const RAW: u64 = 0x55;
+
+    #[tokio::test]
+    async fn test_async_store() -> anyhow::Result<()> {
+        let store = AsyncStore::with_max_blocking_tasks(get_store(), 2);
+
+        let path = Path::new("../../test_files/test.car");
+        let roots = store
+            .load_car(BufReader::new(File::open(path).await?))
+            .await?;
+        assert_eq!(
+            store.dag_traversal(roots[0]).await?,
+            store.inner().dag_traversal(&roots[0])?
+        );
+        assert_eq!(
+            store.car_size(roots[0]).await?,
+            store.inner().car_size(&roots[0])?
+        );
+
+        // more concurrent calls than permits
+        let data = b"hello".to_vec();
+        let cid = Cid::new_v1(RAW, Code::Sha2_256.digest(&data));
+        let puts = (0..8).map(|_| store.put_block(cid, data.clone()));
+        for result in futures::future::join_all(puts).await {
+            result?;
+        }
+        assert_eq!(store.get(cid).await?, Some(data));
+
+        store.delete_block(cid).await?;
+        assert!(!store.has(cid).await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_store_propagates_errors() {
+        let store = AsyncStore::new(get_store());
+        let cid = Cid::new_v1(RAW, Code::Sha2_256.digest(b"hello"));
+        assert!(store.put_block(cid, b"world".to_vec()).await.is_err());
+        assert!(store.dag_traversal(cid).await.is_err());
+    }
+}
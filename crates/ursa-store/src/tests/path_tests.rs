@@ -0,0 +1,76 @@
+#[cfg(test)]
+mod tests {
+    use fvm_ipld_encoding::DAG_CBOR;
+    use libipld::{
+        cbor::DagCborCodec, codec::Codec, ipld, multihash::Code, pb::DagPbCodec, Cid, Ipld,
+    };
+
+    use crate::tests::get_store;
+    use crate::{BlockstoreExt, PathError, DAG_PB};
+
+    fn directory(entries: &[(&str, Cid)]) -> Ipld {
+        let links = entries
+            .iter()
+            .map(|(name, cid)| ipld!({ "Hash": *cid, "Name": *name, "Tsize": 0 }))
+            .collect::<Vec<_>>();
+        ipld!({ "Links": links, "Data": Ipld::Bytes(vec![0x08, 0x01]) })
+    }
+
+    #[test]
+    fn test_resolve_unixfs_path() -> anyhow::Result<()> {
+        let store = get_store();
+        let db = store.blockstore();
+
+        let logo = db.put_raw(b"logo".to_vec(), Code::Sha2_256)?;
+        let readme = db.put_raw(b"readme".to_vec(), Code::Sha2_256)?;
+        let assets = directory(&[("logo.png", logo)]);
+        let assets =
+            db.put_block_with_codec(DAG_PB, Code::Sha2_256, &DagPbCodec.encode(&assets)?)?;
+        let root = directory(&[("assets", assets), ("README.md", readme)]);
+        let root = db.put_block_with_codec(DAG_PB, Code::Sha2_256, &DagPbCodec.encode(&root)?)?;
+
+        assert_eq!(store.resolve_path(&root, "")?, root);
+        assert_eq!(store.resolve_path(&root, "/assets")?, assets);
+        assert_eq!(store.resolve_path(&root, "/assets/logo.png")?, logo);
+        assert_eq!(store.resolve_path(&root, "README.md/")?, readme);
+
+        let err = store
+            .resolve_path(&root, "/assets/missing.png")
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PathError>(),
+            Some(PathError::NotFound { .. })
+        ));
+        let err = store.resolve_path(&root, "/README.md/nested").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PathError>(),
+            Some(PathError::UnsupportedCodec { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_cbor_path() -> anyhow::Result<()> {
+        let store = get_store();
+        let db = store.blockstore();
+
+        let leaf = db.put_raw(b"leaf".to_vec(), Code::Sha2_256)?;
+        let node = ipld!({ "entries": [{ "value": leaf }], "name": "ursa" });
+        let root =
+            db.put_block_with_codec(DAG_CBOR, Code::Sha2_256, &DagCborCodec.encode(&node)?)?;
+
+        assert_eq!(store.resolve_path(&root, "/entries/0/value")?, leaf);
+
+        let err = store.resolve_path(&root, "/name").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PathError>(),
+            Some(PathError::NotALink { .. })
+        ));
+        let err = store.resolve_path(&root, "/entries/1/value").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<PathError>(),
+            Some(PathError::NotFound { .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod tests {
+    use db::MemoryDB;
+    use fvm_ipld_encoding::DAG_CBOR;
+    use libipld::{
+        cbor::DagCborCodec, codec::Codec, ipld, multihash::Code, pb::DagPbCodec, Cid, Ipld,
+    };
+
+    use crate::tests::get_store;
+    use crate::{BlockstoreExt, DagScope, DagSelection, DAG_PB};
+
+    /// root -> [middle -> [leaf], other]
+    fn cbor_dag(blockstore: &MemoryDB) -> anyhow::Result<[Cid; 4]> {
+        let leaf = blockstore.put_raw(b"leaf".to_vec(), Code::Sha2_256)?;
+        let other = blockstore.put_raw(b"other".to_vec(), Code::Sha2_256)?;
+        let middle = ipld!({ "links": [leaf] });
+        let middle = blockstore.put_block_with_codec(
+            DAG_CBOR,
+            Code::Sha2_256,
+            &DagCborCodec.encode(&middle)?,
+        )?;
+        let root = ipld!({ "links": [middle, other] });
+        let root = blockstore.put_block_with_codec(
+            DAG_CBOR,
+            Code::Sha2_256,
+            &DagCborCodec.encode(&root)?,
+        )?;
+        Ok([root, middle, other, leaf])
+    }
+
+    fn cids(blocks: Vec<(Cid, Vec<u8>)>) -> Vec<Cid> {
+        blocks.into_iter().map(|(cid, _)| cid).collect()
+    }
+
+    #[test]
+    fn test_select_depth() -> anyhow::Result<()> {
+        let store = get_store();
+        let [root, middle, other, leaf] = cbor_dag(store.blockstore())?;
+
+        let depth = |depth| DagSelection {
+            depth: Some(depth),
+            ..Default::default()
+        };
+        assert_eq!(cids(store.select(&root, &depth(0))?), vec![root]);
+        assert_eq!(
+            cids(store.select(&root, &depth(1))?),
+            vec![root, middle, other]
+        );
+        assert_eq!(
+            cids(store.select(&root, &depth(2))?),
+            vec![root, middle, other, leaf]
+        );
+
+        let all = cids(store.select(&root, &DagSelection::default())?);
+        assert_eq!(all.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_scope() -> anyhow::Result<()> {
+        let store = get_store();
+        let db = store.blockstore();
+        let [root, ..] = cbor_dag(db)?;
+
+        let scope = |scope| DagSelection {
+            dag_scope: Some(scope),
+            ..Default::default()
+        };
+        assert_eq!(
+            cids(store.select(&root, &scope(DagScope::Block))?),
+            vec![root]
+        );
+        assert_eq!(
+            cids(store.select(&root, &scope(DagScope::Entity))?),
+            vec![root]
+        );
+        assert_eq!(store.select(&root, &scope(DagScope::All))?.len(), 4);
+
+        // a unixfs file is a single entity
+        let chunk = db.put_raw(b"chunk".to_vec(), Code::Sha2_256)?;
+        let file = ipld!({
+            "Links": [{ "Hash": chunk, "Name": "", "Tsize": 5 }],
+            "Data": Ipld::Bytes(vec![0x08, 0x02]),
+        });
+        let file = db.put_block_with_codec(DAG_PB, Code::Sha2_256, &DagPbCodec.encode(&file)?)?;
+        assert_eq!(
+            cids(store.select(&file, &scope(DagScope::Entity))?),
+            vec![file, chunk]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_with_selector() -> anyhow::Result<()> {
+        let store = get_store();
+        let [root, middle, other, leaf] = cbor_dag(store.blockstore())?;
+
+        let selection = DagSelection {
+            selector: Some(r#"{"R":{"l":{"none":{}},":>":{"a":{">":{"@":{}}}}}}"#.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            cids(store.select(&root, &selection)?),
+            vec![root, middle, leaf, other]
+        );
+
+        let selection = DagSelection {
+            selector: Some(r#"{"f":{"f>":{"links":{"i":{"i":1,">":{".":{}}}}}}}"#.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(cids(store.select(&root, &selection)?), vec![root, other]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_selector_recursion_limit() -> anyhow::Result<()> {
+        let store = get_store();
+        let [root, middle, other, leaf] = cbor_dag(store.blockstore())?;
+
+        // the depth counts the nodes on a path, the root and the `links` lists included
+        let depth = |depth: u64| DagSelection {
+            selector: Some(format!(
+                r#"{{"R":{{"l":{{"depth":{depth}}},":>":{{"a":{{">":{{"@":{{}}}}}}}}}}}"#
+            )),
+            ..Default::default()
+        };
+        assert_eq!(cids(store.select(&root, &depth(1))?), vec![root]);
+        assert_eq!(
+            cids(store.select(&root, &depth(3))?),
+            vec![root, middle, other]
+        );
+        assert_eq!(
+            cids(store.select(&root, &depth(5))?),
+            vec![root, middle, leaf, other]
+        );
+
+        // an edge that doesn't explore a child first would never end
+        let selection = DagSelection {
+            selector: Some(r#"{"R":{"l":{"none":{}},":>":{"@":{}}}}"#.to_string()),
+            ..Default::default()
+        };
+        assert!(store.select(&root, &selection).is_err());
+        Ok(())
+    }
+}
@@ -1,13 +1,25 @@
 #[cfg(test)]
 mod tests {
     use async_fs::File;
+    use db::{MemoryDB, Store};
     use futures::io::BufReader;
+    use fvm_ipld_blockstore::Blockstore;
     use fvm_ipld_car::{load_car, CarReader};
-    use libipld::Cid;
+    use fvm_ipld_encoding::{to_vec, DAG_CBOR};
+    use libipld::{
+        multihash::{Code, Multihash, MultihashDigest},
+        store::DefaultParams,
+        Block, Cid,
+    };
     use std::path::Path;
+    use std::str::FromStr;
     use std::sync::Arc;
 
+    use crate::cache::BlockCache;
     use crate::tests::{get_store, setup_logger};
+    use crate::{
+        cid_to_v0, cid_to_v1, verify_block, BlockError, BlockstoreExt, UrsaStore, DAG_JSON, RAW,
+    };
 
     #[tokio::test]
     async fn test_dag_traversal() -> anyhow::Result<()> {
@@ -34,4 +46,240 @@ mod tests {
         // todo: check if they both have sam cids
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_dag_progress() -> anyhow::Result<()> {
+        setup_logger();
+        let store = get_store();
+
+        let path = Path::new("../../test_files/test.car");
+        let file = File::open(path).await?;
+        let reader = BufReader::new(file);
+        let cids = load_car(store.blockstore(), reader).await?;
+
+        let dag = store.dag_traversal(&cids[0])?;
+        let size: u64 = dag.iter().map(|(_, data)| data.len() as u64).sum();
+        assert_eq!(store.dag_progress(&cids[0])?, (dag.len(), size, 0));
+
+        // remove a leaf and it shows up as missing
+        let (leaf, data) = dag
+            .iter()
+            .find(|(cid, data)| {
+                let mut refs = vec![];
+                Block::<DefaultParams>::new_unchecked(*cid, data.clone())
+                    .references(&mut refs)
+                    .is_ok()
+                    && refs.is_empty()
+            })
+            .unwrap();
+        store.db.delete(leaf.to_bytes())?;
+        assert_eq!(
+            store.dag_progress(&cids[0])?,
+            (dag.len() - 1, size - data.len() as u64, 1)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_block() {
+        let data = b"hello world";
+        for code in [Code::Sha2_256, Code::Blake3_256] {
+            let cid = Cid::new_v1(RAW, code.digest(data));
+            assert!(verify_block(&cid, data).is_ok());
+            assert!(matches!(
+                verify_block(&cid, b"poisoned"),
+                Err(BlockError::HashMismatch(_))
+            ));
+        }
+
+        let identity = Cid::new_v1(RAW, Multihash::wrap(0x00, data).unwrap());
+        assert!(verify_block(&identity, data).is_ok());
+        assert!(verify_block(&identity, b"poisoned").is_err());
+
+        let unsupported = Cid::new_v1(RAW, Code::Sha2_512.digest(data));
+        assert!(matches!(
+            verify_block(&unsupported, data),
+            Err(BlockError::UnsupportedHash { code: 0x13, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reject_invalid_block() -> anyhow::Result<()> {
+        let store = get_store();
+        let cid = Cid::new_v1(RAW, Code::Sha2_256.digest(b"hello world"));
+
+        assert!(store.put_block(&cid, b"poisoned").is_err());
+        assert!(store
+            .put_blocks(vec![(cid, &b"hello world"[..]), (cid, &b"poisoned"[..])])
+            .is_err());
+        assert!(!store.db.has(&cid)?);
+
+        store.put_block(&cid, b"hello world")?;
+        assert!(store.db.has(&cid)?);
+
+        let path = Path::new("../../test_files/test.car");
+        let roots = store
+            .load_car(BufReader::new(File::open(path).await?))
+            .await?;
+        assert_eq!(store.dag_progress(&roots[0])?.2, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_store_stats() -> anyhow::Result<()> {
+        let store = get_store();
+
+        let path = Path::new("../../test_files/test.car");
+        let roots = store
+            .load_car(BufReader::new(File::open(path).await?))
+            .await?;
+        let dag = store.dag_traversal(&roots[0])?;
+        let bytes: u64 = dag.iter().map(|(_, data)| data.len() as u64).sum();
+
+        // loading the same car twice does not count blocks twice
+        store
+            .load_car(BufReader::new(File::open(path).await?))
+            .await?;
+        let stats = store.stats();
+        assert_eq!(stats.blocks, dag.len() as u64);
+        assert_eq!(stats.bytes, bytes);
+
+        let size = store.car_size(&roots[0])?;
+        assert_eq!(store.stats().roots.get(&roots[0].to_string()), Some(&size));
+
+        // stats survive reopening the store
+        store.persist_stats()?;
+        let reopened = UrsaStore::new(store.db.clone());
+        assert_eq!(reopened.stats(), store.stats());
+
+        let (cid, data) = &dag[0];
+        store.delete_block(cid)?;
+        let stats = store.stats();
+        assert_eq!(stats.blocks, dag.len() as u64 - 1);
+        assert_eq!(stats.bytes, bytes - data.len() as u64);
+        assert!(stats.roots.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_import_car() -> anyhow::Result<()> {
+        let store = get_store();
+
+        let path = Path::new("../../test_files/test.car");
+        let roots = store
+            .load_car(BufReader::new(File::open(path).await?))
+            .await?;
+
+        let mut car = Vec::new();
+        store.export_car(&roots[0], &mut car).await?;
+        assert_eq!(car.len() as u64, store.car_size(&roots[0])?);
+
+        let imported = get_store();
+        assert_eq!(imported.load_car(car.as_slice()).await?, roots);
+        // the size of the imported blocks is the size of the car file
+        let dag: Vec<_> = imported
+            .dag_traversal(&roots[0])?
+            .into_iter()
+            .map(|(cid, data)| (cid, data.len()))
+            .collect();
+        assert_eq!(imported.insert_car_size(&roots[0], &dag)?, car.len() as u64);
+        assert_eq!(
+            imported.dag_traversal(&roots[0])?,
+            store.dag_traversal(&roots[0])?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_pending_announcements() -> anyhow::Result<()> {
+        let store = get_store();
+        let first = Cid::new_v1(RAW, Code::Sha2_256.digest(b"first"));
+        let second = Cid::new_v1(RAW, Code::Sha2_256.digest(b"second"));
+
+        assert!(store.pending_announcements()?.is_empty());
+        store.add_pending_announcements(&[first, second])?;
+        store.add_pending_announcements(&[first])?;
+        assert_eq!(store.pending_announcements()?, vec![first, second]);
+
+        store.remove_pending_announcement(&first)?;
+        assert_eq!(store.pending_announcements()?, vec![second]);
+        store.remove_pending_announcement(&second)?;
+        assert!(store.pending_announcements()?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_block_cache() -> anyhow::Result<()> {
+        let store = UrsaStore::with_block_cache(Arc::new(MemoryDB::default()), 1024 * 1024);
+
+        let path = Path::new("../../test_files/test.car");
+        let roots = store
+            .load_car(BufReader::new(File::open(path).await?))
+            .await?;
+        let dag = store.dag_traversal(&roots[0])?;
+
+        // blocks read once are served from the cache
+        let (cid, data) = &dag[0];
+        store.db.delete(cid.to_bytes())?;
+        assert_eq!(store.get_block(cid)?.as_ref(), Some(data));
+        assert!(store.has_block(cid)?);
+
+        // deleting through the store invalidates the cache
+        store.delete_block(cid)?;
+        assert_eq!(store.get_block(cid)?, None);
+        assert!(!store.has_block(cid)?);
+
+        // a read that started before a delete doesn't fill the cache
+        let cache = BlockCache::new(1024 * 1024);
+        let generation = cache.generation();
+        cache.invalidate(cid);
+        cache.fill(*cid, data, generation);
+        assert!(!cache.contains(cid));
+        cache.fill(*cid, data, cache.generation());
+        assert!(cache.contains(cid));
+        Ok(())
+    }
+
+    #[test]
+    fn test_codec_aware_cids() -> anyhow::Result<()> {
+        let store = get_store();
+        let db = store.blockstore();
+
+        let raw = db.put_raw(b"hello world".to_vec(), Code::Sha2_256)?;
+        assert_eq!(raw.codec(), RAW);
+        assert_eq!(
+            raw.to_string(),
+            "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        );
+
+        let value = vec!["ursa".to_string(), "bear".to_string()];
+        let cbor = db.put_obj(&value, Code::Sha2_256)?;
+        assert_eq!(cbor.codec(), DAG_CBOR);
+        assert_eq!(db.get_obj::<Vec<String>>(&cbor)?, Some(value.clone()));
+
+        let json = db.put_json(&value, Code::Sha2_256)?;
+        assert_eq!(json.codec(), DAG_JSON);
+        assert_eq!(db.get(&json)?, Some(br#"["ursa","bear"]"#.to_vec()));
+        assert_eq!(db.get_obj::<Vec<String>>(&json)?, Some(value.clone()));
+
+        // raw blocks are decoded as cbor
+        let raw = db.put_raw(to_vec(&value)?, Code::Sha2_256)?;
+        assert_eq!(db.get_obj::<Vec<String>>(&raw)?, Some(value));
+        Ok(())
+    }
+
+    #[test]
+    fn test_cid_versions() -> anyhow::Result<()> {
+        let v0 = Cid::from_str("QmbWqxBEKC3P8tqsKc98xmWNzrzDtRLMiMPL8wBuTGsMnR")?;
+        let v1 = cid_to_v1(&v0);
+        assert_eq!(
+            v1.to_string(),
+            "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi"
+        );
+        assert_eq!(cid_to_v0(&v1)?, v0);
+
+        let raw = Cid::new_v1(RAW, Code::Sha2_256.digest(b"hello world"));
+        assert!(cid_to_v0(&raw).is_err());
+        Ok(())
+    }
 }
@@ -1,22 +1,22 @@
 use crate::{config::UrsaConfig, ursa::identity::IdentityManager};
 use anyhow::{bail, Result};
-use db::{rocks::RocksDb, rocks_config::RocksDbConfig};
 use dotenv::dotenv;
 use resolve_path::PathResolveExt;
 use scopeguard::defer;
 use std::sync::Arc;
-use std::{env, net::SocketAddr};
+use std::{env, net::SocketAddr, time::Duration};
 use structopt::StructOpt;
 use tokio::sync::mpsc::channel;
 use tokio::task;
+use tokio::time::timeout;
 use tracing::{error, info};
-use ursa::{Cli, Subcommand};
+use ursa::{rpc_client, Cli, Subcommand};
 use ursa_application::application_start;
 use ursa_consensus::{consensus::Consensus, Engine};
 use ursa_index_provider::engine::ProviderEngine;
 use ursa_network::UrsaService;
-use ursa_rpc_service::{api::NodeNetworkInterface, server::Server};
-use ursa_store::UrsaStore;
+use ursa_rpc_service::{api::NodeNetworkInterface, auth::Auth, origin::Origins, server::Server};
+use ursa_store::{Backend, UrsaStore};
 use ursa_telemetry::TelemetryConfig;
 use ursa_utils::shutdown::ShutdownController;
 
@@ -59,10 +59,11 @@ async fn run() -> Result<()> {
         }
     };
 
-    if let Some(Subcommand::Rpc(cmd)) = cmd {
-        // TODO(qti3e) cmd.run should return a Result.
-        cmd.run().await;
-        return Ok(());
+    match cmd {
+        Some(Subcommand::Rpc(cmd)) => return cmd.run(&rpc_client(&config)?).await,
+        Some(Subcommand::Store(cmd)) => return cmd.run(config).await,
+        Some(Subcommand::Token(cmd)) => return cmd.run(config),
+        None => {}
     }
 
     let UrsaConfig {
@@ -85,32 +86,47 @@ async fn run() -> Result<()> {
     };
 
     let keypair = im.current();
+    let public_key = keypair.public();
 
     let db_path = network_config.database_path.resolve().to_path_buf();
-    info!("Opening blockstore database at {:?}", db_path);
+    info!(
+        "Opening {:?} blockstore database at {:?}",
+        network_config.storage_backend, db_path
+    );
 
-    let db = RocksDb::open(db_path, &RocksDbConfig::default())
-        .expect("Opening blockstore RocksDB must succeed");
-    let store = Arc::new(UrsaStore::new(Arc::clone(&Arc::new(db))));
+    let db = Backend::open(network_config.storage_backend, db_path)
+        .expect("Opening blockstore database must succeed");
+    let store = Arc::new(UrsaStore::with_block_cache(
+        Arc::new(db),
+        network_config.block_cache_size,
+    ));
+    store
+        .reconcile_stats()
+        .expect("Counting the blocks of the blockstore must succeed");
     let (event_sender, event_receiver) = channel(4096);
     let service = UrsaService::new(
         keypair.clone(),
         &network_config,
         Arc::clone(&store),
         event_sender,
-    )?;
+    )?
+    .with_state_path(network_config.state_path.resolve());
 
-    let provider_db = RocksDb::open(
+    let provider_db = Backend::open(
+        provider_config.storage_backend,
         provider_config.database_path.resolve(),
-        &RocksDbConfig::default(),
     )
-    .expect("Opening provider RocksDB must succeed");
+    .expect("Opening provider database must succeed");
 
-    let index_store = Arc::new(UrsaStore::new(Arc::clone(&Arc::new(provider_db))));
+    let index_store =
+        Arc::new(UrsaStore::new(Arc::clone(&Arc::new(provider_db))).with_name("index_provider"));
+    index_store
+        .reconcile_stats()
+        .expect("Counting the blocks of the provider database must succeed");
     let index_provider_engine = ProviderEngine::new(
         keypair,
         Arc::clone(&store),
-        index_store,
+        Arc::clone(&index_store),
         provider_config,
         service.command_sender(),
         server_config.addresses.clone(),
@@ -149,6 +165,7 @@ async fn run() -> Result<()> {
     let tx_abci_queries = abci_engine.get_abci_queries_sender();
     let tx_certificates = abci_engine.get_certificates_sender();
     let reconfigure_notify = abci_engine.get_reconfigure_notify();
+    let new_blocks = abci_engine.get_new_blocks_sender();
 
     // Spawn engine.
     let engine_shutdown = shutdown_controller.clone();
@@ -164,17 +181,22 @@ async fn run() -> Result<()> {
         store,
         service.command_sender(),
         index_provider_engine.command_sender(),
-        server_config.origin.clone(),
+        Origins::from_config(&server_config.origin)?,
         mempool_address_string.clone(),
         tx_abci_queries.clone(),
+        service.event_broadcast(),
+        new_blocks,
     ));
 
-    let server = Server::new(interface);
+    let server = Server::new(
+        Arc::clone(&interface),
+        Auth::new(public_key, server_config.auth.clone()),
+    );
 
     // Start libp2p service.
     let shutdown = shutdown_controller.clone();
-    let service_task = task::spawn(async move {
-        if let Err(err) = service.start().await {
+    let mut service_task = task::spawn(async move {
+        if let Err(err) = service.start_with_shutdown(shutdown.clone()).await {
             error!("[service_task] - {:?}", err);
             shutdown.shutdown();
         }
@@ -204,6 +226,13 @@ async fn run() -> Result<()> {
         }
     });
 
+    // Announce roots imported with `ursa store import` while the node was offline.
+    let announce_task = task::spawn(async move {
+        if let Err(err) = interface.announce_pending().await {
+            error!("[announce_task] - {:?}", err);
+        }
+    });
+
     // Start the consensus service.
     let consensus_handle = task::spawn(async move {
         let mut consensus_service = Consensus::new(
@@ -220,11 +249,23 @@ async fn run() -> Result<()> {
     // Wait for the shutdown.
     shutdown_controller.wait_for_shutdown().await;
 
-    // Gracefully shutdown node & rpc.
+    // Give the libp2p service time to flush its state and close connections.
+    if timeout(Duration::from_secs(10), &mut service_task)
+        .await
+        .is_err()
+    {
+        error!("[service_task] - timed out while shutting down");
+        service_task.abort();
+    }
+
+    // Shutdown the remaining services.
     rpc_task.abort();
-    service_task.abort();
     provider_task.abort();
+    announce_task.abort();
     application_task.abort();
     consensus_handle.abort();
+    if let Err(err) = index_store.persist_stats() {
+        error!("Failed to persist the provider database stats: {err:?}");
+    }
     Ok(())
 }
@@ -4,10 +4,15 @@ use dirs::home_dir;
 use resolve_path::PathResolveExt;
 use rpc_commands::RpcCommands;
 use std::path::PathBuf;
+use store_commands::StoreCommands;
 use structopt::StructOpt;
+use token_commands::TokenCommands;
+use ursa_rpc_service::client::UrsaClient;
 
 pub mod identity;
 mod rpc_commands;
+mod store_commands;
+mod token_commands;
 
 /// CLI structure generated when interacting with URSA binary
 #[derive(StructOpt)]
@@ -28,6 +33,10 @@ pub struct Cli {
 pub enum Subcommand {
     #[structopt(name = "rpc", about = "run rpc commands from cli")]
     Rpc(RpcCommands),
+    #[structopt(name = "store", about = "inspect and repair the local block store")]
+    Store(StoreCommands),
+    #[structopt(name = "token", about = "manage api keys of the rpc server")]
+    Token(TokenCommands),
 }
 
 /// CLI options
@@ -39,6 +48,11 @@ pub struct CliOpts {
     pub rpc: bool,
     #[structopt(short = "p", long, help = "Port used for JSON-RPC communication")]
     pub rpc_port: Option<u16>,
+    #[structopt(
+        long,
+        help = "Port of the admin listener, also used by the rpc subcommands"
+    )]
+    pub admin_port: Option<u16>,
     #[structopt(
         short,
         long,
@@ -60,7 +74,16 @@ impl CliOpts {
         if let Some(rpc_port) = self.rpc_port {
             config.server_config.port = rpc_port;
         }
+        if let Some(admin_port) = self.admin_port {
+            config.server_config.admin_port = admin_port;
+        }
 
         Ok(config)
     }
 }
+
+/// Client of the local node for the `rpc` subcommands, connecting to the admin listener of
+/// `config` unless an endpoint is set in the environment.
+pub fn rpc_client(config: &UrsaConfig) -> Result<UrsaClient> {
+    Ok(UrsaClient::from_config(&config.server_config)?)
+}
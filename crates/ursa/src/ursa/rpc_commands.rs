@@ -1,9 +1,11 @@
+use anyhow::{anyhow, Result};
 use structopt::StructOpt;
-use tracing::{error, info};
+use tracing::info;
 use ursa_rpc_service::{
     api::{NetworkGetFileParams, NetworkPutFileParams},
-    client::functions::{eth_call, eth_send_transaction, get_file, put_file},
+    client::UrsaClient,
 };
+use ursa_store::{DagScope, DagSelection};
 use ursa_utils::transactions::build_transaction;
 
 #[derive(Debug, StructOpt)]
@@ -21,6 +23,12 @@ pub enum RpcCommands {
         cid: String,
         #[structopt(about = "The path to store the file")]
         path: String,
+        #[structopt(long, help = "Part of the dag to get: all (default), entity or block")]
+        dag_scope: Option<DagScope>,
+        #[structopt(long, help = "Only get blocks at most this many links below the root")]
+        depth: Option<u64>,
+        #[structopt(long, help = "An IPLD selector encoded as dag-json")]
+        selector: Option<String>,
     },
 
     // Example 'ursa rpc txn 0xAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAB "myFunction(string,uint256):(uint256) param1 1"
@@ -49,80 +57,56 @@ pub enum RpcCommands {
 }
 
 impl RpcCommands {
-    pub async fn run(&self) {
+    pub async fn run(&self, client: &UrsaClient) -> Result<()> {
         match self {
             Self::Put { path } => {
                 let params = NetworkPutFileParams {
                     path: path.to_string(),
                 };
-                match put_file(params).await {
-                    Ok(file) => {
-                        info!("Put car file done: {:?}", file);
-                    }
-                    Err(_e) => {
-                        error!("There was an error while calling the rpc server. Please Check Server Logs")
-                    }
-                };
+                let file = client.put_file(params).await?;
+                info!("Put car file done: {:?}", file);
             }
-            Self::Get { cid, path } => {
+            Self::Get {
+                cid,
+                path,
+                dag_scope,
+                depth,
+                selector,
+            } => {
                 let params = NetworkGetFileParams {
                     path: path.to_string(),
                     cid: cid.to_string(),
+                    selection: DagSelection {
+                        dag_scope: *dag_scope,
+                        depth: *depth,
+                        selector: selector.clone(),
+                    },
                 };
-                match get_file(params).await {
-                    Ok(_result) => {
-                        info!("file stored at {path:?}");
-                    }
-                    Err(_e) => {
-                        error!("There was an error while calling the rpc server. Please Check Server Logs")
-                    }
-                };
+                client.get_file(params).await?;
+                info!("file stored at {path:?}");
             }
             Self::Txn {
                 address,
                 function,
                 args,
             } => {
-                let txn = match build_transaction(address, function, args) {
-                    Ok((_, txn)) => txn,
-                    Err(e) => {
-                        error!("{e:?}");
-                        return;
-                    }
-                };
-
-                match eth_send_transaction(txn).await {
-                    Ok(_result) => {
-                        info!("transaction submitted");
-                    }
-                    Err(_e) => {
-                        error!("There was an error while calling the rpc server. Please Check Server Logs")
-                    }
-                };
+                let (_, txn) = build_transaction(address, function, args)?;
+                let hash = client.eth_send_transaction(txn).await?;
+                info!("transaction {hash:?} submitted");
             }
             Self::Call {
                 address,
                 function,
                 args,
             } => {
-                let (function_abi, txn) = match build_transaction(address, function, args) {
-                    Ok((func, txn)) => (func, txn),
-                    Err(e) => {
-                        error!("{e:?}");
-                        return;
-                    }
-                };
-
-                match eth_call(txn).await {
-                    Ok(result) => match function_abi.decode_output(&result) {
-                        Ok(tokens) => info!("Returned data is: {tokens:?}"),
-                        Err(_) => error!("Error decoding output: {result:?}"),
-                    },
-                    Err(_e) => {
-                        error!("There was an error while calling the rpc server. Please Check Server Logs")
-                    }
-                };
+                let (function_abi, txn) = build_transaction(address, function, args)?;
+                let result = client.eth_call(txn).await?;
+                let tokens = function_abi
+                    .decode_output(&result)
+                    .map_err(|_| anyhow!("Error decoding output: {result:?}"))?;
+                info!("Returned data is: {tokens:?}");
             }
         }
+        Ok(())
     }
 }
@@ -0,0 +1,183 @@
+use crate::{config::UrsaConfig, ursa::identity::IdentityManager};
+use anyhow::{anyhow, Result};
+use futures::{future, io::AllowStdIo};
+use libipld::Cid;
+use resolve_path::PathResolveExt;
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+use structopt::StructOpt;
+use tracing::{error, info, warn};
+use ursa_index_provider::provider::Provider;
+use ursa_network::NetworkConfig;
+use ursa_rpc_service::origin::Origins;
+use ursa_store::{Backend, UrsaStore, VerifyReport};
+
+#[derive(Debug, StructOpt)]
+pub enum StoreCommands {
+    #[structopt(about = "check every block in the store and the dags of all advertised roots")]
+    Verify {
+        #[structopt(
+            long,
+            help = "Delete corrupt blocks and re-fetch incomplete dags from the origin"
+        )]
+        repair: bool,
+    },
+    #[structopt(about = "export the dags of the given roots as car files")]
+    Export {
+        #[structopt(long, help = "A file with one root cid per line")]
+        roots: PathBuf,
+        #[structopt(long, help = "The directory to write the car files to")]
+        out: PathBuf,
+    },
+    #[structopt(about = "import car files, announcing their roots on the next start")]
+    Import {
+        #[structopt(about = "The car files to import")]
+        files: Vec<PathBuf>,
+    },
+}
+
+impl StoreCommands {
+    pub async fn run(&self, config: UrsaConfig) -> Result<()> {
+        match self {
+            Self::Verify { repair } => verify(config, *repair).await,
+            Self::Export { roots, out } => export(config, roots, out).await,
+            Self::Import { files } => import(config, files).await,
+        }
+    }
+}
+
+async fn verify(config: UrsaConfig, repair: bool) -> Result<()> {
+    let UrsaConfig {
+        network_config,
+        provider_config,
+        server_config,
+        ..
+    } = config;
+
+    let store = open_store(&network_config)?;
+
+    let roots = {
+        let provider_db = Backend::open(
+            provider_config.storage_backend,
+            provider_config.database_path.resolve(),
+        )?;
+        let index_store = Arc::new(UrsaStore::new(Arc::new(provider_db)));
+        let provider = Provider::new(IdentityManager::random().current(), index_store);
+
+        let mut roots: BTreeSet<Cid> = provider.advertised_roots()?.into_iter().collect();
+        for root in store.stats().roots.keys() {
+            match Cid::from_str(root) {
+                Ok(cid) => {
+                    roots.insert(cid);
+                }
+                Err(e) => warn!("Skipping invalid root {root} in store stats: {e}"),
+            }
+        }
+        roots.into_iter().collect::<Vec<_>>()
+    };
+
+    info!("Verifying store against {} roots", roots.len());
+    let report = store.verify(&roots)?;
+    log_report(&report);
+
+    if report.is_ok() {
+        return Ok(());
+    }
+    if !repair {
+        return Err(anyhow!("Store verification failed"));
+    }
+
+    for cid in &report.corrupt {
+        info!("Deleting corrupt block {cid}");
+        store.delete_block(cid)?;
+    }
+
+    // Roots that pointed at a deleted block are now incomplete as well.
+    let incomplete: Vec<Cid> = roots
+        .iter()
+        .filter(|root| store.dag_traversal(root).is_err())
+        .copied()
+        .collect();
+
+    // Repair with a plain origin client: starting the network service here would bind
+    // the node's ports and overwrite its persisted network state.
+    let origins = Origins::from_config(&server_config.origin)?;
+    for root in &incomplete {
+        let put = |blocks: Vec<(Cid, Vec<u8>)>| future::ready(store.put_blocks(blocks));
+        match origins.fetch(*root, put).await {
+            Ok(()) => info!("Repaired {root} from origin"),
+            Err(e) => error!("Failed to repair {root}: {e:?}"),
+        }
+    }
+
+    let report = store.verify(&roots)?;
+    log_report(&report);
+    if report.is_ok() {
+        Ok(())
+    } else {
+        Err(anyhow!("Store is still inconsistent after repair"))
+    }
+}
+
+async fn export(config: UrsaConfig, roots: &Path, out: &Path) -> Result<()> {
+    let store = open_store(&config.network_config)?;
+    let roots = fs::read_to_string(roots)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(Cid::from_str)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    fs::create_dir_all(out)?;
+    for root in roots {
+        let path = out.join(format!("{root}.car"));
+        let mut writer = AllowStdIo::new(BufWriter::new(File::create(&path)?));
+        store.export_car(&root, &mut writer).await?;
+        writer.into_inner().flush()?;
+        info!("Exported {root} to {path:?}");
+    }
+    Ok(())
+}
+
+async fn import(config: UrsaConfig, files: &[PathBuf]) -> Result<()> {
+    let store = open_store(&config.network_config)?;
+    for path in files {
+        let reader = AllowStdIo::new(BufReader::new(File::open(path)?));
+        let roots = store.load_car(reader).await?;
+        store.add_pending_announcements(&roots)?;
+        info!("Imported {path:?} with roots {roots:?}");
+    }
+    store.persist_stats()
+}
+
+fn open_store(config: &NetworkConfig) -> Result<Arc<UrsaStore<Backend>>> {
+    let db = Backend::open(config.storage_backend, config.database_path.resolve())?;
+    let store = UrsaStore::new(Arc::new(db));
+    store.reconcile_stats()?;
+    Ok(Arc::new(store))
+}
+
+fn log_report(report: &VerifyReport) {
+    info!(
+        "Checked {} blocks: {} corrupt, {} unsupported hashes, {} incomplete roots",
+        report.blocks,
+        report.corrupt.len(),
+        report.unsupported.len(),
+        report.incomplete_roots.len()
+    );
+    for cid in &report.corrupt {
+        warn!("Corrupt block: {cid}");
+    }
+    for cid in &report.unsupported {
+        warn!("Unverifiable block: {cid}");
+    }
+    for cid in &report.incomplete_roots {
+        warn!("Incomplete dag: {cid}");
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{config::UrsaConfig, ursa::identity::IdentityManager};
+use anyhow::{anyhow, Result};
+use resolve_path::PathResolveExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use structopt::StructOpt;
+use ursa_rpc_service::auth::{issue_token, key_id, Claims, Scope};
+
+#[derive(Debug, StructOpt)]
+pub enum TokenCommands {
+    #[structopt(about = "create an api key signed by the node identity")]
+    Create {
+        #[structopt(
+            long,
+            use_delimiter = true,
+            default_value = "read",
+            help = "Comma separated scopes: read, write or admin"
+        )]
+        scopes: Vec<Scope>,
+        #[structopt(
+            long,
+            default_value = "2592000",
+            help = "Seconds until the key expires, 30 days by default"
+        )]
+        expires_in: u64,
+        #[structopt(long, help = "Create a key that never expires")]
+        no_expiry: bool,
+        #[structopt(long, help = "A label for the key holder")]
+        name: Option<String>,
+    },
+}
+
+impl TokenCommands {
+    pub fn run(&self, config: UrsaConfig) -> Result<()> {
+        match self {
+            Self::Create {
+                scopes,
+                expires_in,
+                no_expiry,
+                name,
+            } => {
+                let network_config = config.network_config;
+                if network_config.identity == "random" {
+                    return Err(anyhow!(
+                        "Can't sign keys with a random identity, configure a persistent one"
+                    ));
+                }
+                let im = IdentityManager::load(
+                    network_config.identity.clone(),
+                    network_config.keystore_path.resolve().to_path_buf(),
+                )
+                .ok_or_else(|| anyhow!("Identity {} not found", network_config.identity))?;
+
+                let exp = (!no_expiry).then(|| {
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs()
+                        + expires_in
+                });
+                let claims = Claims {
+                    scopes: scopes.clone(),
+                    exp,
+                    name: name.clone(),
+                };
+                let token = issue_token(&im.current(), &claims)?;
+                // the id goes to stderr so the key can be piped on its own
+                eprintln!("Key id (for revoked_keys): {}", key_id(&token));
+                println!("{token}");
+                Ok(())
+            }
+        }
+    }
+}